@@ -0,0 +1,83 @@
+use crate::{
+    blocks::{file_object_url, walk_blocks},
+    rich_text::raw_plain_text,
+};
+use notion::models::block::Block;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// image captions keyed by the image url
+// the notion crate doesn't model them, so they are read from the raw blocks
+pub fn image_captions(raw: &[Value]) -> HashMap<String, String> {
+    let mut captions = HashMap::new();
+    collect_captions(raw, &mut captions);
+    captions
+}
+
+fn collect_captions(raw: &[Value], captions: &mut HashMap<String, String>) {
+    for block in raw {
+        let block_type = block["type"].as_str().unwrap_or_default();
+        let content = &block[block_type];
+        if block_type == "image" {
+            let file_type = content["type"].as_str().unwrap_or_default();
+            let url = content[file_type]["url"].as_str().unwrap_or_default();
+            let caption = raw_plain_text(&content["caption"]);
+            if !url.is_empty() && !caption.is_empty() {
+                captions.insert(url.to_owned(), caption);
+            }
+        }
+        if let Some(children) = content["children"].as_array() {
+            collect_captions(children, captions);
+        }
+    }
+}
+
+// runs the configured command for every image without a caption
+// the image url is passed as the first argument and stdout is used as the alt text
+pub async fn generate_alt_texts(
+    blocks: &[Block],
+    captions: &HashMap<String, String>,
+    command: &str,
+) -> HashMap<String, String> {
+    let mut urls = vec![];
+    walk_blocks(blocks, &mut |block| {
+        if let Block::Image { image, .. } = block {
+            let url = file_object_url(image);
+            if !captions.contains_key(&url) {
+                urls.push(url);
+            }
+        }
+    });
+
+    let mut alt_texts = HashMap::new();
+    for url in urls {
+        match run_alt_text_command(command, &url).await {
+            Ok(alt_text) if !alt_text.is_empty() => {
+                alt_texts.insert(url, alt_text);
+            }
+            Ok(_) => {}
+            Err(error) => eprintln!("Alt text command failed for {url} with error {error:?}"),
+        }
+    }
+    alt_texts
+}
+
+async fn run_alt_text_command(command: &str, url: &str) -> anyhow::Result<String> {
+    let output = tokio::process::Command::new("sh")
+        .args(["-c", command, "sh", url])
+        .output()
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!("alt text command exited with {}", output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+// alt text has to stay on one line and can't close the image brackets
+pub fn sanitize_alt_text(alt_text: &str) -> String {
+    alt_text
+        .replace(['[', ']'], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,508 @@
+use crate::{
+    configuration::{AppConfig, RetryConfig},
+    recording::Recording,
+};
+use anyhow::{bail, Context};
+use notion::{
+    ids::{BlockId, DatabaseId, PageId},
+    models::{
+        block::Block,
+        paging::Pageable,
+        search::{DatabaseQuery, SearchRequest},
+        Database, ListResponse, Object, Page,
+    },
+};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+use tokio::{sync::Mutex, time::Instant};
+
+// notion documents an average of three requests per second per integration
+const REQUESTS_PER_SECOND: f64 = 3.0;
+
+// every request goes through our own http client so the base url can point at a mock or proxy
+// the notion crate is only used for its models and ids
+pub const NOTION_API_URL: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-06-28";
+
+// one integration token, every source has its own rate limiter
+pub struct Source {
+    // pages of named sources go into a subdirectory of the output
+    pub name: Option<String>,
+    pub client: NotionClient,
+}
+
+pub fn connect_sources(
+    config: &AppConfig,
+    recording: Option<&Recording>,
+) -> anyhow::Result<Vec<Source>> {
+    let connect = |name: Option<&str>, api_key: &str| -> anyhow::Result<Source> {
+        let mut client =
+            NotionClient::new(api_key.to_owned(), config.api_base_url(), config.retry)?;
+        client.recording = recording.map(|recording| recording.for_source(name));
+        client.read_only = config.read_only;
+        Ok(Source {
+            name: name.map(str::to_owned),
+            client,
+        })
+    };
+    if config.sources.is_empty() {
+        // replays don't talk to notion so they work without a token
+        let replay = recording.map(Recording::is_replay).unwrap_or(false);
+        if config.notion_api_key.is_empty() && !replay {
+            bail!("no notion api key configured, run with --save-token first");
+        }
+        return Ok(vec![connect(None, &config.notion_api_key)?]);
+    }
+    config
+        .sources
+        .iter()
+        .map(|source| connect(Some(&source.name), &source.notion_api_key))
+        .collect()
+}
+
+// a failed request to one of the raw endpoints
+#[derive(Debug)]
+pub struct ApiStatusError {
+    pub path: String,
+    pub status: u16,
+    // like `object_not_found`
+    pub code: String,
+    pub message: String,
+}
+
+impl ApiStatusError {
+    // the usual causes, notion's own messages don't mention them
+    pub fn hint(&self) -> Option<&'static str> {
+        match (self.status, self.code.as_str()) {
+            (404, _) | (_, "object_not_found") => Some(
+                "pages have to be shared with the integration, add it under connections in the page menu of the page or one of its parents",
+            ),
+            (401, _) | (_, "unauthorized") => {
+                Some("the token is invalid or was revoked, save a new one with --save-token")
+            }
+            (403, _) | (_, "restricted_resource") => {
+                Some("the integration is missing a capability, check them in its settings")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "notion api request to {} failed with {}: {}",
+            self.path, self.status, self.message
+        )?;
+        if let Some(hint) = self.hint() {
+            write!(f, " (hint: {hint})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiStatusError {}
+
+// the named source or the first one when no name is given
+pub fn find_source<'a>(sources: &'a [Source], name: Option<&str>) -> anyhow::Result<&'a Source> {
+    match name {
+        Some(name) => sources
+            .iter()
+            .find(|source| source.name.as_deref() == Some(name))
+            .with_context(|| format!("unknown source {name}")),
+        None => sources.first().context("no sources configured"),
+    }
+}
+
+// every request goes through the same rate limiter and retries
+pub struct NotionClient {
+    base_url: String,
+    api_key: String,
+    http: reqwest::Client,
+    rate_limiter: RateLimiter,
+    recording: Option<Recording>,
+    // refuse every request that would change the workspace
+    read_only: bool,
+    retry: RetryConfig,
+}
+
+impl NotionClient {
+    pub fn new(api_key: String, base_url: &str, retry: RetryConfig) -> anyhow::Result<Self> {
+        // a stalled connection times out and is retried like a dropped one
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(retry.request_timeout_ms))
+            .build()?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            api_key,
+            http,
+            rate_limiter: RateLimiter::new(REQUESTS_PER_SECOND),
+            recording: None,
+            read_only: false,
+            retry,
+        })
+    }
+
+    // lets commands fail before they prompt or do any work instead of halfway through
+    pub fn ensure_writable(&self) -> anyhow::Result<()> {
+        if self.read_only {
+            bail!("read-only mode is on, refusing to change anything in notion (--read-only or read_only in the config)");
+        }
+        Ok(())
+    }
+
+    pub async fn create_page(&self, body: &Value) -> anyhow::Result<Value> {
+        self.send_raw(Method::POST, "pages", Some(body)).await
+    }
+
+    // the raw page keeps property types the notion crate doesn't model, like wiki verification
+    pub async fn get_page_json(&self, page_id: &PageId) -> anyhow::Result<Value> {
+        self.send_raw(Method::GET, &format!("pages/{page_id}"), None)
+            .await
+    }
+
+    pub async fn update_page_properties(
+        &self,
+        page_id: &PageId,
+        properties: &Value,
+    ) -> anyhow::Result<Value> {
+        let path = format!("pages/{page_id}");
+        let body = json!({ "properties": properties });
+        self.send_raw(Method::PATCH, &path, Some(&body)).await
+    }
+
+    // all comments on a block or page, only open discussions are returned
+    pub async fn list_comments(&self, block_id: &str) -> anyhow::Result<Vec<Value>> {
+        let mut comments = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut path = format!("comments?block_id={block_id}&page_size=100");
+            if let Some(cursor) = &cursor {
+                path.push_str(&format!("&start_cursor={cursor}"));
+            }
+            let response = self.send_raw(Method::GET, &path, None).await?;
+            if let Some(results) = response.get("results").and_then(Value::as_array) {
+                comments.extend(results.iter().cloned());
+            }
+            cursor = response
+                .get("next_cursor")
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(comments)
+    }
+
+    pub async fn get_user_name(&self, user_id: &str) -> anyhow::Result<Option<String>> {
+        let user = self
+            .send_raw(Method::GET, &format!("users/{user_id}"), None)
+            .await?;
+        Ok(user.get("name").and_then(Value::as_str).map(str::to_owned))
+    }
+
+    // archived pages end up in the trash and can be restored from there
+    pub async fn archive_page(&self, page_id: &PageId) -> anyhow::Result<Value> {
+        let path = format!("pages/{page_id}");
+        let body = json!({ "archived": true });
+        self.send_raw(Method::PATCH, &path, Some(&body)).await
+    }
+
+    pub async fn append_block_children(
+        &self,
+        block_id: &BlockId,
+        children: Vec<Value>,
+    ) -> anyhow::Result<Value> {
+        let path = format!("blocks/{block_id}/children");
+        let body = json!({ "children": children });
+        self.send_raw(Method::PATCH, &path, Some(&body)).await
+    }
+
+    // ids of the direct children of a block, following the cursor until the end
+    pub async fn list_block_child_ids(&self, block_id: &BlockId) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .list_block_children_json(block_id)
+            .await?
+            .iter()
+            .filter_map(|block| block.get("id").and_then(Value::as_str))
+            .map(str::to_owned)
+            .collect())
+    }
+
+    // the raw children keep fields the notion crate doesn't model, like column widths
+    pub async fn list_block_children_json(&self, block_id: &BlockId) -> anyhow::Result<Vec<Value>> {
+        let mut children = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut path = format!("blocks/{block_id}/children?page_size=100");
+            if let Some(cursor) = &cursor {
+                path.push_str(&format!("&start_cursor={cursor}"));
+            }
+            let response = self.send_raw(Method::GET, &path, None).await?;
+            if let Some(results) = response.get("results").and_then(Value::as_array) {
+                children.extend(results.iter().cloned());
+            }
+            cursor = response
+                .get("next_cursor")
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(children)
+    }
+
+    // deleted blocks are archived and can be restored from the page history
+    pub async fn delete_block(&self, block_id: &str) -> anyhow::Result<Value> {
+        self.send_raw(Method::DELETE, &format!("blocks/{block_id}"), None)
+            .await
+    }
+
+    async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> anyhow::Result<Value> {
+        if is_write(&method, path) {
+            self.ensure_writable()?;
+        }
+        if let Some(recording) = &self.recording {
+            if let Some((status, response_body)) = recording.replay(&method, path, body)? {
+                return status_result(path, status, response_body);
+            }
+        }
+        let write = is_write(&method, path);
+        let mut attempt = 0;
+        let (status, response_body) = loop {
+            self.rate_limiter.wait().await;
+            let mut request = self
+                .http
+                .request(method.clone(), format!("{}/{path}", self.base_url))
+                .bearer_auth(&self.api_key)
+                .header("Notion-Version", NOTION_VERSION);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            let retries_left = attempt < self.retry.max_retries;
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(error)
+                    if retries_left && !write && (error.is_timeout() || error.is_connect()) =>
+                {
+                    let pause = backoff(&self.retry, attempt);
+                    eprintln!("Request to {path} failed with error {error}, retrying in {pause:?}");
+                    tokio::time::sleep(pause).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(error) => return Err(error.into()),
+            };
+            let status = response.status().as_u16();
+            if retries_left && (status == 429 || (!write && status >= 500)) {
+                let retry_after = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<f64>().ok())
+                    .map(Duration::from_secs_f64);
+                let pause = retry_after.unwrap_or_else(|| backoff(&self.retry, attempt));
+                eprintln!("Notion answered {status} to {path}, retrying in {pause:?}");
+                if status == 429 {
+                    // the limit is per integration, every other request has to wait as well
+                    self.rate_limiter.pause(pause).await;
+                } else {
+                    tokio::time::sleep(pause).await;
+                }
+                attempt += 1;
+                continue;
+            }
+            // gateways answer errors with html, the status is what matters then
+            let text = response.text().await?;
+            let response_body = match serde_json::from_str(&text) {
+                Ok(response_body) => response_body,
+                Err(_) if !(200..300).contains(&status) => Value::Null,
+                Err(error) => {
+                    return Err(error).with_context(|| {
+                        format!("unexpected response from notion api request to {path}")
+                    })
+                }
+            };
+            break (status, response_body);
+        };
+        if let Some(recording) = &self.recording {
+            recording.record(&method, path, body, status, &response_body)?;
+        }
+        status_result(path, status, response_body)
+    }
+
+    async fn send_typed<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> anyhow::Result<T> {
+        let response = self.send_raw(method, path, body).await?;
+        serde_json::from_value(response)
+            .with_context(|| format!("unexpected response from notion api request to {path}"))
+    }
+
+    pub async fn search<T: Into<SearchRequest>>(
+        &self,
+        query: T,
+    ) -> anyhow::Result<ListResponse<Object>> {
+        let body = serde_json::to_value(query.into())?;
+        self.send_typed(Method::POST, "search", Some(&body)).await
+    }
+
+    pub async fn get_page(&self, page_id: PageId) -> anyhow::Result<Page> {
+        self.send_typed(Method::GET, &format!("pages/{page_id}"), None)
+            .await
+    }
+
+    pub async fn get_database(&self, database_id: DatabaseId) -> anyhow::Result<Database> {
+        self.send_typed(Method::GET, &format!("databases/{database_id}"), None)
+            .await
+    }
+
+    // the models don't keep formula expressions or rollup settings
+    pub async fn get_database_json(&self, database_id: &DatabaseId) -> anyhow::Result<Value> {
+        self.send_raw(Method::GET, &format!("databases/{database_id}"), None)
+            .await
+    }
+
+    pub async fn query_database(
+        &self,
+        database_id: DatabaseId,
+        query: DatabaseQuery,
+    ) -> anyhow::Result<ListResponse<Page>> {
+        let body = serde_json::to_value(query)?;
+        self.send_typed(
+            Method::POST,
+            &format!("databases/{database_id}/query"),
+            Some(&body),
+        )
+        .await
+    }
+
+    // every row of a database, following the cursor until the end
+    pub async fn query_all_rows(&self, database_id: DatabaseId) -> anyhow::Result<Vec<Page>> {
+        let mut rows = vec![];
+        let mut response = self
+            .query_database(database_id.clone(), DatabaseQuery::default())
+            .await?;
+        loop {
+            rows.extend(response.results);
+            match response.next_cursor {
+                Some(cursor) => {
+                    let query = DatabaseQuery::default().start_from(Some(cursor));
+                    response = self.query_database(database_id.clone(), query).await?;
+                }
+                None => break,
+            }
+        }
+        Ok(rows)
+    }
+
+    pub async fn get_block_children(
+        &self,
+        block_id: BlockId,
+    ) -> anyhow::Result<ListResponse<Block>> {
+        self.send_typed(Method::GET, &format!("blocks/{block_id}/children"), None)
+            .await
+    }
+
+    // one page of children as the api returned it, blocks the notion crate doesn't model keep every field
+    pub async fn get_block_children_json(
+        &self,
+        block_id: &BlockId,
+        cursor: Option<&str>,
+    ) -> anyhow::Result<Value> {
+        let path = match cursor {
+            Some(cursor) => format!("blocks/{block_id}/children?start_cursor={cursor}"),
+            None => format!("blocks/{block_id}/children"),
+        };
+        self.send_raw(Method::GET, &path, None).await
+    }
+}
+
+// search and database queries are reads even though they are posted
+fn is_write(method: &Method, path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or_default();
+    match *method {
+        Method::GET => false,
+        Method::POST => path != "search" && !path.ends_with("/query"),
+        _ => true,
+    }
+}
+
+// exponential with jitter so parallel requests don't all come back at the same moment
+fn backoff(retry: &RetryConfig, attempt: u32) -> Duration {
+    let ceiling = retry
+        .initial_backoff_ms
+        .saturating_mul(1 << attempt.min(20))
+        .min(retry.max_backoff_ms);
+    // randomly seeded by the standard library, good enough for jitter
+    let random = RandomState::new().build_hasher().finish();
+    let jitter = random % (ceiling / 2 + 1);
+    Duration::from_millis(ceiling - jitter)
+}
+
+fn status_result(path: &str, status: u16, response_body: Value) -> anyhow::Result<Value> {
+    if !(200..300).contains(&status) {
+        let field = |name| {
+            response_body
+                .get(name)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned()
+        };
+        return Err(ApiStatusError {
+            path: path.to_owned(),
+            status,
+            code: field("code"),
+            message: field("message"),
+        }
+        .into());
+    }
+    Ok(response_body)
+}
+
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    // nobody gets a slot before the pause is over
+    pub async fn pause(&self, pause: Duration) {
+        let mut next_slot = self.next_slot.lock().await;
+        *next_slot = (*next_slot).max(Instant::now() + pause);
+    }
+
+    // holding the lock while sleeping queues callers up in order
+    pub async fn wait(&self) {
+        let mut next_slot = self.next_slot.lock().await;
+        let now = Instant::now();
+        if *next_slot > now {
+            tokio::time::sleep_until(*next_slot).await;
+        }
+        *next_slot = (*next_slot).max(now) + self.interval;
+    }
+}
@@ -0,0 +1,149 @@
+use crate::api::NotionClient;
+use anyhow::Context;
+use futures::{future::BoxFuture, FutureExt};
+use notion::ids::BlockId;
+use serde_json::Value;
+use std::str::FromStr;
+
+// the api accepts at most this many children per append request, and in one children array
+pub const APPEND_CHUNK_SIZE: usize = 100;
+// and at most this many blocks in one request, nested ones included
+const REQUEST_BLOCK_LIMIT: usize = 1000;
+
+// a block to create and every block below it
+// blocks like tables and columns can't be created empty, their json carries the first children along
+// the rest of `children` is appended once the block exists
+#[derive(Debug)]
+pub struct NewBlock {
+    pub block: Value,
+    pub children: Vec<NewBlock>,
+}
+
+impl NewBlock {
+    pub fn new(block: Value) -> Self {
+        Self {
+            block,
+            children: vec![],
+        }
+    }
+
+    // how many of the children are sent along in the json of the block
+    fn inline_children(&self) -> usize {
+        let block_type = self.block["type"].as_str().unwrap_or_default();
+        self.block[block_type]["children"]
+            .as_array()
+            .map(Vec::len)
+            .unwrap_or_default()
+    }
+}
+
+// creates the blocks below the parent in order, children go below the blocks that were created for them
+pub fn append_blocks<'a>(
+    notion_client: &'a NotionClient,
+    parent_id: BlockId,
+    blocks: &'a [NewBlock],
+) -> BoxFuture<'a, anyhow::Result<()>> {
+    async move {
+        for chunk in request_chunks(blocks) {
+            let children = chunk.iter().map(|block| block.block.clone()).collect();
+            let response = notion_client
+                .append_block_children(&parent_id, children)
+                .await?;
+            let created = response
+                .get("results")
+                .and_then(Value::as_array)
+                .context("append response has no results")?;
+            // the response lists the new blocks in order
+            for (block, created_block) in chunk.iter().zip(created) {
+                if block.children.is_empty() {
+                    continue;
+                }
+                let created_id = created_block
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .context("created block has no id")?;
+                append_children(notion_client, BlockId::from_str(created_id)?, block).await?;
+            }
+        }
+        Ok(())
+    }
+    .boxed()
+}
+
+// `block` exists as `block_id`, children sent along only need their own children
+fn append_children<'a>(
+    notion_client: &'a NotionClient,
+    block_id: BlockId,
+    block: &'a NewBlock,
+) -> BoxFuture<'a, anyhow::Result<()>> {
+    async move {
+        let inline = block.inline_children().min(block.children.len());
+        let (sent, remaining) = block.children.split_at(inline);
+        if sent.iter().any(|child| !child.children.is_empty()) {
+            // the response only has the ids of the top level blocks, the nested ones are listed
+            let created_ids = notion_client.list_block_child_ids(&block_id).await?;
+            for (child, created_id) in sent.iter().zip(&created_ids) {
+                if !child.children.is_empty() {
+                    append_children(notion_client, BlockId::from_str(created_id)?, child).await?;
+                }
+            }
+        }
+        append_blocks(notion_client, block_id, remaining).await
+    }
+    .boxed()
+}
+
+// consecutive blocks that fit into one request
+fn request_chunks(blocks: &[NewBlock]) -> Vec<&[NewBlock]> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    let mut size = 0;
+    for (index, block) in blocks.iter().enumerate() {
+        let weight = block_count(&block.block);
+        if index > start
+            && (index - start == APPEND_CHUNK_SIZE || size + weight > REQUEST_BLOCK_LIMIT)
+        {
+            chunks.push(&blocks[start..index]);
+            start = index;
+            size = 0;
+        }
+        size += weight;
+    }
+    if start < blocks.len() {
+        chunks.push(&blocks[start..]);
+    }
+    chunks
+}
+
+// the block and every block sent along in its json
+fn block_count(block: &Value) -> usize {
+    let block_type = block["type"].as_str().unwrap_or_default();
+    let nested: usize = block[block_type]["children"]
+        .as_array()
+        .map(|children| children.iter().map(block_count).sum())
+        .unwrap_or_default();
+    1 + nested
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn requests_stay_below_the_block_limit() {
+        let rows: Vec<Value> = (0..APPEND_CHUNK_SIZE)
+            .map(|_| json!({ "type": "table_row", "table_row": { "cells": [] } }))
+            .collect();
+        let table = || NewBlock::new(json!({ "type": "table", "table": { "children": rows } }));
+        let mut blocks: Vec<NewBlock> = (0..12).map(|_| table()).collect();
+        blocks.extend((0..150).map(|_| NewBlock::new(json!({ "type": "divider", "divider": {} }))));
+
+        let sizes: Vec<usize> = request_chunks(&blocks)
+            .iter()
+            .map(|chunk| chunk.len())
+            .collect();
+        // 9 tables of 101 blocks, then 3 tables and 97 dividers, then the last 53 dividers
+        assert_eq!(sizes, vec![9, 100, 53]);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::{
+    blocks::{before_deadline, file_object_url, walk_blocks},
+    configuration::AttachmentsLayout,
+    escape,
+    state::AttachmentState,
+};
+use indicatif::ProgressBar;
+use notion::models::block::{Block, FileObject};
+use percent_encoding::percent_decode_str;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio::time::Instant;
+
+pub const ATTACHMENTS_DIR_NAME: &str = "attachments";
+
+pub fn attachments_dir(layout: AttachmentsLayout, output_dir: &Path, note_path: &Path) -> PathBuf {
+    let note_stem = note_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let note_dir = note_path.parent().unwrap_or(output_dir);
+    match layout {
+        AttachmentsLayout::Global => output_dir.join(ATTACHMENTS_DIR_NAME),
+        AttachmentsLayout::PerNote => note_dir.join(format!("{note_stem}.assets")),
+        AttachmentsLayout::Notion => note_dir.join(note_stem),
+    }
+}
+
+// downloaded files keyed by their url without the signature
+// the same file embedded several times is stored once and every embed keeps its own caption
+#[derive(Debug, Default)]
+pub struct AttachmentIndex {
+    files: HashMap<String, PathBuf>,
+    // the page timeout passed first, the rest keep linking to notion
+    pub truncated: bool,
+}
+
+impl AttachmentIndex {
+    pub fn get(&self, url: &str) -> Option<&PathBuf> {
+        self.files.get(attachment_key(url))
+    }
+}
+
+// notion signs file urls with a query that changes on every request, the path stays the same
+pub fn attachment_key(url: &str) -> &str {
+    url.split('?').next().unwrap_or_default()
+}
+
+// notion hosted files are signed urls that expire after an hour, so every kind of file is downloaded
+// external images are downloaded too, other external files like youtube videos stay links
+pub fn attachment_urls(blocks: &[Block]) -> Vec<String> {
+    let mut urls = vec![];
+    walk_blocks(blocks, &mut |block| match block {
+        Block::Image { image, .. } => urls.push(file_object_url(image)),
+        Block::Video { video: file, .. }
+        | Block::File { file, .. }
+        | Block::Pdf { pdf: file, .. } => {
+            if let FileObject::File { .. } = file {
+                urls.push(file_object_url(file));
+            }
+        }
+        _ => {}
+    });
+    urls
+}
+
+// downloads attachments into the attachments directory and returns the local path for each url
+// files are named after their content, identical uploads on different blocks or pages share one file
+// `known` remembers earlier downloads so unchanged files aren't fetched on every run
+// offline only the known files are used
+pub async fn download_attachments(
+    blocks: &[Block],
+    attachments_dir: &Path,
+    known: &mut HashMap<String, AttachmentState>,
+    progress: &ProgressBar,
+    offline: bool,
+    deadline: Option<Instant>,
+) -> anyhow::Result<AttachmentIndex> {
+    let urls = attachment_urls(blocks);
+    let mut index = AttachmentIndex::default();
+    if urls.is_empty() {
+        return Ok(index);
+    }
+    tokio::fs::create_dir_all(attachments_dir).await?;
+
+    let client = reqwest::Client::new();
+    for url in urls {
+        let key = attachment_key(&url);
+        if index.files.contains_key(key) {
+            continue;
+        }
+        // a page moved to another folder gets its own copy with per note layouts
+        let previous = known
+            .get(key)
+            .filter(|attachment| {
+                attachment.path.parent() == Some(attachments_dir) && attachment.path.exists()
+            })
+            .map(|attachment| attachment.path.clone());
+        let local_path = match previous {
+            Some(local_path) => local_path,
+            None if offline => continue,
+            None => match download_deduplicated(
+                &client,
+                &url,
+                attachments_dir,
+                known,
+                progress,
+                deadline,
+            )
+            .await
+            {
+                Ok(Some(attachment)) => {
+                    let local_path = attachment.path.clone();
+                    known.insert(key.to_owned(), attachment);
+                    local_path
+                }
+                Ok(None) => {
+                    index.truncated = true;
+                    break;
+                }
+                Err(error) => {
+                    progress
+                        .suspend(|| eprintln!("Failed to download attachment {key}: {error:#}"));
+                    continue;
+                }
+            },
+        };
+        index.files.insert(key.to_owned(), local_path);
+    }
+    Ok(index)
+}
+
+async fn download_deduplicated(
+    client: &reqwest::Client,
+    url: &str,
+    attachments_dir: &Path,
+    known: &HashMap<String, AttachmentState>,
+    progress: &ProgressBar,
+    deadline: Option<Instant>,
+) -> anyhow::Result<Option<AttachmentState>> {
+    // only the download is cut short, a file is never left half written
+    let Some(bytes) = before_deadline(deadline, fetch_attachment(client, url, progress)).await?
+    else {
+        return Ok(None);
+    };
+    let sha256 = format!("{:x}", Sha256::digest(&bytes));
+    let duplicate = known.values().find(|attachment| {
+        attachment.sha256 == sha256
+            && attachment.path.parent() == Some(attachments_dir)
+            && attachment.path.exists()
+    });
+    if let Some(duplicate) = duplicate {
+        return Ok(Some(duplicate.clone()));
+    }
+    let path = attachments_dir.join(attachment_file_name(url, &sha256));
+    if !path.exists() {
+        tokio::fs::write(&path, &bytes).await?;
+    }
+    Ok(Some(AttachmentState { path, sha256 }))
+}
+
+async fn fetch_attachment(
+    client: &reqwest::Client,
+    url: &str,
+    progress: &ProgressBar,
+) -> anyhow::Result<Vec<u8>> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    progress.set_message(
+        attachment_key(url)
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_owned(),
+    );
+    progress.inc_length(response.content_length().unwrap_or_default());
+    // read in chunks so large files move the progress bar
+    let mut bytes = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        progress.inc(chunk.len() as u64);
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+// the content hash keeps names stable between runs even though the signed urls change
+// notion percent-encodes the uploaded name, readers would decode `%20` in the link and miss the file
+fn attachment_file_name(url: &str, sha256: &str) -> String {
+    let url_path = attachment_key(url);
+    let encoded_name = url_path.rsplit('/').next().unwrap_or_default();
+    let decoded_name = percent_decode_str(encoded_name).decode_utf8_lossy();
+    let safe_name = if decoded_name.is_empty() {
+        String::new()
+    } else {
+        escape::file_name(&decoded_name)
+    };
+    let file_name = Path::new(&safe_name);
+    let short_hash: String = sha256.chars().take(12).collect();
+    // `design?.png` is sanitized to `design-`, the hash brings its own separator
+    let stem = file_name
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|stem| stem.trim_end_matches(['-', '_', ' ']))
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("attachment");
+    match file_name
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some(extension) => format!("{stem}-{short_hash}.{extension}"),
+        None => format!("{stem}-{short_hash}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attachment_names_are_decoded() {
+        let url = "https://prod-files-secure.s3.us-west-2.amazonaws.com/a/b/Untitled%20design%3F.png?X-Amz-Signature=abc";
+        assert_eq!(
+            attachment_file_name(url, "0123456789abcdef"),
+            "Untitled design-0123456789ab.png"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    changes::ChangeSet, configuration::ExportConfig, crawl::PageGraph, state::ExportState,
+};
+use anyhow::{bail, Context};
+use std::path::{Path, PathBuf};
+
+// a copy of the output directory the export renders into, swapped in once every page succeeded
+// so tools reading the notes never see a half updated vault
+// `output_dir` holds every format, the notes and their state are in `notes_dir` below it
+pub struct Staging {
+    output_dir: PathBuf,
+    staging_dir: PathBuf,
+    notes_dir: PathBuf,
+}
+
+impl Staging {
+    // incremental exports need the previous notes and state, so the staging directory starts as a copy
+    pub fn prepare(output_dir: &Path, notes_dir: &Path) -> anyhow::Result<Self> {
+        ensure_renamable(output_dir)?;
+        let staging = Self {
+            output_dir: output_dir.to_path_buf(),
+            staging_dir: sibling(output_dir, "staging"),
+            notes_dir: notes_dir
+                .strip_prefix(output_dir)
+                .unwrap_or(Path::new(""))
+                .to_path_buf(),
+        };
+        // left behind by a run that crashed
+        if staging.staging_dir.exists() {
+            std::fs::remove_dir_all(&staging.staging_dir)?;
+        }
+        if output_dir.exists() {
+            copy_dir(output_dir, &staging.staging_dir).with_context(|| {
+                format!(
+                    "failed to copy {} into {}",
+                    output_dir.display(),
+                    staging.staging_dir.display()
+                )
+            })?;
+        }
+        rebase(
+            &staging.staging_dir.join(&staging.notes_dir),
+            output_dir,
+            &staging.staging_dir,
+        )?;
+        Ok(staging)
+    }
+
+    pub fn config(&self, config: &ExportConfig) -> ExportConfig {
+        ExportConfig {
+            output_dir: self.staging_dir.join(&self.notes_dir),
+            ..config.clone()
+        }
+    }
+
+    // the two renames leave the output directory missing for a moment, it's never half written
+    pub fn commit(self) -> anyhow::Result<()> {
+        rebase(
+            &self.staging_dir.join(&self.notes_dir),
+            &self.staging_dir,
+            &self.output_dir,
+        )?;
+        let previous_dir = sibling(&self.output_dir, "previous");
+        if previous_dir.exists() {
+            std::fs::remove_dir_all(&previous_dir)?;
+        }
+        if self.output_dir.exists() {
+            std::fs::rename(&self.output_dir, &previous_dir)?;
+        }
+        std::fs::rename(&self.staging_dir, &self.output_dir)
+            .with_context(|| format!("failed to move {} into place", self.staging_dir.display()))?;
+        if previous_dir.exists() {
+            std::fs::remove_dir_all(&previous_dir)?;
+        }
+        Ok(())
+    }
+
+    // changes name the notes where they end up
+    pub fn rebase_changes(&self, changes: &mut ChangeSet) {
+        let paths = changes
+            .added
+            .iter_mut()
+            .chain(changes.updated.iter_mut())
+            .chain(changes.removed.iter_mut())
+            .chain(changes.truncated.iter_mut())
+            .filter_map(|change| change.path.as_mut());
+        for path in paths {
+            if let Ok(relative) = path.strip_prefix(&self.staging_dir) {
+                *path = self.output_dir.join(relative);
+            }
+        }
+    }
+
+    pub fn discard(self) -> anyhow::Result<()> {
+        if self.staging_dir.exists() {
+            std::fs::remove_dir_all(&self.staging_dir)?;
+        }
+        Ok(())
+    }
+}
+
+// `notes` -> `notes.lock`, the directory is swapped so a lock inside it would be swapped along
+pub fn lock_path(output_dir: &Path) -> anyhow::Result<PathBuf> {
+    ensure_renamable(output_dir)?;
+    Ok(sibling(output_dir, "lock"))
+}
+
+fn ensure_renamable(output_dir: &Path) -> anyhow::Result<()> {
+    if output_dir.file_name().is_none() {
+        bail!(
+            "--atomic needs an output directory that can be renamed, not {}",
+            output_dir.display()
+        );
+    }
+    Ok(())
+}
+
+// `notes` -> `notes.staging` next to it, renames don't work across file systems
+fn sibling(output_dir: &Path, suffix: &str) -> PathBuf {
+    let mut name = output_dir.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{suffix}"));
+    output_dir.with_file_name(name)
+}
+
+// state and page graph in `dir` remember paths with the output directory in front
+fn rebase(dir: &Path, from: &Path, to: &Path) -> anyhow::Result<()> {
+    let mut export_state = ExportState::load(dir)?;
+    export_state.rebase(from, to);
+    export_state.save(dir)?;
+    if let Some(mut graph) = PageGraph::load(dir)? {
+        for node in graph.pages.iter_mut() {
+            if let Ok(relative) = node.path.strip_prefix(from) {
+                node.path = to.join(relative);
+            }
+        }
+        graph.save(dir)?;
+    }
+    Ok(())
+}
+
+// a plain copy, hard links would let the export write through into the live notes
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staged_notes_replace_the_output_on_commit() {
+        let root =
+            std::env::temp_dir().join(format!("notion-bridge-atomic-test-{}", std::process::id()));
+        let output_dir = root.join("notes");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::write(output_dir.join("Page.md"), "old").unwrap();
+
+        let staging = Staging::prepare(&output_dir, &output_dir).unwrap();
+        let staging_dir = root.join("notes.staging");
+        // the staging directory starts as a copy and the output stays untouched until the commit
+        assert_eq!(
+            std::fs::read_to_string(staging_dir.join("Page.md")).unwrap(),
+            "old"
+        );
+        std::fs::write(staging_dir.join("Page.md"), "new").unwrap();
+        std::fs::write(staging_dir.join("Other.md"), "other").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("Page.md")).unwrap(),
+            "old"
+        );
+
+        staging.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("Page.md")).unwrap(),
+            "new"
+        );
+        assert!(output_dir.join("Other.md").exists());
+        assert!(!staging_dir.exists());
+        assert!(!root.join("notes.previous").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,341 @@
+use crate::api::NotionClient;
+use anyhow::Context;
+use futures::{future::BoxFuture, Future, FutureExt};
+use notion::{
+    ids::BlockId,
+    models::{
+        block::{Block, BlockCommon, FileObject},
+        Parent,
+    },
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use tokio::time::Instant;
+
+// the api only returns one level of children at a time
+// so we walk the tree and attach nested children to their parents
+pub async fn fetch_block_tree(
+    notion_client: &NotionClient,
+    block_id: BlockId,
+) -> anyhow::Result<Vec<Block>> {
+    let tree = fetch_block_tree_until(notion_client, block_id, None, false).await?;
+    Ok(tree.blocks)
+}
+
+pub struct BlockTree {
+    pub blocks: Vec<Block>,
+    // the same blocks as the api returned them, children nested as `<type>.children`
+    pub raw: Vec<Value>,
+    // the deadline passed before everything was fetched
+    pub truncated: bool,
+}
+
+// stops fetching once the deadline passes and keeps whatever arrived until then
+// `archive` also follows children of blocks the renderer doesn't handle, so the raw tree is complete
+pub fn fetch_block_tree_until(
+    notion_client: &NotionClient,
+    block_id: BlockId,
+    deadline: Option<Instant>,
+    archive: bool,
+) -> BoxFuture<'_, anyhow::Result<BlockTree>> {
+    fetch_children_until(notion_client, block_id, "block", deadline, archive)
+}
+
+// `kind` names the parent in errors, like `toggle block`
+fn fetch_children_until<'a>(
+    notion_client: &'a NotionClient,
+    block_id: BlockId,
+    kind: &'static str,
+    deadline: Option<Instant>,
+    archive: bool,
+) -> BoxFuture<'a, anyhow::Result<BlockTree>> {
+    async move {
+        let operation = || format!("fetching children of {kind} {block_id}");
+        let mut blocks = vec![];
+        let mut raw = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let next_page = notion_client.get_block_children_json(&block_id, cursor.as_deref());
+            let Some(response) = before_deadline(deadline, next_page)
+                .await
+                .with_context(operation)?
+            else {
+                return Ok(BlockTree {
+                    blocks,
+                    raw,
+                    truncated: true,
+                });
+            };
+            for result in response
+                .get("results")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default()
+            {
+                let block = serde_json::from_value(result.clone()).with_context(|| {
+                    format!("unexpected block in children of {kind} {block_id}")
+                })?;
+                blocks.push(block);
+                raw.push(result);
+            }
+            cursor = response
+                .get("next_cursor")
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        let mut is_truncated = false;
+        for (block, raw_block) in blocks.iter_mut().zip(raw.iter_mut()) {
+            if is_truncated {
+                break;
+            }
+            let container = match child_container_id(block) {
+                Some(container) => Some(container),
+                None if archive => raw_container_id(raw_block),
+                None => None,
+            };
+            if let Some((container_id, kind)) = container {
+                let nested =
+                    fetch_children_until(notion_client, container_id, kind, deadline, archive)
+                        .await?;
+                is_truncated = nested.truncated;
+                attach_children(block, nested.blocks);
+                attach_raw_children(raw_block, nested.raw);
+            }
+        }
+        Ok(BlockTree {
+            blocks,
+            raw,
+            truncated: is_truncated,
+        })
+    }
+    .boxed()
+}
+
+pub async fn before_deadline<T>(
+    deadline: Option<Instant>,
+    request: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<Option<T>> {
+    match deadline {
+        Some(deadline) => match tokio::time::timeout_at(deadline, request).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        },
+        None => request.await.map(Some),
+    }
+}
+
+// child pages and databases also report children but those are separate documents
+fn child_container_id(block: &Block) -> Option<(BlockId, &'static str)> {
+    let (common, kind) = match block {
+        Block::Paragraph { common, .. } => (common, "paragraph block"),
+        Block::Quote { common, .. } => (common, "quote block"),
+        Block::BulletedListItem { common, .. } => (common, "bulleted list item"),
+        Block::NumberedListItem { common, .. } => (common, "numbered list item"),
+        Block::ToDo { common, .. } => (common, "to-do block"),
+        Block::Toggle { common, .. } => (common, "toggle block"),
+        Block::ColumnList { common, .. } => (common, "column list"),
+        Block::Column { common, .. } => (common, "column"),
+        Block::Template { common, .. } => (common, "template block"),
+        Block::Table { common, .. } => (common, "table"),
+        Block::SyncedBlock { common, .. } => (common, "synced block"),
+        _ => return None,
+    };
+    common.has_children.then(|| (common.id.clone(), kind))
+}
+
+// blocks the notion crate doesn't model, like toggleable headings or newer block types
+fn raw_container_id(block: &Value) -> Option<(BlockId, &'static str)> {
+    let has_children = block.get("has_children").and_then(Value::as_bool) == Some(true);
+    let block_type = block.get("type").and_then(Value::as_str)?;
+    if !has_children || matches!(block_type, "child_page" | "child_database") {
+        return None;
+    }
+    let id = block.get("id").and_then(Value::as_str)?;
+    Some((BlockId::from_str(id).ok()?, "block"))
+}
+
+// where the append children api expects them, so the archive can be sent back as it is
+fn attach_raw_children(block: &mut Value, children: Vec<Value>) {
+    let Some(block_type) = block.get("type").and_then(Value::as_str).map(str::to_owned) else {
+        return;
+    };
+    if let Some(content) = block.get_mut(&block_type).and_then(Value::as_object_mut) {
+        content.insert(String::from("children"), Value::Array(children));
+    }
+}
+
+fn attach_children(block: &mut Block, children: Vec<Block>) {
+    match block {
+        Block::Paragraph { paragraph, .. } => paragraph.children = Some(children),
+        Block::Quote { quote, .. } => quote.children = Some(children),
+        Block::BulletedListItem {
+            bulleted_list_item, ..
+        } => bulleted_list_item.children = Some(children),
+        Block::NumberedListItem {
+            numbered_list_item, ..
+        } => numbered_list_item.children = Some(children),
+        Block::Toggle { toggle, .. } => toggle.children = Some(children),
+        Block::ToDo { to_do, .. } => to_do.children = Some(children),
+        Block::SyncedBlock { synced_block, .. } => synced_block.children = Some(children),
+        Block::ColumnList { column_list, .. } => column_list.children = children,
+        Block::Column { column, .. } => column.children = children,
+        Block::Template { template, .. } => template.children = children,
+        Block::Table { table, .. } => table.children = children,
+        _ => {}
+    }
+}
+
+pub fn block_children(block: &Block) -> &[Block] {
+    let children = match block {
+        Block::Paragraph { paragraph, .. } => paragraph.children.as_deref(),
+        Block::Quote { quote, .. } => quote.children.as_deref(),
+        Block::BulletedListItem {
+            bulleted_list_item, ..
+        } => bulleted_list_item.children.as_deref(),
+        Block::NumberedListItem {
+            numbered_list_item, ..
+        } => numbered_list_item.children.as_deref(),
+        Block::Toggle { toggle, .. } => toggle.children.as_deref(),
+        Block::ToDo { to_do, .. } => to_do.children.as_deref(),
+        Block::SyncedBlock { synced_block, .. } => synced_block.children.as_deref(),
+        Block::ColumnList { column_list, .. } => Some(column_list.children.as_slice()),
+        Block::Column { column, .. } => Some(column.children.as_slice()),
+        Block::Template { template, .. } => Some(template.children.as_slice()),
+        Block::Table { table, .. } => Some(table.children.as_slice()),
+        _ => None,
+    };
+    children.unwrap_or_default()
+}
+
+pub fn block_common(block: &Block) -> Option<&BlockCommon> {
+    match block {
+        Block::Paragraph { common, .. }
+        | Block::Heading1 { common, .. }
+        | Block::Heading2 { common, .. }
+        | Block::Heading3 { common, .. }
+        | Block::Callout { common, .. }
+        | Block::Quote { common, .. }
+        | Block::BulletedListItem { common, .. }
+        | Block::NumberedListItem { common, .. }
+        | Block::ToDo { common, .. }
+        | Block::Toggle { common, .. }
+        | Block::Code { common, .. }
+        | Block::ChildPage { common, .. }
+        | Block::ChildDatabase { common, .. }
+        | Block::Embed { common, .. }
+        | Block::Image { common, .. }
+        | Block::Video { common, .. }
+        | Block::File { common, .. }
+        | Block::Pdf { common, .. }
+        | Block::Bookmark { common, .. }
+        | Block::Equation { common, .. }
+        | Block::Divider { common, .. }
+        | Block::TableOfContents { common, .. }
+        | Block::Breadcrumb { common, .. }
+        | Block::ColumnList { common, .. }
+        | Block::Column { common, .. }
+        | Block::LinkPreview { common, .. }
+        | Block::Template { common, .. }
+        | Block::LinkToPage { common, .. }
+        | Block::Table { common, .. }
+        | Block::SyncedBlock { common, .. }
+        | Block::TableRow { common, .. }
+        | Block::Unsupported { common, .. } => Some(common),
+        Block::Unknown => None,
+    }
+}
+
+// visits every block in the tree depth first
+pub fn walk_blocks<'a>(blocks: &'a [Block], visit: &mut dyn FnMut(&'a Block)) {
+    for block in blocks {
+        visit(block);
+        walk_blocks(block_children(block), visit);
+    }
+}
+
+// ids of the pages mentioned anywhere in the rich text of the blocks, without duplicates
+// rich text sits in too many places to match on, so it is looked for in the json form
+pub fn mentioned_page_ids(blocks: &[Block]) -> anyhow::Result<Vec<String>> {
+    fn collect(value: &serde_json::Value, page_ids: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(object) => {
+                let page_id = object
+                    .get("mention")
+                    .filter(|mention| {
+                        mention.get("type").and_then(|kind| kind.as_str()) == Some("page")
+                    })
+                    .and_then(|mention| mention.get("page")?.get("id")?.as_str());
+                if let Some(page_id) = page_id {
+                    if !page_ids.iter().any(|known| known == page_id) {
+                        page_ids.push(page_id.to_owned());
+                    }
+                }
+                object.values().for_each(|value| collect(value, page_ids));
+            }
+            serde_json::Value::Array(values) => {
+                values.iter().for_each(|value| collect(value, page_ids));
+            }
+            _ => {}
+        }
+    }
+    let mut page_ids = vec![];
+    collect(&serde_json::to_value(blocks)?, &mut page_ids);
+    Ok(page_ids)
+}
+
+// targets of link to page blocks, pages and databases
+pub fn linked_page_ids(blocks: &[Block]) -> (Vec<String>, Vec<String>) {
+    let mut page_ids = vec![];
+    let mut database_ids = vec![];
+    walk_blocks(blocks, &mut |block| match block {
+        Block::LinkToPage {
+            link_to_page: Parent::Page { page_id },
+            ..
+        } => page_ids.push(page_id.to_string()),
+        Block::LinkToPage {
+            link_to_page: Parent::Database { database_id },
+            ..
+        } => database_ids.push(database_id.to_string()),
+        _ => {}
+    });
+    (page_ids, database_ids)
+}
+
+// pages and databases a page links to, mentions included
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LinkTargets {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub databases: Vec<String>,
+}
+
+pub fn link_targets(blocks: &[Block]) -> anyhow::Result<LinkTargets> {
+    let (linked_pages, databases) = linked_page_ids(blocks);
+    let mut pages = mentioned_page_ids(blocks)?;
+    pages.extend(linked_pages);
+    Ok(LinkTargets { pages, databases })
+}
+
+// ids of the subpages shown on a page, in order
+pub fn child_page_ids(blocks: &[Block]) -> Vec<String> {
+    let mut child_page_ids = vec![];
+    walk_blocks(blocks, &mut |block| {
+        if let Block::ChildPage { common, .. } = block {
+            child_page_ids.push(common.id.to_string());
+        }
+    });
+    child_page_ids
+}
+
+pub fn file_object_url(file_object: &FileObject) -> String {
+    match file_object {
+        FileObject::File { file } => file.url.clone(),
+        FileObject::External { external } => external.url.clone(),
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    api::{find_source, NotionClient, Source},
+    capabilities::{self, Capability},
+    configuration::ExportConfig,
+    escape, export,
+    ids::normalize_id,
+    markdown::notion_page_id_to_url,
+    properties::page_property_text,
+};
+use anyhow::{bail, Context};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use notion::{
+    ids::{DatabaseId, PageId},
+    models::{properties::PropertyValue, Page},
+};
+use serde_json::{json, Value};
+use std::{path::PathBuf, str::FromStr};
+
+const SNAPSHOT_DIR_NAME: &str = "pre-archive";
+
+pub struct BulkUpdate<'a> {
+    pub database_id: &'a str,
+    // `Property = Value`
+    pub filter: &'a str,
+    pub assignment: &'a str,
+    pub dry_run: bool,
+    pub source: Option<&'a str>,
+}
+
+pub async fn set_property(sources: &[Source], update: BulkUpdate<'_>) -> anyhow::Result<()> {
+    let notion_client = &find_source(sources, update.source)?.client;
+    if !update.dry_run {
+        notion_client.ensure_writable()?;
+    }
+    let (set_property, set_value) = parse_assignment(update.assignment)?;
+    let matching = matching_rows(notion_client, update.database_id, update.filter).await?;
+    if let (false, Some(row)) = (update.dry_run, matching.first()) {
+        capabilities::require(notion_client, Capability::UpdateContent, &row.id).await?;
+    }
+
+    let mut failed = 0;
+    for row in &matching {
+        let title = row.title().unwrap_or_default();
+        let Some(current) = row.properties.properties.get(set_property) else {
+            bail!("database has no property {set_property}");
+        };
+        let value = property_update(current, set_value)?;
+        if update.dry_run {
+            println!("Would set {set_property} = {set_value} on {title}");
+            continue;
+        }
+        let properties = json!({ set_property: value });
+        // keep going so one bad row doesn't leave the rest of the batch untouched
+        match notion_client
+            .update_page_properties(&row.id, &properties)
+            .await
+        {
+            Ok(_) => println!("Updated {title} {}", notion_page_id_to_url(&row.id)),
+            Err(error) => {
+                failed += 1;
+                eprintln!("Failed to update '{title}' {}: {error:#}", row.id);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} rows failed to update");
+    }
+    Ok(())
+}
+
+pub struct ArchiveRequest<'a> {
+    pub page_ids: &'a [String],
+    pub database_id: Option<&'a str>,
+    pub filter: Option<&'a str>,
+    pub dry_run: bool,
+    // skip the confirmation prompt
+    pub assume_yes: bool,
+    pub source: Option<&'a str>,
+    // renders the snapshot and decides where it goes
+    pub export_config: &'a ExportConfig,
+}
+
+pub async fn archive(sources: &[Source], request: ArchiveRequest<'_>) -> anyhow::Result<()> {
+    let notion_client = &find_source(sources, request.source)?.client;
+    if !request.dry_run {
+        notion_client.ensure_writable()?;
+    }
+    let mut pages = vec![];
+    for page_id in request.page_ids {
+        let page_id = PageId::from_str(&normalize_id(page_id))
+            .with_context(|| format!("invalid page id {page_id}"))?;
+        pages.push(notion_client.get_page(page_id).await?);
+    }
+    match (request.database_id, request.filter) {
+        (Some(database_id), Some(filter)) => {
+            pages.extend(matching_rows(notion_client, database_id, filter).await?)
+        }
+        (None, None) => {}
+        _ => bail!("--database and --where have to be used together"),
+    }
+
+    if pages.is_empty() {
+        println!("Nothing to archive");
+        return Ok(());
+    }
+    for page in &pages {
+        println!(
+            "{} {}",
+            page.title().unwrap_or_default(),
+            notion_page_id_to_url(&page.id)
+        );
+    }
+    if request.dry_run {
+        println!("Would archive {} pages", pages.len());
+        return Ok(());
+    }
+    capabilities::require(notion_client, Capability::UpdateContent, &pages[0].id).await?;
+    if !request.assume_yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Archive {} pages?", pages.len()))
+            .default(false)
+            .interact()?
+    {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let snapshot_dir = snapshot_pages(notion_client, request.export_config, &pages).await?;
+    println!("Saved a snapshot to {}", snapshot_dir.display());
+
+    let mut failed = 0;
+    for page in &pages {
+        let title = page.title().unwrap_or_default();
+        match notion_client.archive_page(&page.id).await {
+            Ok(_) => println!("Archived {title}"),
+            Err(error) => {
+                failed += 1;
+                eprintln!("Failed to archive '{title}' {}: {error:#}", page.id);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} pages failed to archive");
+    }
+    Ok(())
+}
+
+// pages in the trash are deleted for good after a while
+// so scripted cleanups keep a copy of everything they archive
+// every page gets its rendered content and the raw page with all properties
+async fn snapshot_pages(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    pages: &[Page],
+) -> anyhow::Result<PathBuf> {
+    let snapshot_dir = config
+        .output_dir
+        .join(SNAPSHOT_DIR_NAME)
+        .join(chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string());
+    std::fs::create_dir_all(&snapshot_dir)
+        .with_context(|| format!("failed to create {}", snapshot_dir.display()))?;
+    for page in pages {
+        let title = page.title().unwrap_or_default();
+        let body = export::page_body_markdown(notion_client, config, page).await?;
+        let raw_page = notion_client.get_page_json(&page.id).await?;
+        let url = notion_page_id_to_url(&page.id);
+        let file_stem = snapshot_file_stem(&title, &page.id);
+        std::fs::write(
+            snapshot_dir.join(format!("{file_stem}.md")),
+            format!("# [{title}]({url})\n\n{body}\n"),
+        )?;
+        std::fs::write(
+            snapshot_dir.join(format!("{file_stem}.json")),
+            serde_json::to_string_pretty(&raw_page)?,
+        )?;
+    }
+    Ok(snapshot_dir)
+}
+
+// titles repeat across rows, the id keeps the files apart
+fn snapshot_file_stem(title: &str, page_id: &PageId) -> String {
+    format!(
+        "{} {}",
+        escape::file_name(title),
+        normalize_id(&page_id.to_string())
+    )
+}
+
+async fn matching_rows(
+    notion_client: &NotionClient,
+    database_id: &str,
+    filter: &str,
+) -> anyhow::Result<Vec<Page>> {
+    let (filter_property, filter_value) = parse_assignment(filter)?;
+    let database_id = DatabaseId::from_str(&normalize_id(database_id))
+        .with_context(|| format!("invalid database id {database_id}"))?;
+    let rows = notion_client.query_all_rows(database_id).await?;
+    let total = rows.len();
+    let matching: Vec<Page> = rows
+        .into_iter()
+        .filter(|row| page_property_text(row, filter_property).as_deref() == Some(filter_value))
+        .collect();
+    println!(
+        "{} of {total} rows match {filter_property} = {filter_value}",
+        matching.len(),
+    );
+    Ok(matching)
+}
+
+fn parse_assignment(text: &str) -> anyhow::Result<(&str, &str)> {
+    let (property, value) = text
+        .split_once('=')
+        .with_context(|| format!("expected `Property = Value` but got `{text}`"))?;
+    Ok((property.trim(), value.trim()))
+}
+
+// the existing value tells us the property type so the request can be shaped to match
+fn property_update(current: &PropertyValue, value: &str) -> anyhow::Result<Value> {
+    let update = match current {
+        PropertyValue::Title { .. } => json!({ "title": [{ "text": { "content": value } }] }),
+        PropertyValue::Text { .. } => json!({ "rich_text": [{ "text": { "content": value } }] }),
+        PropertyValue::Number { .. } => {
+            let number: f64 = value
+                .parse()
+                .with_context(|| format!("{value} is not a number"))?;
+            json!({ "number": number })
+        }
+        PropertyValue::Select { .. } => json!({ "select": { "name": value } }),
+        PropertyValue::Status { .. } => json!({ "status": { "name": value } }),
+        PropertyValue::MultiSelect { .. } => {
+            let options: Vec<Value> = value
+                .split(',')
+                .map(str::trim)
+                .filter(|option| !option.is_empty())
+                .map(|option| json!({ "name": option }))
+                .collect();
+            json!({ "multi_select": options })
+        }
+        PropertyValue::Date { .. } => json!({ "date": { "start": value } }),
+        PropertyValue::Checkbox { .. } => {
+            let checked: bool = value
+                .parse()
+                .with_context(|| format!("{value} is not true or false"))?;
+            json!({ "checkbox": checked })
+        }
+        PropertyValue::Url { .. } => json!({ "url": value }),
+        PropertyValue::Email { .. } => json!({ "email": value }),
+        PropertyValue::PhoneNumber { .. } => json!({ "phone_number": value }),
+        _ => bail!("setting this property type isn't supported"),
+    };
+    Ok(update)
+}
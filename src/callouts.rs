@@ -0,0 +1,59 @@
+use crate::configuration::{CalloutRule, ExportConfig};
+use notion::models::{block::FileOrEmojiObject, text::TextColor};
+
+const FALLBACK_CALLOUT_TYPE: &str = "info";
+
+// uploaded and external icons have nothing to put into the title
+pub fn callout_emoji(icon: &FileOrEmojiObject) -> Option<&str> {
+    match icon {
+        FileOrEmojiObject::Emoji { emoji } => Some(emoji),
+        _ => None,
+    }
+}
+
+pub fn callout_type<'a>(
+    config: &'a ExportConfig,
+    emoji: Option<&str>,
+    color: TextColor,
+) -> &'a str {
+    config
+        .callout_types
+        .iter()
+        .find(|rule| matches(rule, emoji, color))
+        .map(|rule| rule.callout_type.as_str())
+        .unwrap_or(FALLBACK_CALLOUT_TYPE)
+}
+
+fn matches(rule: &CalloutRule, emoji: Option<&str>, color: TextColor) -> bool {
+    let emoji_matches = match (&rule.emoji, emoji) {
+        (None, _) => true,
+        (Some(wanted), Some(emoji)) => same_emoji(wanted, emoji),
+        (Some(_), None) => false,
+    };
+    emoji_matches && rule.color.is_none_or(|wanted| wanted == color)
+}
+
+// `⚠` and `⚠️` differ only by the variation selector asking for the colored glyph
+fn same_emoji(a: &str, b: &str) -> bool {
+    let strip = |emoji: &str| emoji.replace('\u{fe0f}', "");
+    strip(a) == strip(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_wins_over_color() {
+        let config = ExportConfig::default();
+        assert_eq!(
+            callout_type(&config, Some("\u{26a0}"), TextColor::BlueBackground),
+            "warning"
+        );
+        assert_eq!(
+            callout_type(&config, Some("🙂"), TextColor::RedBackground),
+            "danger"
+        );
+        assert_eq!(callout_type(&config, None, TextColor::Default), "info");
+    }
+}
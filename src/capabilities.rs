@@ -0,0 +1,57 @@
+use crate::api::{ApiStatusError, NotionClient};
+use notion::ids::{BlockId, PageId};
+use serde_json::json;
+
+// the api has no endpoint listing an integration's capabilities
+// so write commands send a request that changes nothing before doing any real work
+// notion answers those with 403 when the capability is missing
+#[derive(Debug, Clone, Copy)]
+pub enum Capability {
+    UpdateContent,
+    InsertContent,
+}
+
+impl Capability {
+    fn name(self) -> &'static str {
+        match self {
+            Capability::UpdateContent => "update content",
+            Capability::InsertContent => "insert content",
+        }
+    }
+}
+
+// `page_id` has to be a page the integration can read, capabilities apply to the whole workspace
+pub async fn require(
+    notion_client: &NotionClient,
+    capability: Capability,
+    page_id: &PageId,
+) -> anyhow::Result<()> {
+    let probe = match capability {
+        // an update without properties leaves the page as it is
+        Capability::UpdateContent => {
+            notion_client
+                .update_page_properties(page_id, &json!({}))
+                .await
+        }
+        Capability::InsertContent => {
+            let block_id: BlockId = page_id.clone().into();
+            notion_client.append_block_children(&block_id, vec![]).await
+        }
+    };
+    let Err(error) = probe else {
+        return Ok(());
+    };
+    let forbidden = error
+        .downcast_ref::<ApiStatusError>()
+        .map(|error| error.status == 403)
+        .unwrap_or(false);
+    if forbidden {
+        return Err(error.context(format!(
+            "integration lacks the {} capability, enable it in the integration settings under capabilities",
+            capability.name()
+        )));
+    }
+    // anything else, like a rejected empty request, says nothing about the capability
+    // the real requests will surface it if it's a problem
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use crate::exit_codes::FailureClass;
+use serde::Serialize;
+use similar::TextDiff;
+use std::path::PathBuf;
+
+// what an export run changed on disk
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ChangeSet {
+    pub added: Vec<PageChange>,
+    pub updated: Vec<PageChange>,
+    pub removed: Vec<PageChange>,
+    // cut short by the page timeout, also listed as added or updated when the file changed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub truncated: Vec<PageChange>,
+    // pages that couldn't be exported, their previous files are left alone
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<PageFailure>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PageFailure {
+    pub page_id: String,
+    pub title: String,
+    pub class: FailureClass,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PageChange {
+    pub page_id: String,
+    pub title: String,
+    pub path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.removed.is_empty()
+            && self.truncated.is_empty()
+    }
+
+    pub fn extend(&mut self, other: ChangeSet) {
+        self.added.extend(other.added);
+        self.updated.extend(other.updated);
+        self.removed.extend(other.removed);
+        self.truncated.extend(other.truncated);
+        self.failed.extend(other.failed);
+    }
+
+    pub fn without_diffs(&self) -> ChangeSet {
+        let strip = |changes: &[PageChange]| {
+            changes
+                .iter()
+                .cloned()
+                .map(|change| PageChange {
+                    diff: None,
+                    ..change
+                })
+                .collect()
+        };
+        ChangeSet {
+            added: strip(&self.added),
+            updated: strip(&self.updated),
+            removed: strip(&self.removed),
+            truncated: strip(&self.truncated),
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+pub fn unified_diff(previous: &str, current: &str) -> String {
+    TextDiff::from_lines(previous, current)
+        .unified_diff()
+        .header("before", "after")
+        .to_string()
+}
@@ -0,0 +1,293 @@
+use crate::{
+    assets::ATTACHMENTS_DIR_NAME,
+    configuration::{AppConfig, OutputFormat},
+    lock::ExportLock,
+    manifest::ExportManifest,
+    state::{self, ExportState},
+    stats,
+};
+use chrono::Utc;
+use regex::{Regex, RegexBuilder};
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::{Path, PathBuf},
+};
+
+const QUARANTINE_DIR_NAME: &str = "quarantine";
+
+#[derive(Debug, Clone, Copy)]
+pub struct CleanOptions {
+    // remove files instead of moving them into the quarantine directory
+    pub delete: bool,
+    pub dry_run: bool,
+    pub wait_for_lock: bool,
+}
+
+// attachments stay around when the embed or the whole page is gone from notion
+// anything in the attachments store that no note of the manifest mentions anymore is moved away
+pub async fn clean_attachments(config: &AppConfig, options: CleanOptions) -> anyhow::Result<()> {
+    for job in config.export_jobs() {
+        // attachments are only looked for in markdown notes, without them every file looks orphaned
+        if !job.export.formats.contains(&OutputFormat::Markdown) {
+            println!("Job {} writes no markdown notes, skipping", job.name);
+            continue;
+        }
+        for output_dir in stats::job_output_dirs(config, &job) {
+            let Some(manifest) = ExportManifest::load(&output_dir)? else {
+                // without a manifest every file would look orphaned
+                println!("No manifest in {}, skipping", output_dir.display());
+                continue;
+            };
+            let _lock = ExportLock::acquire(&output_dir, options.wait_for_lock).await?;
+            clean_output_dir(&output_dir, &manifest, options)?;
+        }
+    }
+    Ok(())
+}
+
+fn clean_output_dir(
+    output_dir: &Path,
+    manifest: &ExportManifest,
+    options: CleanOptions,
+) -> anyhow::Result<()> {
+    let mut export_state = ExportState::load(output_dir)?;
+    let mut candidates = attachment_files(output_dir, &export_state)?;
+    // a page titled like an attachments directory puts its note in there
+    for entry in manifest.pages.values() {
+        for note_path in entry.note_paths(output_dir) {
+            candidates.remove(&note_path);
+        }
+    }
+    let referenced = referenced_files(output_dir, manifest, &candidates)?;
+    let orphans: Vec<&PathBuf> = candidates
+        .iter()
+        .filter(|path| file_name(path).is_some_and(|name| !referenced.contains(name)))
+        .collect();
+
+    let quarantine_dir = state::state_dir(output_dir)
+        .join(QUARANTINE_DIR_NAME)
+        .join(Utc::now().format("%Y%m%d-%H%M%S").to_string());
+    let mut freed_bytes = 0;
+    for path in &orphans {
+        let relative = path.strip_prefix(output_dir).unwrap_or(path);
+        freed_bytes += std::fs::metadata(path).map(|metadata| metadata.len())?;
+        if options.dry_run {
+            println!("Would remove {}", relative.display());
+        } else if options.delete {
+            std::fs::remove_file(path)?;
+            println!("Removed {}", relative.display());
+        } else {
+            let target = quarantine_dir.join(relative);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(path, &target)?;
+            println!("Quarantined {}", relative.display());
+        }
+    }
+
+    if !options.dry_run && !orphans.is_empty() {
+        // forget the downloads so a page embedding the file again fetches it again
+        export_state
+            .attachments
+            .retain(|_, attachment| attachment.path.exists());
+        export_state.save(output_dir)?;
+    }
+    println!(
+        "{}: {} of {} attachments unreferenced, {freed_bytes} bytes",
+        output_dir.display(),
+        orphans.len(),
+        candidates.len()
+    );
+    if !options.dry_run && !options.delete && !orphans.is_empty() {
+        println!("Moved to {}", quarantine_dir.display());
+    }
+    Ok(())
+}
+
+// the shared attachments directory, per note `.assets` directories and every file a run downloaded
+// note named directories of the notion layout also hold subpages, only downloads are taken from them
+pub fn attachment_files(
+    output_dir: &Path,
+    export_state: &ExportState,
+) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    collect_files(&output_dir.join(ATTACHMENTS_DIR_NAME), &mut files)?;
+    collect_assets_dirs(output_dir, &state::state_dir(output_dir), &mut files)?;
+    files.extend(
+        export_state
+            .attachments
+            .values()
+            .map(|attachment| attachment.path.clone())
+            .filter(|path| path.starts_with(output_dir) && path.is_file()),
+    );
+    Ok(files)
+}
+
+fn collect_files(dir: &Path, files: &mut BTreeSet<PathBuf>) -> anyhow::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+fn collect_assets_dirs(
+    dir: &Path,
+    state_dir: &Path,
+    files: &mut BTreeSet<PathBuf>,
+) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() || path == state_dir {
+            continue;
+        }
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "assets")
+        {
+            collect_files(&path, files)?;
+        } else {
+            collect_assets_dirs(&path, state_dir, files)?;
+        }
+    }
+    Ok(())
+}
+
+// attachment names carry a content hash, so a note mentioning the file name references the file
+// whatever link syntax the flavor used
+pub struct AttachmentMatcher {
+    pattern: Option<Regex>,
+}
+
+impl AttachmentMatcher {
+    pub fn new(files: &BTreeSet<PathBuf>) -> anyhow::Result<Self> {
+        let mut names: Vec<&str> = files.iter().filter_map(|path| file_name(path)).collect();
+        if names.is_empty() {
+            return Ok(Self { pattern: None });
+        }
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        names.dedup();
+        let alternatives: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+        let pattern = RegexBuilder::new(&alternatives.join("|"))
+            .size_limit(1 << 30)
+            .build()?;
+        Ok(Self {
+            pattern: Some(pattern),
+        })
+    }
+
+    // file names of the attachments a note mentions, each once
+    pub fn referenced<'a>(&self, note: &'a str) -> HashSet<&'a str> {
+        match &self.pattern {
+            Some(pattern) => pattern
+                .find_iter(note)
+                .map(|found| found.as_str())
+                .collect(),
+            None => HashSet::new(),
+        }
+    }
+}
+
+fn referenced_files(
+    output_dir: &Path,
+    manifest: &ExportManifest,
+    candidates: &BTreeSet<PathBuf>,
+) -> anyhow::Result<HashSet<String>> {
+    let matcher = AttachmentMatcher::new(candidates)?;
+    let mut referenced = HashSet::new();
+    for entry in manifest.pages.values() {
+        // a note deleted by hand references nothing
+        let Ok(note) = entry.read_note(output_dir) else {
+            continue;
+        };
+        referenced.extend(matcher.referenced(&note).into_iter().map(str::to_owned));
+    }
+    Ok(referenced)
+}
+
+// files with names that aren't utf-8 can't be matched and are never touched
+pub fn file_name(path: &Path) -> Option<&str> {
+    path.file_name()?.to_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ManifestEntry;
+
+    fn manifest_with_note(path: &str) -> ExportManifest {
+        let mut manifest = ExportManifest::default();
+        manifest.pages.insert(
+            String::from("0123456789abcdef0123456789abcdef"),
+            ManifestEntry {
+                title: String::from("Attachments"),
+                path: path.to_owned(),
+                sha256: String::new(),
+                words: 0,
+                created_time: Utc::now(),
+                last_edited_time: Utc::now(),
+                metrics: Default::default(),
+                parts: vec![],
+            },
+        );
+        manifest
+    }
+
+    #[test]
+    fn matcher_finds_names_in_any_link_syntax() {
+        let files = BTreeSet::from([
+            PathBuf::from("attachments/diagram-ab12.png"),
+            PathBuf::from("attachments/report-cd34.pdf"),
+        ]);
+        let matcher = AttachmentMatcher::new(&files).unwrap();
+        assert_eq!(
+            matcher.referenced("![](attachments/diagram-ab12.png) and [[report-cd34.pdf]]"),
+            HashSet::from(["diagram-ab12.png", "report-cd34.pdf"])
+        );
+        assert!(matcher.referenced("diagram.png").is_empty());
+        let empty = AttachmentMatcher::new(&BTreeSet::new()).unwrap();
+        assert!(empty.referenced("diagram-ab12.png").is_empty());
+    }
+
+    #[test]
+    fn only_unreferenced_attachments_are_removed() {
+        let output_dir = std::env::temp_dir().join(format!(
+            "notion-bridge-clean-attachments-test-{}",
+            std::process::id()
+        ));
+        let attachments_dir = output_dir.join(ATTACHMENTS_DIR_NAME);
+        std::fs::create_dir_all(&attachments_dir).unwrap();
+        let referenced = attachments_dir.join("used-ab12.png");
+        let orphan = attachments_dir.join("orphan-cd34.png");
+        // the note of a page titled like the attachments directory ends up in it
+        let note = attachments_dir.join("Attachments.md");
+        std::fs::write(&referenced, "png").unwrap();
+        std::fs::write(&orphan, "png").unwrap();
+        std::fs::write(&note, "![](used-ab12.png)").unwrap();
+
+        let options = CleanOptions {
+            delete: true,
+            dry_run: false,
+            wait_for_lock: false,
+        };
+        clean_output_dir(
+            &output_dir,
+            &manifest_with_note("attachments/Attachments.md"),
+            options,
+        )
+        .unwrap();
+
+        assert!(referenced.exists());
+        assert!(note.exists());
+        assert!(!orphan.exists());
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
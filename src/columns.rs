@@ -0,0 +1,65 @@
+use crate::{api::NotionClient, blocks::walk_blocks, ids::normalize_id};
+use notion::models::block::Block;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub fn has_columns(blocks: &[Block]) -> bool {
+    let mut found = false;
+    walk_blocks(blocks, &mut |block| {
+        found |= matches!(block, Block::ColumnList { .. });
+    });
+    found
+}
+
+// width ratios of resized columns keyed by normalized column id
+// columns nobody resized have no ratio and share the space evenly
+pub async fn fetch_column_widths(
+    notion_client: &NotionClient,
+    blocks: &[Block],
+) -> anyhow::Result<HashMap<String, f64>> {
+    let mut column_lists = vec![];
+    walk_blocks(blocks, &mut |block| {
+        if let Block::ColumnList { common, .. } = block {
+            column_lists.push(common.id.clone());
+        }
+    });
+
+    let mut widths = HashMap::new();
+    for column_list_id in column_lists {
+        for column in notion_client
+            .list_block_children_json(&column_list_id)
+            .await?
+        {
+            let id = column.get("id").and_then(Value::as_str);
+            let width_ratio = column
+                .get("column")
+                .and_then(|column| column.get("width_ratio"))
+                .and_then(Value::as_f64);
+            if let (Some(id), Some(width_ratio)) = (id, width_ratio) {
+                widths.insert(normalize_id(id), width_ratio);
+            }
+        }
+    }
+    Ok(widths)
+}
+
+// ratios of the resized columns, the rest split whatever is left
+pub fn column_ratios(widths: &HashMap<String, f64>, column_ids: &[String]) -> Vec<f64> {
+    let known: Vec<Option<f64>> = column_ids
+        .iter()
+        .map(|id| widths.get(&normalize_id(id)).copied())
+        .collect();
+    let unknown = known.iter().filter(|ratio| ratio.is_none()).count();
+    let remaining = (1.0 - known.iter().flatten().sum::<f64>()).max(0.0);
+    let fallback = if unknown == 0 {
+        0.0
+    } else if remaining > 0.0 {
+        remaining / unknown as f64
+    } else {
+        1.0 / column_ids.len() as f64
+    };
+    known
+        .into_iter()
+        .map(|ratio| ratio.unwrap_or(fallback))
+        .collect()
+}
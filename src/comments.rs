@@ -0,0 +1,159 @@
+use crate::{
+    api::NotionClient,
+    blocks::{block_common, walk_blocks},
+    configuration::ExportConfig,
+    i18n::{self, Text},
+    ids::normalize_id,
+    redaction::Redactor,
+    rich_text::raw_plain_text,
+};
+use notion::models::block::Block;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CommentThread {
+    pub discussion_id: String,
+    // the page itself for page level discussions
+    pub block_id: String,
+    // the api only returns open discussions so this is always false for now
+    pub resolved: bool,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Comment {
+    pub id: String,
+    pub author_id: String,
+    pub author: Option<String>,
+    pub created_time: String,
+    pub text: String,
+}
+
+// comments can only be listed per block so this costs a request for every block on the page
+pub async fn fetch_comment_threads(
+    notion_client: &NotionClient,
+    page_id: &str,
+    blocks: &[Block],
+) -> anyhow::Result<Vec<CommentThread>> {
+    let mut block_ids = vec![page_id.to_owned()];
+    walk_blocks(blocks, &mut |block| {
+        if let Some(common) = block_common(block) {
+            block_ids.push(common.id.to_string());
+        }
+    });
+
+    let mut threads: Vec<CommentThread> = vec![];
+    let mut authors: HashMap<String, Option<String>> = HashMap::new();
+    for block_id in block_ids {
+        for raw_comment in notion_client.list_comments(&block_id).await? {
+            let author_id = raw_comment["created_by"]["id"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned();
+            if !authors.contains_key(&author_id) {
+                // users the integration can't read stay anonymous
+                let name = notion_client.get_user_name(&author_id).await.ok().flatten();
+                authors.insert(author_id.clone(), name);
+            }
+            let comment = Comment {
+                id: string_field(&raw_comment, "id"),
+                author: authors.get(&author_id).cloned().flatten(),
+                author_id,
+                created_time: string_field(&raw_comment, "created_time"),
+                text: raw_plain_text(&raw_comment["rich_text"]),
+            };
+
+            let discussion_id = string_field(&raw_comment, "discussion_id");
+            match threads
+                .iter_mut()
+                .find(|thread| thread.discussion_id == discussion_id)
+            {
+                Some(thread) => thread.comments.push(comment),
+                None => threads.push(CommentThread {
+                    discussion_id,
+                    block_id: block_id.clone(),
+                    resolved: false,
+                    comments: vec![comment],
+                }),
+            }
+        }
+    }
+    for thread in threads.iter_mut() {
+        thread
+            .comments
+            .sort_by(|a, b| a.created_time.cmp(&b.created_time));
+    }
+    Ok(threads)
+}
+
+// the first comment starts the thread and replies are nested under it
+pub fn render_comment_threads(
+    config: &ExportConfig,
+    threads: &[CommentThread],
+    page_id: &str,
+) -> String {
+    if threads.is_empty() {
+        return String::new();
+    }
+    let unknown_author = i18n::text(config, Text::UnknownAuthor);
+    let mut buffer = format!("\n## {}\n\n", i18n::text(config, Text::Comments));
+    for thread in threads {
+        let Some((first, replies)) = thread.comments.split_first() else {
+            continue;
+        };
+        if normalize_id(&thread.block_id) != normalize_id(page_id) {
+            let _ = writeln!(buffer, "<!-- block {} -->", normalize_id(&thread.block_id));
+        }
+        let _ = writeln!(buffer, "- {}", render_comment(first, unknown_author));
+        for reply in replies {
+            let _ = writeln!(buffer, "    - {}", render_comment(reply, unknown_author));
+        }
+    }
+    buffer
+}
+
+fn render_comment(comment: &Comment, unknown_author: &str) -> String {
+    let author = comment.author.as_deref().unwrap_or(unknown_author);
+    format!(
+        "**{author}** ({}): {}",
+        comment.created_time,
+        comment.text.replace('\n', " ")
+    )
+}
+
+pub fn sidecar_path(note_path: &Path) -> PathBuf {
+    note_path.with_extension("comments.json")
+}
+
+pub async fn write_sidecar(
+    note_path: &Path,
+    threads: &[CommentThread],
+    redactor: Option<&Redactor>,
+) -> anyhow::Result<()> {
+    let path = sidecar_path(note_path);
+    if threads.is_empty() {
+        if path.exists() {
+            tokio::fs::remove_file(&path).await?;
+        }
+        return Ok(());
+    }
+    let mut threads = serde_json::to_value(threads)?;
+    if let Some(redactor) = redactor {
+        redactor.redact_json(&mut threads);
+    }
+    let content = serde_json::to_string_pretty(&threads)?;
+    if tokio::fs::read_to_string(&path).await.ok().as_deref() != Some(content.as_str()) {
+        tokio::fs::write(&path, content).await?;
+    }
+    Ok(())
+}
+
+fn string_field(value: &Value, field: &str) -> String {
+    value[field].as_str().unwrap_or_default().to_owned()
+}
@@ -1,8 +1,13 @@
+use crate::{
+    api::NOTION_API_URL,
+    presets::{self, Preset},
+};
 use anyhow::{Context, Result};
 use config::Config;
 use directories::ProjectDirs;
+use notion::models::text::TextColor;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 const PROJECT_QUALIFIER: &str = "com";
 const PROJECT_ORGANIZATION: &str = "dmweis";
@@ -26,20 +31,921 @@ fn get_config_file_path() -> Result<PathBuf> {
     Ok(config_dir_path.join(CONFIG_FILE_NAME))
 }
 
+pub fn user_config_file_path() -> Result<PathBuf> {
+    Ok(get_config_file_path()?.with_extension(CONFIG_FILE_EXTENSION))
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct AppConfig {
+    #[serde(default)]
+    pub notion_api_key: String,
+    // several integrations exported together, replaces `notion_api_key` when set
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
+    #[serde(default)]
+    pub export: ExportConfig,
+    // several exports in one run, when empty the `export` section is used on its own
+    #[serde(default)]
+    pub jobs: Vec<ExportJob>,
+    #[serde(default)]
+    pub watch: WatchConfig,
+    // only ask the search api for objects edited since the last run
+    // deleted or unshared pages never show up as edited, they are dropped by the full search every `full_search_after_hours`
+    #[serde(default)]
+    pub incremental_search: bool,
+    #[serde(default = "default_full_search_after_hours")]
+    pub full_search_after_hours: u64,
+    // notion page whose yaml code blocks override roots, excludes and flavor of every job
+    #[serde(default)]
+    pub config_page: Option<String>,
+    // notion page that gets a summary of every run, its previous content is replaced
+    #[serde(default)]
+    pub status_page: Option<String>,
+    // chat webhooks that get a summary after every run
+    #[serde(default)]
+    pub notifications: Vec<Notification>,
+    #[serde(default)]
+    pub tasks: TasksConfig,
+    // pages that `new --template <name>` copies
+    #[serde(default)]
+    pub templates: Vec<PageTemplate>,
+    // notion api endpoint, for mock servers in tests, api gateways or recording proxies
+    #[serde(default)]
+    pub api_base_url: Option<String>,
+    // for tokens that should never change the workspace, every write request is refused
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    // every api response is kept here so `--offline` can render without notion
+    #[serde(default)]
+    pub raw_archive: Option<PathBuf>,
+}
+
+// rate limited, failed and timed out requests are tried again after a growing pause
+// writes are only retried when notion rate limited them, anything else might have gone through
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct RetryConfig {
+    // 0 turns retries off
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    // doubled on every attempt, the actual pause is somewhere between half and all of it
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    // a request without a complete response by then counts as failed
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            request_timeout_ms: default_request_timeout_ms(),
+        }
+    }
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_request_timeout_ms() -> u64 {
+    60_000
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Notification {
+    pub service: ChatService,
+    // incoming webhook url of the slack app or discord channel
+    pub webhook_url: String,
+    // stay quiet unless the run failed or some pages couldn't be exported
+    #[serde(default)]
+    pub only_failures: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatService {
+    Slack,
+    Discord,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PageTemplate {
+    pub name: String,
+    pub page_id: String,
+    // where new pages are created, next to the template when unset
+    #[serde(default)]
+    pub parent_page_id: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+// settings for the assigned-to-me agenda
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct TasksConfig {
+    // notion user ids that count as "me", the api has no notion of the person running the bridge
+    #[serde(default)]
+    pub user_ids: Vec<String>,
+    #[serde(default)]
+    pub databases: Vec<TaskDatabase>,
+    // printed to stdout when neither this nor --output is set
+    #[serde(default)]
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TaskDatabase {
+    pub database_id: String,
+    // name of the source that can see the database, the first source when unset
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default = "default_person_property")]
+    pub person_property: String,
+    // rows whose status is one of the done values are left out
+    #[serde(default)]
+    pub status_property: Option<String>,
+    #[serde(default = "default_done_values")]
+    pub done_values: Vec<String>,
+    #[serde(default)]
+    pub due_property: Option<String>,
+}
+
+fn default_person_property() -> String {
+    String::from("Assignee")
+}
+
+fn default_done_values() -> Vec<String> {
+    vec![String::from("Done"), String::from("true")]
+}
+
+// pages of a source are exported into a subdirectory named after it
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SourceConfig {
+    pub name: String,
     pub notion_api_key: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WatchConfig {
+    #[serde(default = "default_watch_interval_seconds")]
+    pub interval_seconds: u64,
+    // every cycle that changed something is appended here, rotated once it gets too big
+    #[serde(default)]
+    pub changelog_file: Option<PathBuf>,
+    #[serde(default = "default_changelog_max_bytes")]
+    pub changelog_max_bytes: u64,
+    #[serde(default = "default_changelog_keep")]
+    pub changelog_keep: usize,
+    // receives the changes of every cycle as json
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    #[serde(default)]
+    pub include_diffs: bool,
+    // only changes matching one of these rules trigger the webhook, all changes do when empty
+    #[serde(default)]
+    pub rules: Vec<WatchRule>,
+}
+
+// every condition that is set has to match
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct WatchRule {
+    #[serde(default)]
+    pub roots: Vec<String>,
+    #[serde(default)]
+    pub database_id: Option<String>,
+    #[serde(default)]
+    pub property: Option<String>,
+    #[serde(default)]
+    pub equals: Option<String>,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: default_watch_interval_seconds(),
+            changelog_file: None,
+            changelog_max_bytes: default_changelog_max_bytes(),
+            changelog_keep: default_changelog_keep(),
+            webhook_url: None,
+            include_diffs: false,
+            rules: Vec::new(),
+        }
+    }
+}
+
+fn default_watch_interval_seconds() -> u64 {
+    300
+}
+
+fn default_changelog_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_changelog_keep() -> usize {
+    5
+}
+
+fn default_full_search_after_hours() -> u64 {
+    24
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ExportJob {
+    pub name: String,
+    #[serde(flatten)]
+    pub export: ExportConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ExportConfig {
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+    // page or database ids to export, everything the integration can see when empty
+    #[serde(default)]
+    pub roots: Vec<String>,
+    // databases whose rows are exported as daily notes
+    #[serde(default)]
+    pub daily_note_databases: Vec<DailyNoteDatabase>,
+    #[serde(default)]
+    pub list_style: ListStyle,
+    // shell command generating alt text for images without a caption, gets the image url as $1
+    #[serde(default)]
+    pub image_alt_text_command: Option<String>,
+    // shell command rendering equation blocks to svg images, gets the tex expression as $1
+    #[serde(default)]
+    pub equation_svg_command: Option<String>,
+    #[serde(default)]
+    pub flavor: Flavor,
+    // download images next to the notes instead of linking to expiring notion urls
+    #[serde(default)]
+    pub download_attachments: bool,
+    #[serde(default)]
+    pub attachments_layout: AttachmentsLayout,
+    // write code blocks to code/<page-slug>/<n>.<ext> and embed them in the note
+    #[serde(default)]
+    pub extract_code_blocks: bool,
+    // pages below these are left out even when they are under a root
+    #[serde(default)]
+    pub excludes: Vec<String>,
+    // append comment threads to every note, costs one request per block
+    #[serde(default)]
+    pub export_comments: bool,
+    // also write the threads to <note>.comments.json for tooling
+    #[serde(default)]
+    pub comments_sidecar: bool,
+    // databases streamed row by row into tabular files
+    #[serde(default)]
+    pub database_exports: Vec<DatabaseExport>,
+    // wiki pages keep their hierarchy as folders and get owners and verification in frontmatter
+    #[serde(default)]
+    pub wikis: Vec<WikiDatabase>,
+    // number properties copied into frontmatter and ranked by the stats command
+    #[serde(default)]
+    pub metrics: Vec<MetricProperty>,
+    // database rows go into a folder named after the database next to a generated index note
+    // and inline databases are followed even when their rows aren't under a root
+    #[serde(default)]
+    pub database_as_notes: bool,
+    // checkbox property that keeps a page out of the export when ticked, like "Exclude from export"
+    #[serde(default)]
+    pub exclude_property: Option<String>,
+    // replacements for the headings and labels the bridge writes, keyed like `child_page`
+    #[serde(default)]
+    pub translations: HashMap<String, String>,
+    // summary of what changed since the previous run, written to export-report.<format>
+    #[serde(default)]
+    pub report: Option<ReportFormat>,
+    // databases whose rows are named after another property or their first heading
+    #[serde(default)]
+    pub title_sources: Vec<TitleSource>,
+    // flat writes every note into output_dir, hierarchy nests subpages like the notion sidebar
+    #[serde(default)]
+    pub folders: FolderLayout,
+    // how the emoji icon of a page shows up on the folder holding its subpages
+    #[serde(default)]
+    pub folder_icons: FolderIcons,
+    // files hosted on google drive or dropbox are linked with the provider name unless this is set
+    #[serde(default)]
+    pub embed_external_files: bool,
+    // export subpages and inline databases found while rendering, even when the search missed them
+    #[serde(default)]
+    pub recursive: bool,
+    // database rows get all their properties in the frontmatter
+    #[serde(default)]
+    pub property_frontmatter: bool,
+    // everything besides markdown is rendered from the same fetch into <output_dir>/<format>/
+    #[serde(default = "default_formats")]
+    pub formats: Vec<OutputFormat>,
+    // how column lists are laid out
+    #[serde(default)]
+    pub columns: ColumnLayout,
+    // markdown has no underline, it is dropped unless html is allowed
+    #[serde(default)]
+    pub underline: UnderlineStyle,
+    // markdown has no colors either
+    #[serde(default)]
+    pub text_color: TextColorStyle,
+    #[serde(default)]
+    pub toggles: ToggleStyle,
+    // database whose rows are terms linked from the first place every page mentions them
+    #[serde(default)]
+    pub glossary: Option<GlossaryConfig>,
+    // per database choice of frontmatter properties, keys and types, replaces property_frontmatter there
+    #[serde(default)]
+    pub frontmatter_mappings: Vec<FrontmatterMapping>,
+    // obsidian bases or dataview notes showing the exported rows of databases as a table
+    #[serde(default)]
+    pub database_views: Option<DatabaseViewsConfig>,
+    // obsidian callout type of a notion callout, the first matching rule wins and `info` is the fallback
+    #[serde(default = "default_callout_types")]
+    pub callout_types: Vec<CalloutRule>,
+    // commits the output directory after every run that changed something in it
+    #[serde(default)]
+    pub git: Option<GitConfig>,
+    // terminology checked in every note after the export
+    #[serde(default)]
+    pub lint: Option<LintConfig>,
+    // sensitive text and pages kept out of public exports of a mixed workspace
+    #[serde(default)]
+    pub redaction: Option<RedactionConfig>,
+    // notes too big for editors are split at their top level headings
+    #[serde(default)]
+    pub split_pages: Option<SplitConfig>,
+    // notes as hugo content, with hugo frontmatter and relref links between notes
+    #[serde(default)]
+    pub hugo: Option<HugoConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HugoConfig {
+    #[serde(default)]
+    pub frontmatter: FrontmatterFormat,
+    // date property for `date`, the creation time of the page when unset or empty
+    #[serde(default)]
+    pub date_property: Option<String>,
+    // checkbox property for `draft`
+    #[serde(default = "default_hugo_draft_property")]
+    pub draft_property: String,
+    // select or multi select property for `tags`
+    #[serde(default = "default_hugo_tags_property")]
+    pub tags_property: String,
+}
+
+fn default_hugo_draft_property() -> String {
+    String::from("Draft")
+}
+
+fn default_hugo_tags_property() -> String {
+    String::from("Tags")
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FrontmatterFormat {
+    // between `---` lines
+    #[default]
+    Yaml,
+    // between `+++` lines
+    Toml,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SplitConfig {
+    // size of the rendered note in bytes
+    #[serde(default = "default_split_max_bytes")]
+    pub max_bytes: usize,
+}
+
+fn default_split_max_bytes() -> usize {
+    1_000_000
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RedactionConfig {
+    // regular expressions, every match in notes, frontmatter and json is replaced
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default = "default_redaction_replacement")]
+    pub replacement: String,
+    // pages with a property value, like a tag `Internal`
+    #[serde(default)]
+    pub pages: Vec<PageRedaction>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PageRedaction {
+    pub property: String,
+    // one of the options of a multi select, checkboxes match `true`
+    pub value: String,
+    #[serde(default)]
+    pub action: RedactionAction,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionAction {
+    // left out like an excluded page
+    #[default]
+    Exclude,
+    // the note is kept with its title so links still resolve, the body is replaced
+    Redact,
+}
+
+fn default_redaction_replacement() -> String {
+    String::from("[REDACTED]")
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LintConfig {
+    // words and phrases that shouldn't appear at all, matched ignoring case
+    #[serde(default)]
+    pub banned: Vec<BannedTerm>,
+    // names with a fixed spelling like `GitHub`, any other casing is reported
+    #[serde(default)]
+    pub terms: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BannedTerm {
+    pub word: String,
+    #[serde(default)]
+    pub suggestion: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct GitConfig {
+    #[serde(default)]
+    pub push: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DatabaseViewsConfig {
+    #[serde(default)]
+    pub format: DatabaseViewFormat,
+    pub databases: Vec<DatabaseView>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DatabaseView {
+    pub database_id: String,
+    // columns after the note name, every property in the frontmatter when empty
+    #[serde(default)]
+    pub properties: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabaseViewFormat {
+    // `<database>.base` for the obsidian bases core plugin
+    #[default]
+    Base,
+    // `<database> view.md` holding a dataview table query
+    Dataview,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CalloutRule {
+    // matches with or without the emoji variation selector, a rule without conditions matches everything
+    #[serde(default)]
+    pub emoji: Option<String>,
+    #[serde(default)]
+    pub color: Option<TextColor>,
+    #[serde(rename = "type")]
+    pub callout_type: String,
+}
+
+fn default_callout_types() -> Vec<CalloutRule> {
+    let emoji = |emoji: &str, callout_type: &str| CalloutRule {
+        emoji: Some(emoji.to_owned()),
+        color: None,
+        callout_type: callout_type.to_owned(),
+    };
+    let color = |color: TextColor, callout_type: &str| CalloutRule {
+        emoji: None,
+        color: Some(color),
+        callout_type: callout_type.to_owned(),
+    };
+    vec![
+        emoji("⚠️", "warning"),
+        emoji("🚧", "warning"),
+        emoji("💡", "tip"),
+        emoji("🔥", "tip"),
+        emoji("❗", "danger"),
+        emoji("‼️", "danger"),
+        emoji("🚨", "danger"),
+        emoji("⛔", "danger"),
+        emoji("🛑", "danger"),
+        emoji("❌", "failure"),
+        emoji("✅", "success"),
+        emoji("✔️", "success"),
+        emoji("❓", "question"),
+        emoji("🤔", "question"),
+        emoji("📝", "note"),
+        emoji("✏️", "note"),
+        emoji("📌", "important"),
+        emoji("🐛", "bug"),
+        emoji("💬", "quote"),
+        emoji("📋", "todo"),
+        emoji("🔍", "example"),
+        emoji("📄", "abstract"),
+        emoji("ℹ️", "info"),
+        color(TextColor::RedBackground, "danger"),
+        color(TextColor::Red, "danger"),
+        color(TextColor::YellowBackground, "warning"),
+        color(TextColor::OrangeBackground, "warning"),
+        color(TextColor::GreenBackground, "success"),
+        color(TextColor::BlueBackground, "info"),
+        color(TextColor::PurpleBackground, "example"),
+    ]
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FrontmatterMapping {
+    pub database_id: String,
+    // written in this order, properties not listed are left out
+    pub properties: Vec<PropertyMapping>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PropertyMapping {
+    pub property: String,
+    // frontmatter field name, the property name when unset
+    #[serde(default)]
+    pub key: Option<String>,
+    // the type the value is turned into, the natural one for the property type when unset
+    #[serde(default)]
+    pub coerce: Option<Coercion>,
+}
+
+impl PropertyMapping {
+    pub fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or(&self.property)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Coercion {
+    Text,
+    // comma separated text is split
+    List,
+    Bool,
+    Number,
+    // iso date or date time, ranges keep their start
+    Date,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GlossaryConfig {
+    pub database_id: String,
+    // text property explaining the term, used for abbr tooltips
+    #[serde(default)]
+    pub definition_property: Option<String>,
+    #[serde(default)]
+    pub style: GlossaryStyle,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GlossaryStyle {
+    // a link to the note of the term
+    #[default]
+    Link,
+    // `<abbr title="definition">term</abbr>` for html output, terms without a definition are linked
+    Abbr,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ToggleStyle {
+    // `<details>` html, folds in most markdown viewers
+    #[default]
+    Details,
+    // `> [!note]- Title`, folds in obsidian live preview as well
+    Callout,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UnderlineStyle {
+    #[default]
+    Ignore,
+    // `<u>text</u>`
+    Html,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TextColorStyle {
+    #[default]
+    Ignore,
+    // `<span style="color: red">text</span>`
+    Html,
+    // background colors become obsidian highlights `==text==`, text colors are dropped
+    Highlight,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnLayout {
+    // one column after another, markdown has no columns
+    #[default]
+    Sequential,
+    // flexbox divs keeping the width ratios set in notion, for the html format
+    Html,
+}
+
+fn default_formats() -> Vec<OutputFormat> {
+    vec![OutputFormat::Markdown]
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    Markdown,
+    Html,
+    // the raw page and block tree
+    Json,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    // the subdirectory the format is written into when there are several
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        match text.trim() {
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            "json" => Ok(OutputFormat::Json),
+            other => anyhow::bail!("unknown format {other}, expected markdown, html or json"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderLayout {
+    #[default]
+    Flat,
+    // `Parent.md` next to `Parent/Child.md`, database rows in a folder named after the database
+    Hierarchy,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderIcons {
+    #[default]
+    None,
+    // `🚀 Launch/`
+    Prefix,
+    // a `.folder-icon` file holding the emoji inside the folder
+    File,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TitleSource {
+    pub database_id: String,
+    // text property holding the real title
+    #[serde(default)]
+    pub property: Option<String>,
+    // use the first h1 of the page when the property is unset or empty
+    #[serde(default)]
+    pub first_heading: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MetricProperty {
+    pub property: String,
+    // frontmatter field name, the property name when unset
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+impl MetricProperty {
+    pub fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or(&self.property)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WikiDatabase {
+    pub database_id: String,
+    #[serde(default = "default_owner_property")]
+    pub owner_property: String,
+    #[serde(default = "default_verification_property")]
+    pub verification_property: String,
+}
+
+fn default_owner_property() -> String {
+    String::from("Owner")
+}
+
+fn default_verification_property() -> String {
+    String::from("Verification")
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DatabaseExport {
+    pub database_id: String,
+    pub format: TableFormat,
+    // defaults to databases/<database-slug>.<format> in the output directory
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    // adds a body column with every row page rendered to markdown
+    #[serde(default)]
+    pub include_body: bool,
+    // writes the properties with formula expressions, rollup and relation settings
+    // next to the table as <name>.schema.yaml so the logic of the database can be reviewed in version control
+    #[serde(default)]
+    pub include_schema: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TableFormat {
+    Csv,
+    Jsonl,
+    Sqlite,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: default_output_dir(),
+            roots: Vec::new(),
+            daily_note_databases: Vec::new(),
+            list_style: ListStyle::default(),
+            image_alt_text_command: None,
+            equation_svg_command: None,
+            flavor: Flavor::default(),
+            download_attachments: false,
+            attachments_layout: AttachmentsLayout::default(),
+            extract_code_blocks: false,
+            excludes: Vec::new(),
+            export_comments: false,
+            comments_sidecar: false,
+            database_exports: Vec::new(),
+            wikis: Vec::new(),
+            metrics: Vec::new(),
+            database_as_notes: false,
+            exclude_property: None,
+            translations: HashMap::new(),
+            report: None,
+            title_sources: Vec::new(),
+            folders: FolderLayout::Flat,
+            folder_icons: FolderIcons::None,
+            embed_external_files: false,
+            recursive: false,
+            property_frontmatter: false,
+            formats: default_formats(),
+            columns: ColumnLayout::Sequential,
+            underline: UnderlineStyle::Ignore,
+            text_color: TextColorStyle::Ignore,
+            toggles: ToggleStyle::Details,
+            glossary: None,
+            frontmatter_mappings: Vec::new(),
+            database_views: None,
+            callout_types: default_callout_types(),
+            git: None,
+            lint: None,
+            redaction: None,
+            split_pages: None,
+            hugo: None,
+        }
+    }
+}
+
+// obsidian uses wikilink embeds, standard sticks to commonmark syntax (typora and friends)
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Flavor {
+    #[default]
+    Obsidian,
+    Standard,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentsLayout {
+    // one attachments directory for the whole export
+    #[default]
+    Global,
+    // a `Page Title.assets` directory next to every note
+    PerNote,
+    // a directory named like the note, same as the notion markdown export
+    Notion,
+}
+
+// tight lists have no blank lines between items, loose lists separate every item
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ListStyle {
+    #[default]
+    Tight,
+    Loose,
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("output")
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DailyNoteDatabase {
+    pub database_id: String,
+    // date property used for the note name, falls back to created time
+    #[serde(default)]
+    pub date_property: Option<String>,
+}
+
 impl AppConfig {
     pub fn new(notion_api_key: String) -> Self {
-        Self { notion_api_key }
+        Self {
+            notion_api_key,
+            ..Default::default()
+        }
+    }
+
+    pub fn api_base_url(&self) -> &str {
+        self.api_base_url.as_deref().unwrap_or(NOTION_API_URL)
+    }
+
+    // the command line replaces the formats of every job
+    pub fn set_formats(&mut self, formats: &[OutputFormat]) {
+        self.export.formats = formats.to_vec();
+        for job in &mut self.jobs {
+            job.export.formats = formats.to_vec();
+        }
+    }
+
+    pub fn export_jobs(&self) -> Vec<ExportJob> {
+        if self.jobs.is_empty() {
+            vec![ExportJob {
+                name: String::from("default"),
+                export: self.export.clone(),
+            }]
+        } else {
+            self.jobs.clone()
+        }
     }
 
-    pub fn load_user_config() -> anyhow::Result<Self> {
+    pub fn load_user_config(preset: Option<Preset>) -> anyhow::Result<Self> {
         let config_file_path = get_config_file_path()?;
-        let settings = Config::builder()
-            .add_source(config::File::from(config_file_path))
+        let builder = match preset {
+            None => Config::builder().add_source(config::File::from(config_file_path)),
+            // the preset is merged below the user config so anything set there still wins
+            Some(preset) => {
+                let path = user_config_file_path()?;
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read config file {}", path.display()))?;
+                let mut user_config: serde_yaml::Value = serde_yaml::from_str(&text)
+                    .with_context(|| format!("failed to parse config file {}", path.display()))?;
+                presets::apply_preset(preset, &mut user_config)?;
+                Config::builder().add_source(config::File::from_str(
+                    &serde_yaml::to_string(&user_config)?,
+                    config::FileFormat::Yaml,
+                ))
+            }
+        };
+        let settings = builder
             .add_source(config::Environment::with_prefix("CHATTY"))
             .build()?;
 
@@ -47,7 +953,7 @@ impl AppConfig {
     }
 
     pub fn save_user_config(&self) -> anyhow::Result<()> {
-        let config_file_path = get_config_file_path()?.with_extension(CONFIG_FILE_EXTENSION);
+        let config_file_path = user_config_file_path()?;
 
         std::fs::create_dir_all(
             config_file_path
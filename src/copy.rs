@@ -0,0 +1,406 @@
+use crate::{
+    api::{find_source, NotionClient, Source},
+    append::{append_blocks, NewBlock, APPEND_CHUNK_SIZE},
+    blocks::fetch_block_tree_until,
+    capabilities::{self, Capability},
+    ids,
+};
+use anyhow::{bail, Context};
+use notion::ids::{BlockId, PageId};
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+
+// fields notion sets itself and rejects when creating blocks
+const READ_ONLY_FIELDS: &[&str] = &[
+    "object",
+    "id",
+    "parent",
+    "created_time",
+    "last_edited_time",
+    "created_by",
+    "last_edited_by",
+    "has_children",
+    "archived",
+    "in_trash",
+];
+
+// these can't be created empty so their children are sent along in the same request
+const INLINE_CHILDREN_TYPES: &[&str] = &["column_list", "column", "table"];
+
+// block types the api can create, child pages and databases are separate documents
+const CREATABLE_TYPES: &[&str] = &[
+    "audio",
+    "bookmark",
+    "breadcrumb",
+    "bulleted_list_item",
+    "callout",
+    "code",
+    "column",
+    "column_list",
+    "divider",
+    "embed",
+    "equation",
+    "file",
+    "heading_1",
+    "heading_2",
+    "heading_3",
+    "image",
+    "link_to_page",
+    "numbered_list_item",
+    "paragraph",
+    "pdf",
+    "quote",
+    "synced_block",
+    "table",
+    "table_of_contents",
+    "table_row",
+    "to_do",
+    "toggle",
+    "video",
+];
+
+// the public api has no cross workspace copy, so the page is read with one source and rebuilt block by block with the other
+pub async fn copy_page(
+    sources: &[Source],
+    page: &str,
+    to_parent: &str,
+    source: Option<&str>,
+    to_source: Option<&str>,
+    link_files: bool,
+) -> anyhow::Result<()> {
+    let reader = &find_source(sources, source)?.client;
+    let writer = &find_source(sources, to_source)?.client;
+    writer.ensure_writable()?;
+    let page_id = ids::parse_page_reference(page)
+        .with_context(|| format!("{page} is neither a page id nor a notion url"))?;
+    let page_id = PageId::from_str(&page_id)?;
+    let parent_id = ids::parse_page_reference(to_parent)
+        .with_context(|| format!("{to_parent} is neither a page id nor a notion url"))?;
+    let parent_id = PageId::from_str(&parent_id)?;
+    capabilities::require(writer, Capability::InsertContent, &parent_id).await?;
+
+    let page = reader.get_page(page_id.clone()).await?;
+    // the raw tree keeps fields and block types the notion crate doesn't model
+    let tree = fetch_block_tree_until(reader, page_id.into(), None, true).await?;
+    let files = hosted_files(&tree.raw);
+    if !files.is_empty() && !link_files {
+        bail!(
+            "the page has {} notion hosted files, the api can't upload them and their links expire after about an hour, pass --link-files to copy them as links anyway",
+            files.len()
+        );
+    }
+
+    let title = page.title().unwrap_or_default();
+    let mut body = json!({
+        "parent": { "page_id": parent_id.to_string() },
+        "properties": {
+            "title": { "title": [{ "text": { "content": title } }] }
+        },
+    });
+    // uploaded icons can't be reused, only emojis and external links
+    if let Some(icon) = &page.icon {
+        let icon = serde_json::to_value(icon)?;
+        if icon.get("type").and_then(Value::as_str) != Some("file") {
+            body["icon"] = icon;
+        }
+    }
+    let created_page = writer.create_page(&body).await?;
+    let new_page_id = created_page
+        .get("id")
+        .and_then(Value::as_str)
+        .context("created page has no id")?;
+
+    let same_source = source == to_source;
+    copy_blocks(
+        writer,
+        BlockId::from_str(new_page_id)?,
+        &tree.raw,
+        !same_source,
+    )
+    .await?;
+
+    let url = created_page
+        .get("url")
+        .and_then(Value::as_str)
+        .unwrap_or(new_page_id);
+    println!("Copied {title} {url}");
+    Ok(())
+}
+
+// `blocks` is a raw tree with children nested as `<type>.children`
+// with `portable` mentions, page links and synced blocks lose the ids only the source workspace knows
+pub async fn copy_blocks(
+    notion_client: &NotionClient,
+    parent_id: BlockId,
+    blocks: &[Value],
+    portable: bool,
+) -> anyhow::Result<()> {
+    let blocks = new_blocks(blocks, portable);
+    append_blocks(notion_client, parent_id, &blocks).await
+}
+
+fn new_blocks(blocks: &[Value], portable: bool) -> Vec<NewBlock> {
+    blocks
+        .iter()
+        .filter(|block| is_copyable(block))
+        .map(|block| {
+            let children = new_blocks(block_children(block), portable);
+            let mut new_block = NewBlock::new(block_to_json(block, portable));
+            // sent along since these can't be created empty, their own children follow once they exist
+            let block_type = block_type(&new_block.block).to_owned();
+            if INLINE_CHILDREN_TYPES.contains(&block_type.as_str()) {
+                new_block.block[&block_type]["children"] = children
+                    .iter()
+                    .take(APPEND_CHUNK_SIZE)
+                    .map(|child| child.block.clone())
+                    .collect();
+            }
+            new_block.children = children;
+            new_block
+        })
+        .collect()
+}
+
+// notion hosted files anywhere in the tree
+fn hosted_files(blocks: &[Value]) -> Vec<String> {
+    blocks
+        .iter()
+        .flat_map(|block| {
+            let content = &block[block_type(block)];
+            let url = (content["type"] == "file").then(|| {
+                content["file"]["url"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_owned()
+            });
+            url.into_iter().chain(hosted_files(block_children(block)))
+        })
+        .collect()
+}
+
+fn block_type(block: &Value) -> &str {
+    block
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+fn is_copyable(block: &Value) -> bool {
+    let copyable = CREATABLE_TYPES.contains(&block_type(block));
+    if !copyable && !matches!(block_type(block), "child_page" | "child_database") {
+        eprintln!(
+            "Skipping {} block, the api can't create it",
+            block_type(block)
+        );
+    }
+    copyable
+}
+
+fn block_children(block: &Value) -> &[Value] {
+    block
+        .get(block_type(block))
+        .and_then(|content| content.get("children"))
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn block_to_json(block: &Value, portable: bool) -> Value {
+    let mut value = block.clone();
+    clean_block(&mut value);
+    if portable {
+        make_portable(&mut value);
+        replace_mentions(&mut value);
+    }
+    value
+}
+
+fn clean_block(value: &mut Value) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+    for field in READ_ONLY_FIELDS {
+        object.remove(*field);
+    }
+    let Some(block_type) = object
+        .get("type")
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+    else {
+        return;
+    };
+    let Some(content) = object.get_mut(&block_type).and_then(Value::as_object_mut) else {
+        return;
+    };
+    external_file_reference(content);
+    content.remove("children");
+}
+
+// notion hosted files can't be uploaded through the api
+// so copies link the signed url, which expires after about an hour
+fn external_file_reference(content: &mut Map<String, Value>) {
+    if content.get("type").and_then(Value::as_str) != Some("file") {
+        return;
+    }
+    if let Some(url) = content
+        .remove("file")
+        .and_then(|file| file.get("url").cloned())
+    {
+        eprintln!("Linking notion hosted file {url}, the link expires in about an hour");
+        content.insert(String::from("type"), json!("external"));
+        content.insert(String::from("external"), json!({ "url": url }));
+    }
+}
+
+// ids of pages, databases and users in another workspace can't be resolved
+fn make_portable(block: &mut Value) {
+    match block_type(block) {
+        // a link to the page in the source workspace
+        "link_to_page" => {
+            let target = &block["link_to_page"];
+            let id = target["page_id"]
+                .as_str()
+                .or_else(|| target["database_id"].as_str())
+                .unwrap_or_default()
+                .replace('-', "");
+            let url = format!("https://www.notion.so/{id}");
+            *block = json!({
+                "type": "paragraph",
+                "paragraph": {
+                    "rich_text": [{ "type": "text", "text": { "content": url, "link": { "url": url } } }]
+                },
+            });
+        }
+        // a copy of a synced block becomes an original of its own, its children are copied along
+        "synced_block" => block["synced_block"]["synced_from"] = Value::Null,
+        _ => {}
+    }
+}
+
+// mentions keep their text, and their link when they had one
+fn replace_mentions(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            let is_mention = object.get("type").and_then(Value::as_str) == Some("mention");
+            let is_date = object
+                .get("mention")
+                .and_then(|mention| mention.get("type"))
+                .and_then(Value::as_str)
+                == Some("date");
+            if is_mention && !is_date {
+                let text = object
+                    .get("plain_text")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned();
+                let link = object
+                    .get("href")
+                    .and_then(Value::as_str)
+                    .map(|href| json!({ "url": href }));
+                let annotations = object.get("annotations").cloned();
+                object.clear();
+                object.insert(String::from("type"), json!("text"));
+                object.insert(
+                    String::from("text"),
+                    json!({ "content": text, "link": link }),
+                );
+                if let Some(annotations) = annotations {
+                    object.insert(String::from("annotations"), annotations);
+                }
+                return;
+            }
+            object.values_mut().for_each(replace_mentions);
+        }
+        Value::Array(values) => values.iter_mut().for_each(replace_mentions),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_become_text_and_dates_stay() {
+        let block = json!({
+            "object": "block",
+            "id": "a",
+            "type": "paragraph",
+            "has_children": false,
+            "paragraph": {
+                "rich_text": [
+                    {
+                        "type": "mention",
+                        "mention": { "type": "page", "page": { "id": "b" } },
+                        "plain_text": "Roadmap",
+                        "href": "https://www.notion.so/b",
+                        "annotations": { "bold": true },
+                    },
+                    {
+                        "type": "mention",
+                        "mention": { "type": "date", "date": { "start": "2024-05-01" } },
+                        "plain_text": "2024-05-01",
+                    },
+                ],
+            },
+        });
+        assert_eq!(
+            block_to_json(&block, true),
+            json!({
+                "type": "paragraph",
+                "paragraph": {
+                    "rich_text": [
+                        {
+                            "type": "text",
+                            "text": { "content": "Roadmap", "link": { "url": "https://www.notion.so/b" } },
+                            "annotations": { "bold": true },
+                        },
+                        {
+                            "type": "mention",
+                            "mention": { "type": "date", "date": { "start": "2024-05-01" } },
+                            "plain_text": "2024-05-01",
+                        },
+                    ],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn children_below_columns_are_kept() {
+        let paragraph = |text: &str| {
+            json!({
+                "type": "paragraph",
+                "paragraph": { "rich_text": [{ "type": "text", "text": { "content": text } }] },
+            })
+        };
+        let columns = json!({
+            "id": "a",
+            "type": "column_list",
+            "column_list": {
+                "children": [{
+                    "id": "b",
+                    "type": "column",
+                    "column": {
+                        "children": [{
+                            "id": "c",
+                            "type": "toggle",
+                            "has_children": true,
+                            "toggle": { "rich_text": [], "children": [paragraph("hidden")] },
+                        }],
+                    },
+                }],
+            },
+        });
+        let blocks = new_blocks(&[columns], false);
+
+        let toggle = &blocks[0].block["column_list"]["children"][0]["column"]["children"][0];
+        assert_eq!(
+            toggle,
+            &json!({ "type": "toggle", "toggle": { "rich_text": [] } })
+        );
+        let toggle = &blocks[0].children[0].children[0];
+        assert_eq!(toggle.children.len(), 1);
+        assert_eq!(toggle.children[0].block, paragraph("hidden"));
+    }
+}
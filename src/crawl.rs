@@ -0,0 +1,124 @@
+use crate::{
+    configuration::{ExportConfig, RedactionAction},
+    ids::normalize_id,
+    layout,
+    properties::page_checkbox,
+    redaction,
+    state::state_dir,
+    workspace::Workspace,
+};
+use chrono::{DateTime, Utc};
+use notion::models::{Page, Parent};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+const PAGE_GRAPH_FILE_NAME: &str = "page-graph.json";
+
+// every page of a job with its place in the hierarchy, known before anything is rendered
+// rows of inline databases are only found while rendering and aren't part of it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PageGraph {
+    pub crawled_at: DateTime<Utc>,
+    // in export order
+    pub pages: Vec<GraphNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GraphNode {
+    // normalized
+    pub page_id: String,
+    pub title: String,
+    // page or database this one lives in, normalized
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    pub path: PathBuf,
+    // pages of the graph directly below this one
+    #[serde(default)]
+    pub children: Vec<String>,
+}
+
+pub fn is_excluded(config: &ExportConfig, page: &Page) -> bool {
+    let excluded = config
+        .exclude_property
+        .as_ref()
+        .map(|property| page_checkbox(page, property))
+        .unwrap_or(false);
+    excluded || redaction::page_action(config, page) == Some(RedactionAction::Exclude)
+}
+
+pub fn crawl(workspace: &Workspace, config: &ExportConfig) -> PageGraph {
+    let mut pages = vec![];
+    for page in workspace.pages_under_roots(&config.roots, &config.excludes) {
+        let title = page.title().unwrap_or_default();
+        if is_excluded(config, page) {
+            println!("Skipping excluded page {title}");
+            continue;
+        }
+        let parent_id = match &page.parent {
+            Parent::Page { page_id } => Some(normalize_id(&page_id.to_string())),
+            Parent::Database { database_id } => Some(normalize_id(&database_id.to_string())),
+            _ => None,
+        };
+        pages.push(GraphNode {
+            page_id: normalize_id(&page.id.to_string()),
+            title,
+            parent_id,
+            path: layout::page_output_path(config, workspace, page),
+            children: vec![],
+        });
+    }
+
+    let index: HashMap<String, usize> = pages
+        .iter()
+        .enumerate()
+        .map(|(position, node)| (node.page_id.clone(), position))
+        .collect();
+    for child in 0..pages.len() {
+        let parent = pages[child]
+            .parent_id
+            .as_ref()
+            .and_then(|parent_id| index.get(parent_id).copied());
+        if let Some(parent) = parent {
+            let child_id = pages[child].page_id.clone();
+            pages[parent].children.push(child_id);
+        }
+    }
+
+    PageGraph {
+        crawled_at: Utc::now(),
+        pages,
+    }
+}
+
+impl PageGraph {
+    pub fn node(&self, page_id: &str) -> Option<&GraphNode> {
+        let page_id = normalize_id(page_id);
+        self.pages.iter().find(|node| node.page_id == page_id)
+    }
+
+    pub fn path(output_dir: &Path) -> PathBuf {
+        state_dir(output_dir).join(PAGE_GRAPH_FILE_NAME)
+    }
+
+    pub fn load(output_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = Self::path(output_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = std::fs::File::open(&path)?;
+        Ok(Some(serde_json::from_reader(std::io::BufReader::new(
+            file,
+        ))?))
+    }
+
+    // kept between runs for tooling and for comparing what a run would change
+    pub fn save(&self, output_dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(state_dir(output_dir))?;
+        let file = std::fs::File::create(Self::path(output_dir))?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
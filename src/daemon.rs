@@ -0,0 +1,274 @@
+use crate::{
+    api::{self, Source},
+    configuration::{self, AppConfig, OutputFormat},
+    presets::Preset,
+    recording::Recording,
+};
+use anyhow::bail;
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+// how often the config file is checked for edits
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// everything needed to load the config the way the command line did
+// so watch and serve can pick up an edited config file without a restart
+pub struct ConfigLoader {
+    pub preset: Option<Preset>,
+    pub read_only: bool,
+    pub formats: Vec<OutputFormat>,
+    pub recording: Option<Recording>,
+    // replay the raw archive and stay away from anything else that needs the network
+    pub offline: bool,
+    // nothing is written, not even the raw archive
+    pub dry_run: bool,
+}
+
+impl ConfigLoader {
+    pub fn load_config(&self) -> anyhow::Result<AppConfig> {
+        let mut config = AppConfig::load_user_config(self.preset)?;
+        config.read_only |= self.read_only;
+        if !self.formats.is_empty() {
+            config.set_formats(&self.formats);
+        }
+        if self.offline {
+            config.read_only = true;
+            config.status_page = None;
+            config.notifications.clear();
+            // the archive only has the full search, not one filtered by the last run
+            config.incremental_search = false;
+        }
+        Ok(config)
+    }
+
+    pub fn connect_sources(&self, config: &AppConfig) -> anyhow::Result<Vec<Source>> {
+        api::connect_sources(config, self.recording(config)?.as_ref())
+    }
+
+    // --record and --replay win over the raw archive
+    fn recording(&self, config: &AppConfig) -> anyhow::Result<Option<Recording>> {
+        let recording = match (&self.recording, &config.raw_archive, self.offline) {
+            (Some(recording), _, _) => Some(recording.clone()),
+            (None, Some(dir), false) => Some(Recording::Record(dir.clone())),
+            (None, Some(dir), true) => Some(Recording::Replay(dir.clone())),
+            (None, None, true) => {
+                bail!("--offline needs raw_archive in the config or --replay <dir>")
+            }
+            (None, None, false) => None,
+        };
+        // dry runs may replay but never record
+        if self.dry_run {
+            return Ok(recording.filter(Recording::is_replay));
+        }
+        Ok(recording)
+    }
+
+    // api keys might have changed as well, so the sources are connected again
+    pub fn reload(&self) -> anyhow::Result<(AppConfig, Vec<Source>)> {
+        let config = self.load_config()?;
+        let sources = self.connect_sources(&config)?;
+        Ok((config, sources))
+    }
+}
+
+// notices edits to the config file by its modification time
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Self {
+        let path = configuration::user_config_file_path().ok();
+        let modified = path.as_deref().and_then(modified_time);
+        Self { path, modified }
+    }
+
+    // resolves once the file was written since the last time, never without a config dir
+    pub async fn changed(&mut self) {
+        let Some(path) = &self.path else {
+            return std::future::pending().await;
+        };
+        loop {
+            tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+            let modified = modified_time(path);
+            if modified != self.modified {
+                self.modified = modified;
+                return;
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
+
+// `export.roots: [..] -> [..]` for every setting that differs, secrets only show that they changed
+pub fn config_changes(old: &AppConfig, new: &AppConfig) -> anyhow::Result<Vec<String>> {
+    let mut changes = vec![];
+    diff_values(
+        "",
+        &serde_json::to_value(old)?,
+        &serde_json::to_value(new)?,
+        &mut changes,
+    );
+    Ok(changes)
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<String>) {
+    if old == new {
+        return;
+    }
+    if is_secret(path) {
+        changes.push(format!("{path} changed"));
+        return;
+    }
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                diff_values(
+                    &child_path(path, key),
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        // lists of the same length are compared item by item, jobs and sources mostly
+        (Value::Array(old), Value::Array(new))
+            if old.len() == new.len() && old.iter().chain(new).any(Value::is_object) =>
+        {
+            for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                diff_values(&child_path(path, &index.to_string()), old, new, changes);
+            }
+        }
+        _ => changes.push(format!(
+            "{path}: {} -> {}",
+            redact(path, old),
+            redact(path, new)
+        )),
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+// webhook urls carry their token in the url itself
+fn is_secret(path: &str) -> bool {
+    let key = path.rsplit('.').next().unwrap_or(path);
+    let section = path.split('.').next().unwrap_or(path);
+    key.ends_with("api_key")
+        || key.contains("token")
+        || key.contains("secret")
+        || (matches!(section, "notifications" | "watch") && key.ends_with("url"))
+}
+
+fn redact(path: &str, value: &Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| {
+                    let path = child_path(path, key);
+                    let value = if is_secret(&path) {
+                        Value::String(String::from("***"))
+                    } else {
+                        redact(&path, value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| redact(&child_path(path, &index.to_string()), item))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+// `kill -HUP` asks a running daemon for a full sync with a freshly loaded config
+#[cfg(unix)]
+pub struct HangupSignal(tokio::signal::unix::Signal);
+
+#[cfg(unix)]
+impl HangupSignal {
+    pub fn new() -> anyhow::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self(signal(SignalKind::hangup())?))
+    }
+
+    pub async fn recv(&mut self) {
+        if self.0.recv().await.is_none() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+// no SIGHUP outside of unix, serve still has the sync button
+#[cfg(not(unix))]
+pub struct HangupSignal;
+
+#[cfg(not(unix))]
+impl HangupSignal {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self)
+    }
+
+    pub async fn recv(&mut self) {
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configuration::{ChatService, Notification, SourceConfig};
+
+    #[test]
+    fn changes_name_the_setting_and_hide_secrets() {
+        let old = AppConfig::new(String::from("secret-one"));
+        let mut new = AppConfig::new(String::from("secret-two"));
+        new.export.roots = vec![String::from("abc")];
+        new.sources = vec![SourceConfig {
+            name: String::from("work"),
+            notion_api_key: String::from("secret-three"),
+        }];
+        let changes = config_changes(&old, &new).unwrap();
+        assert!(changes.contains(&String::from("notion_api_key changed")));
+        assert!(changes.contains(&String::from("export.roots: [] -> [\"abc\"]")));
+        assert!(changes.iter().all(|change| !change.contains("secret")));
+    }
+
+    #[test]
+    fn webhook_urls_are_hidden() {
+        let old = AppConfig::new(String::from("key"));
+        let mut new = old.clone();
+        new.watch.webhook_url = Some(String::from("https://hooks.example.com/secret-one"));
+        new.notifications = vec![Notification {
+            service: ChatService::Slack,
+            webhook_url: String::from("https://hooks.example.com/secret-two"),
+            only_failures: false,
+        }];
+        let changes = config_changes(&old, &new).unwrap();
+        assert!(changes.contains(&String::from("watch.webhook_url changed")));
+        assert!(changes
+            .iter()
+            .all(|change| !change.contains("hooks.example.com")));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    api::NotionClient,
+    configuration::{DatabaseExport, ExportConfig, RedactionAction, TableFormat},
+    export,
+    ids::normalize_id,
+    markdown::notion_page_id_to_url,
+    properties::property_text,
+    redaction::{self, Redactor},
+    slug::slugify,
+};
+use anyhow::Context;
+use futures::{stream, StreamExt, TryStreamExt};
+use notion::{
+    ids::DatabaseId,
+    models::{paging::Pageable, search::DatabaseQuery, Page},
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const DATABASES_DIR_NAME: &str = "databases";
+const FIXED_COLUMNS: &[&str] = &["id", "url", "created_time", "last_edited_time"];
+const BODY_COLUMN: &str = "body";
+// bodies in flight at once, the rate limiter still caps the request rate
+// but the slow block tree walks of different rows overlap
+const BODY_CONCURRENCY: usize = 8;
+
+// rows are written as each page of query results arrives
+// so large databases never have to be held in memory at once
+pub async fn export_database(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    database_export: &DatabaseExport,
+) -> anyhow::Result<PathBuf> {
+    let output_dir = &config.output_dir;
+    let database_id = DatabaseId::from_str(&normalize_id(&database_export.database_id))
+        .with_context(|| format!("invalid database id {}", database_export.database_id))?;
+    let database = notion_client.get_database(database_id.clone()).await?;
+    let title = database.title_plain_text();
+
+    let mut properties: Vec<String> = database.properties.keys().cloned().collect();
+    properties.sort();
+    let columns: Vec<String> = FIXED_COLUMNS
+        .iter()
+        .map(|column| column.to_string())
+        .chain(properties.iter().cloned())
+        .chain(database_export.include_body.then(|| BODY_COLUMN.to_owned()))
+        .collect();
+
+    let path = match &database_export.path {
+        Some(path) => path.clone(),
+        None => output_dir.join(DATABASES_DIR_NAME).join(format!(
+            "{}.{}",
+            slugify(&title),
+            database_export.format.extension()
+        )),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // written next to the target and moved over it at the end so readers never see half a file
+    let mut partial_path = path.clone().into_os_string();
+    partial_path.push(".partial");
+    let partial_path = PathBuf::from(partial_path);
+    if partial_path.exists() {
+        std::fs::remove_file(&partial_path)?;
+    }
+
+    let mut writer: Box<dyn RowWriter> = match database_export.format {
+        TableFormat::Csv => Box::new(CsvRowWriter::create(&partial_path, &columns)?),
+        TableFormat::Jsonl => Box::new(JsonlRowWriter::create(
+            &partial_path,
+            &columns,
+            database_export.include_body,
+        )?),
+        TableFormat::Sqlite => Box::new(SqliteRowWriter::create(&partial_path, &columns)?),
+    };
+
+    let redactor = Redactor::new(config).context("invalid redaction pattern")?;
+    let mut row_count = 0;
+    let mut response = notion_client
+        .query_database(database_id.clone(), DatabaseQuery::default())
+        .await?;
+    loop {
+        let bodies = if database_export.include_body {
+            row_bodies(notion_client, config, &response.results).await?
+        } else {
+            vec![]
+        };
+        for (index, row) in response.results.iter().enumerate() {
+            let mut values = row_values(row, &properties);
+            if database_export.include_body {
+                values.push(Some(bodies[index].clone()));
+            }
+            if let Some(redactor) = &redactor {
+                for value in values.iter_mut().flatten() {
+                    *value = redactor.redact(value);
+                }
+            }
+            writer.write_row(&values)?;
+            row_count += 1;
+        }
+        match response.next_cursor {
+            Some(cursor) => {
+                let query = DatabaseQuery::default().start_from(Some(cursor));
+                response = notion_client
+                    .query_database(database_id.clone(), query)
+                    .await?;
+            }
+            None => break,
+        }
+    }
+    writer.finish()?;
+    std::fs::rename(&partial_path, &path)?;
+
+    if database_export.include_schema {
+        let database_json = notion_client.get_database_json(&database_id).await?;
+        write_schema(&database_json, &path.with_extension("schema.yaml"))?;
+    }
+
+    println!("Database: {title} {row_count} rows -> {}", path.display());
+    Ok(path)
+}
+
+// property names sorted so the file only changes when the database does
+fn write_schema(database: &Value, path: &Path) -> anyhow::Result<()> {
+    let title: String = database["title"]
+        .as_array()
+        .map(|title| {
+            title
+                .iter()
+                .filter_map(|text| text["plain_text"].as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut properties = BTreeMap::new();
+    if let Some(configurations) = database["properties"].as_object() {
+        for (name, configuration) in configurations {
+            properties.insert(name.clone(), property_schema(configuration));
+        }
+    }
+    let schema = json!({
+        "database_id": database["id"],
+        "title": title,
+        "properties": properties,
+    });
+    let content = serde_yaml::to_string(&schema)?;
+    if std::fs::read_to_string(path).ok().as_deref() != Some(content.as_str()) {
+        std::fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+// the type of a property and whatever configures it, ids and colors are left out
+fn property_schema(configuration: &Value) -> Value {
+    let property_type = configuration["type"].as_str().unwrap_or_default();
+    let settings = &configuration[property_type];
+    let mut schema = Map::new();
+    schema.insert(String::from("type"), json!(property_type));
+    match property_type {
+        "formula" => {
+            schema.insert(String::from("expression"), settings["expression"].clone());
+        }
+        "rollup" => {
+            for (key, setting) in [
+                ("relation_property", "relation_property_name"),
+                ("rollup_property", "rollup_property_name"),
+                ("function", "function"),
+            ] {
+                schema.insert(key.to_owned(), settings[setting].clone());
+            }
+        }
+        "relation" => {
+            schema.insert(String::from("database_id"), settings["database_id"].clone());
+            if let Some(synced) = settings["dual_property"]["synced_property_name"].as_str() {
+                schema.insert(String::from("synced_property"), json!(synced));
+            }
+        }
+        "select" | "multi_select" | "status" => {
+            let options: Vec<&Value> = settings["options"]
+                .as_array()
+                .map(|options| options.iter().map(|option| &option["name"]).collect())
+                .unwrap_or_default();
+            schema.insert(String::from("options"), json!(options));
+        }
+        "number" => {
+            schema.insert(String::from("format"), settings["format"].clone());
+        }
+        _ => {}
+    }
+    Value::Object(schema)
+}
+
+// fetched concurrently but returned in row order so the file comes out the same every run
+async fn row_bodies(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    rows: &[Page],
+) -> anyhow::Result<Vec<String>> {
+    stream::iter(rows)
+        .map(|row| async move {
+            // like their notes, bodies of redacted rows are never fetched
+            match redaction::page_action(config, row) {
+                Some(RedactionAction::Redact | RedactionAction::Exclude) => Ok(config
+                    .redaction
+                    .as_ref()
+                    .map(|redaction| redaction.replacement.clone())
+                    .unwrap_or_default()),
+                None => export::page_body_markdown(notion_client, config, row).await,
+            }
+        })
+        .buffered(BODY_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+// values in column order, fixed columns first
+fn row_values(row: &Page, properties: &[String]) -> Vec<Option<String>> {
+    let mut values = vec![
+        Some(row.id.to_string()),
+        Some(notion_page_id_to_url(&row.id)),
+        Some(row.created_time.to_rfc3339()),
+        Some(row.last_edited_time.to_rfc3339()),
+    ];
+    values.extend(properties.iter().map(|property| {
+        row.properties
+            .properties
+            .get(property)
+            .and_then(property_text)
+    }));
+    values
+}
+
+impl TableFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            TableFormat::Csv => "csv",
+            TableFormat::Jsonl => "jsonl",
+            TableFormat::Sqlite => "sqlite",
+        }
+    }
+}
+
+trait RowWriter {
+    fn write_row(&mut self, values: &[Option<String>]) -> anyhow::Result<()>;
+    fn finish(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+struct CsvRowWriter {
+    writer: csv::Writer<File>,
+}
+
+impl CsvRowWriter {
+    fn create(path: &Path, columns: &[String]) -> anyhow::Result<Self> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(columns)?;
+        Ok(Self { writer })
+    }
+}
+
+impl RowWriter for CsvRowWriter {
+    fn write_row(&mut self, values: &[Option<String>]) -> anyhow::Result<()> {
+        self.writer.write_record(
+            values
+                .iter()
+                .map(|value| value.as_deref().unwrap_or_default()),
+        )?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct JsonlRowWriter {
+    writer: BufWriter<File>,
+    columns: Vec<String>,
+    // the last column is the body and goes next to the fixed ones
+    has_body: bool,
+}
+
+impl JsonlRowWriter {
+    fn create(path: &Path, columns: &[String], has_body: bool) -> anyhow::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            columns: columns.to_vec(),
+            has_body,
+        })
+    }
+}
+
+impl RowWriter for JsonlRowWriter {
+    fn write_row(&mut self, values: &[Option<String>]) -> anyhow::Result<()> {
+        let mut properties = Map::new();
+        let mut record = Map::new();
+        for (index, (column, value)) in self.columns.iter().zip(values).enumerate() {
+            let value = value.clone().map(Value::String).unwrap_or(Value::Null);
+            let is_body = self.has_body && index == self.columns.len() - 1;
+            if index < FIXED_COLUMNS.len() || is_body {
+                record.insert(column.clone(), value);
+            } else {
+                properties.insert(column.clone(), value);
+            }
+        }
+        record.insert(String::from("properties"), json!(properties));
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct SqliteRowWriter {
+    connection: rusqlite::Connection,
+    insert: String,
+}
+
+impl SqliteRowWriter {
+    fn create(path: &Path, columns: &[String]) -> anyhow::Result<Self> {
+        let connection = rusqlite::Connection::open(path)?;
+        let quoted: Vec<String> = columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect();
+        let definitions: Vec<String> = quoted
+            .iter()
+            .map(|column| format!("{column} TEXT"))
+            .collect();
+        connection.execute_batch(&format!(
+            "CREATE TABLE rows ({}); BEGIN;",
+            definitions.join(", ")
+        ))?;
+        let placeholders = vec!["?"; columns.len()].join(", ");
+        let insert = format!(
+            "INSERT INTO rows ({}) VALUES ({placeholders})",
+            quoted.join(", ")
+        );
+        Ok(Self { connection, insert })
+    }
+}
+
+impl RowWriter for SqliteRowWriter {
+    fn write_row(&mut self, values: &[Option<String>]) -> anyhow::Result<()> {
+        let mut statement = self.connection.prepare_cached(&self.insert)?;
+        statement.execute(rusqlite::params_from_iter(values))?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<()> {
+        self.connection.execute_batch("COMMIT;")?;
+        Ok(())
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
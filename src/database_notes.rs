@@ -0,0 +1,148 @@
+use crate::{
+    blocks,
+    configuration::ExportConfig,
+    escape,
+    ids::normalize_id,
+    layout::note_file_name,
+    markdown::{external_link, note_link},
+    workspace::Workspace,
+};
+use notion::models::block::Block;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+// a database shown inline on a page
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ChildDatabase {
+    pub database_id: String,
+    pub title: String,
+}
+
+// every database gets a folder holding its rows next to an index note of the same name
+// <output>/<database>/<database>.md and <output>/<database>/<row>.md
+pub fn database_folder(output_dir: &Path, title: &str) -> PathBuf {
+    output_dir.join(escape::file_name(title))
+}
+
+pub fn index_note_path(output_dir: &Path, title: &str) -> PathBuf {
+    database_folder(output_dir, title).join(note_file_name(title))
+}
+
+pub fn row_path(output_dir: &Path, database_title: &str, row_title: &str) -> PathBuf {
+    database_folder(output_dir, database_title).join(note_file_name(row_title))
+}
+
+// database objects are only known when the search returned them
+pub fn database_title(workspace: &Workspace, database_id: &str) -> String {
+    workspace
+        .database(database_id)
+        .map(|database| database.title_plain_text())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| database_id.to_owned())
+}
+
+pub fn child_databases(blocks: &[Block]) -> Vec<ChildDatabase> {
+    let mut child_databases = vec![];
+    blocks::walk_blocks(blocks, &mut |block| {
+        if let Block::ChildDatabase {
+            common,
+            child_database,
+        } = block
+        {
+            let database_id = common.id.to_string();
+            let title = if child_database.title.is_empty() {
+                database_id.clone()
+            } else {
+                child_database.title.clone()
+            };
+            child_databases.push(ChildDatabase { database_id, title });
+        }
+    });
+    child_databases
+}
+
+// index notes of the databases a page links to, keyed by block id for the renderer
+pub fn index_note_paths(
+    output_dir: &Path,
+    databases: &[ChildDatabase],
+) -> HashMap<String, PathBuf> {
+    databases
+        .iter()
+        .map(|database| {
+            (
+                database.database_id.clone(),
+                index_note_path(output_dir, &database.title),
+            )
+        })
+        .collect()
+}
+
+struct DatabaseIndex {
+    title: String,
+    rows: Vec<(String, PathBuf)>,
+}
+
+// collects exported rows per database so the index notes can be written at the end of a job
+#[derive(Default)]
+pub struct DatabaseIndexes {
+    // keyed by normalized database id
+    indexes: BTreeMap<String, DatabaseIndex>,
+}
+
+impl DatabaseIndexes {
+    // returns false when the database was already known
+    pub fn add_database(&mut self, database_id: &str, title: &str) -> bool {
+        let key = normalize_id(database_id);
+        if self.indexes.contains_key(&key) {
+            return false;
+        }
+        self.indexes.insert(
+            key,
+            DatabaseIndex {
+                title: title.to_owned(),
+                rows: vec![],
+            },
+        );
+        true
+    }
+
+    pub fn add_row(&mut self, database_id: &str, title: &str, row_title: &str, path: &Path) {
+        self.add_database(database_id, title);
+        if let Some(index) = self.indexes.get_mut(&normalize_id(database_id)) {
+            index.rows.push((row_title.to_owned(), path.to_path_buf()));
+        }
+    }
+
+    pub async fn write(&self, config: &ExportConfig) -> anyhow::Result<()> {
+        for (database_id, index) in &self.indexes {
+            let path = index_note_path(&config.output_dir, &index.title);
+            let note_dir = database_folder(&config.output_dir, &index.title);
+            let mut rows = index.rows.clone();
+            rows.sort();
+
+            let mut content = format!(
+                "# {}\n\n",
+                external_link(
+                    Some(&index.title),
+                    &format!("http://notion.so/{database_id}")
+                )
+            );
+            for (row_title, row_path) in &rows {
+                content.push_str(&format!(
+                    "- {}\n",
+                    note_link(config, row_title, row_path, &note_dir)
+                ));
+            }
+
+            tokio::fs::create_dir_all(&note_dir).await?;
+            // rewriting an unchanged index would wake up file watchers for nothing
+            if tokio::fs::read_to_string(&path).await.ok().as_deref() != Some(content.as_str()) {
+                tokio::fs::write(&path, content).await?;
+            }
+        }
+        Ok(())
+    }
+}
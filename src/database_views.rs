@@ -0,0 +1,243 @@
+use crate::{
+    configuration::{DatabaseView, DatabaseViewFormat, ExportConfig},
+    database_notes, escape,
+    ids::{normalize_id, same_id},
+    workspace::Workspace,
+};
+use notion::models::{Page, Parent};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+// where the rows of a database ended up, relative to the output directory which is the vault root
+enum RowSource {
+    // a folder holding nothing but the rows, from database_as_notes or the hierarchy layout
+    Folder(String),
+    Files(Vec<String>),
+}
+
+// exported rows of the databases that get a view, written at the end of a full run
+#[derive(Default)]
+pub struct DatabaseRows {
+    // keyed by normalized database id
+    rows: BTreeMap<String, Vec<PathBuf>>,
+}
+
+impl DatabaseRows {
+    pub fn add_row(&mut self, config: &ExportConfig, page: &Page, path: &Path) {
+        let Some(views) = &config.database_views else {
+            return;
+        };
+        let Parent::Database { database_id } = &page.parent else {
+            return;
+        };
+        let database_id = database_id.to_string();
+        if views
+            .databases
+            .iter()
+            .any(|view| same_id(&view.database_id, &database_id))
+        {
+            self.rows
+                .entry(normalize_id(&database_id))
+                .or_default()
+                .push(path.to_path_buf());
+        }
+    }
+
+    pub async fn write(&self, config: &ExportConfig, workspace: &Workspace) -> anyhow::Result<()> {
+        let Some(views) = &config.database_views else {
+            return Ok(());
+        };
+        for view in &views.databases {
+            // a database without exported rows has nothing to show
+            let Some(rows) = self.rows.get(&normalize_id(&view.database_id)) else {
+                continue;
+            };
+            let title = database_notes::database_title(workspace, &view.database_id);
+            let columns = columns(config, workspace, view);
+            let folder = shared_folder(&config.output_dir, rows);
+            let source = match &folder {
+                Some(folder) => RowSource::Folder(vault_path(&config.output_dir, folder)),
+                None => {
+                    let mut files: Vec<String> = rows
+                        .iter()
+                        .map(|row| vault_path(&config.output_dir, row))
+                        .collect();
+                    files.sort();
+                    RowSource::Files(files)
+                }
+            };
+            let (file_name, content) = match views.format {
+                DatabaseViewFormat::Base => (
+                    format!("{}.base", escape::file_name(&title)),
+                    base_file(&title, &source, &columns)?,
+                ),
+                DatabaseViewFormat::Dataview => (
+                    format!("{} view.md", escape::file_name(&title)),
+                    dataview_note(&source, &columns),
+                ),
+            };
+            let dir = folder.unwrap_or_else(|| config.output_dir.clone());
+            let path = dir.join(file_name);
+            tokio::fs::create_dir_all(&dir).await?;
+            if tokio::fs::read_to_string(&path).await.ok().as_deref() != Some(content.as_str()) {
+                tokio::fs::write(&path, content).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// frontmatter keys of the columns, mapped keys where the database has a frontmatter mapping
+fn columns(config: &ExportConfig, workspace: &Workspace, view: &DatabaseView) -> Vec<String> {
+    let mapping = config
+        .frontmatter_mappings
+        .iter()
+        .find(|mapping| same_id(&mapping.database_id, &view.database_id));
+    if view.properties.is_empty() {
+        return match mapping {
+            Some(mapping) => mapping
+                .properties
+                .iter()
+                .map(|property| property.key().to_owned())
+                .collect(),
+            None => {
+                let mut names: Vec<String> = workspace
+                    .database(&view.database_id)
+                    .map(|database| database.properties.keys().cloned().collect())
+                    .unwrap_or_default();
+                names.sort();
+                names
+            }
+        };
+    }
+    view.properties
+        .iter()
+        .map(|property| {
+            mapping
+                .and_then(|mapping| {
+                    mapping
+                        .properties
+                        .iter()
+                        .find(|mapped| &mapped.property == property)
+                })
+                .map(|mapped| mapped.key().to_owned())
+                .unwrap_or_else(|| property.clone())
+        })
+        .collect()
+}
+
+fn shared_folder(output_dir: &Path, rows: &[PathBuf]) -> Option<PathBuf> {
+    let folder = rows.first()?.parent()?;
+    let shared = rows.iter().all(|row| row.parent() == Some(folder));
+    (shared && folder != output_dir).then(|| folder.to_path_buf())
+}
+
+fn vault_path(output_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(output_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+// json string literals are valid in both query languages
+fn quoted(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+fn base_file(title: &str, source: &RowSource, columns: &[String]) -> anyhow::Result<String> {
+    let filters = match source {
+        RowSource::Folder(folder) => {
+            json!({ "and": [format!("file.inFolder({})", quoted(folder))] })
+        }
+        RowSource::Files(files) => json!({
+            "or": files
+                .iter()
+                .map(|file| format!("file.path == {}", quoted(file)))
+                .collect::<Vec<_>>(),
+        }),
+    };
+    let order: Vec<String> = std::iter::once(String::from("file.name"))
+        .chain(columns.iter().map(|column| format!("note.{column}")))
+        .collect();
+    let base = json!({
+        "filters": filters,
+        "views": [{
+            "type": "table",
+            "name": title,
+            "order": order,
+        }],
+    });
+    Ok(serde_yaml::to_string(&base)?)
+}
+
+fn dataview_note(source: &RowSource, columns: &[String]) -> String {
+    let mut query = String::from("TABLE WITHOUT ID file.link AS \"Name\"");
+    for column in columns {
+        query.push_str(&format!(", row[{}] AS {}", quoted(column), quoted(column)));
+    }
+    match source {
+        RowSource::Folder(folder) => query.push_str(&format!("\nFROM {}", quoted(folder))),
+        RowSource::Files(files) => {
+            let files: Vec<String> = files.iter().map(|file| quoted(file)).collect();
+            query.push_str(&format!(
+                "\nFROM \"\"\nWHERE contains(list({}), file.path)",
+                files.join(", ")
+            ));
+        }
+    }
+    query.push_str("\nSORT file.name ASC");
+    format!("```dataview\n{query}\n```\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a task database whose status property is mapped to `state`
+    fn schema() -> (ExportConfig, DatabaseView) {
+        let config = serde_yaml::from_str(
+            "frontmatter_mappings:\n  - database_id: db\n    properties:\n      - property: Status\n        key: state\n      - property: Due\n",
+        )
+        .unwrap();
+        let view = DatabaseView {
+            database_id: String::from("db"),
+            properties: vec![String::from("Due"), String::from("Status")],
+        };
+        (config, view)
+    }
+
+    #[test]
+    fn columns_use_the_mapped_keys() {
+        let (config, view) = schema();
+        let workspace = Workspace::default();
+        assert_eq!(columns(&config, &workspace, &view), vec!["Due", "state"]);
+        let all = DatabaseView {
+            properties: vec![],
+            ..view
+        };
+        assert_eq!(columns(&config, &workspace, &all), vec!["state", "Due"]);
+    }
+
+    #[test]
+    fn views_render_as_bases_and_dataview() {
+        let (config, view) = schema();
+        let columns = columns(&config, &Workspace::default(), &view);
+        let folder = RowSource::Folder(String::from("Tasks"));
+        assert_eq!(
+            base_file("Tasks", &folder, &columns).unwrap(),
+            "filters:\n  and:\n  - file.inFolder(\"Tasks\")\nviews:\n- name: Tasks\n  order:\n  - file.name\n  - note.Due\n  - note.state\n  type: table\n"
+        );
+        assert_eq!(
+            dataview_note(&folder, &columns),
+            "```dataview\nTABLE WITHOUT ID file.link AS \"Name\", row[\"Due\"] AS \"Due\", row[\"state\"] AS \"state\"\nFROM \"Tasks\"\nSORT file.name ASC\n```\n"
+        );
+        let files = RowSource::Files(vec![String::from("a.md"), String::from("b/c.md")]);
+        assert_eq!(
+            dataview_note(&files, &[]),
+            "```dataview\nTABLE WITHOUT ID file.link AS \"Name\"\nFROM \"\"\nWHERE contains(list(\"a.md\", \"b/c.md\"), file.path)\nSORT file.name ASC\n```\n"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{
+    api::Source,
+    assets, blocks,
+    configuration::AppConfig,
+    crawl, database_notes,
+    export::{self, RunOptions},
+    fingerprint, formats,
+    glossary::Glossary,
+    ids::normalize_id,
+    markdown::{PageIdCache, PageResources},
+    state::ExportState,
+    stats::format_bytes,
+    titles,
+    workspace::Workspace,
+};
+use notion::{ids::BlockId, models::block::Block};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// what a run would do to one note
+enum Action {
+    Create,
+    Overwrite,
+    Move { from: PathBuf },
+    Delete,
+}
+
+// attachments the run would download, their size is asked for without fetching them
+#[derive(Default)]
+struct Downloads {
+    keys: HashSet<String>,
+    bytes: u64,
+    // the server didn't say
+    unknown: usize,
+}
+
+// walks the workspace like an export and reports what it would change, nothing is written
+// pages only found while rendering, subpages with recursive and rows of inline databases, aren't known yet
+pub async fn dry_run(
+    sources: &[Source],
+    workspaces: &[Workspace],
+    config: &AppConfig,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    let http = reqwest::Client::new();
+    for job in export::prepare_jobs(sources, config, options).await? {
+        println!("Export job {}", job.name);
+        let mut exported_page_ids = HashSet::new();
+        for (source, workspace) in sources.iter().zip(workspaces) {
+            let config = export::source_config(&job.export, source);
+            // titles are recorded like in a real run, the state is never saved
+            let mut export_state = ExportState::load(&config.output_dir)?;
+            let full = options.full
+                || export_state.settings_hash.as_deref()
+                    != Some(fingerprint::settings_hash(&config)?.as_str());
+            let retitled;
+            let workspace = if config.title_sources.is_empty() {
+                workspace
+            } else {
+                retitled =
+                    titles::retitle_workspace(&source.client, &config.title_sources, workspace)
+                        .await;
+                &retitled
+            };
+            let graph = crawl::crawl(workspace, &config);
+            let page_id_cache = PageIdCache::new();
+            for node in &graph.pages {
+                page_id_cache.insert(&node.page_id, &node.title).await;
+            }
+            let glossary = Arc::new(Glossary::build(config.glossary.as_ref(), workspace, &graph));
+
+            let mut actions = vec![];
+            let mut unchanged = 0;
+            let mut downloads = Downloads::default();
+            let mut seen_page_ids = HashSet::new();
+            for node in &graph.pages {
+                let Some(page) = workspace.page(&node.page_id) else {
+                    continue;
+                };
+                if !exported_page_ids.insert(normalize_id(&node.page_id)) {
+                    continue;
+                }
+                seen_page_ids.insert(normalize_id(&node.page_id));
+                let state = export_state.pages.get(&normalize_id(&node.page_id));
+                let previous_path = state.and_then(|state| state.output_path.clone());
+                if let Some(from) = previous_path.filter(|from| *from != node.path && from.exists())
+                {
+                    actions.push((node.path.clone(), Action::Move { from }));
+                }
+                let unedited = export_state
+                    .unedited_page(&page.id, page.last_edited_time)
+                    .filter(|_| !full && !export::renders_extra_content(&config, page, None));
+                if let Some(state) = unedited {
+                    let notes = export_state.output_paths(&state.link_targets.pages);
+                    let links_changed = export::links_changed(
+                        &source.client,
+                        &config,
+                        &node.title,
+                        state,
+                        &notes,
+                        &page_id_cache,
+                        &glossary,
+                    )
+                    .await?;
+                    if !links_changed {
+                        unchanged += 1;
+                        continue;
+                    }
+                }
+                let exists = node.path.exists() && formats::formats_exist(&config, &node.path);
+                let aliases = export_state.record_title(&page.id, &node.title);
+                let blocks = if (exists && !full) || config.download_attachments {
+                    let block_id: BlockId = page.id.clone().into();
+                    match blocks::fetch_block_tree(&source.client, block_id).await {
+                        Ok(blocks) => Some(blocks),
+                        Err(error) => {
+                            eprintln!("Failed to fetch page '{}': {error:#}", node.title);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                // the same check a run makes before writing, edits that don't change the note leave it alone
+                if let (Some(blocks), true, false) = (&blocks, exists, full) {
+                    let link_targets = blocks::link_targets(blocks)?;
+                    let mut resources = PageResources {
+                        glossary: Some(glossary.clone()),
+                        ..Default::default()
+                    };
+                    if config.database_as_notes {
+                        let child_databases = database_notes::child_databases(blocks);
+                        resources.database_notes =
+                            database_notes::index_note_paths(&config.output_dir, &child_databases);
+                    }
+                    export::resolve_links(
+                        &source.client,
+                        &node.title,
+                        &link_targets,
+                        &export_state.output_paths(&link_targets.pages),
+                        &page_id_cache,
+                        &mut resources,
+                    )
+                    .await?;
+                    let content_hash = fingerprint::page_content_hash(
+                        &config, page, &aliases, blocks, &resources,
+                    )?;
+                    if export_state.content_hash(&page.id) == Some(content_hash.as_str())
+                        && !export::renders_extra_content(&config, page, Some(blocks))
+                    {
+                        unchanged += 1;
+                        continue;
+                    }
+                }
+                let action = if node.path.exists() {
+                    Action::Overwrite
+                } else {
+                    Action::Create
+                };
+                actions.push((node.path.clone(), action));
+                if let (Some(blocks), true) = (&blocks, config.download_attachments) {
+                    count_downloads(&http, blocks, &export_state, &mut downloads).await;
+                }
+            }
+            for (page_id, state) in &export_state.pages {
+                if seen_page_ids.contains(page_id) {
+                    continue;
+                }
+                // a page exported to the same path keeps the note, like in a real run
+                let path = state
+                    .output_path
+                    .as_ref()
+                    .filter(|path| !actions.iter().any(|(planned, _)| planned == *path));
+                if let Some(path) = path {
+                    for file in export::note_files(&config, path) {
+                        actions.push((file, Action::Delete));
+                    }
+                }
+            }
+
+            print_plan(&config.output_dir, &actions, unchanged, &downloads);
+        }
+    }
+    Ok(())
+}
+
+// the files of the attachments are only asked for their size
+async fn count_downloads(
+    http: &reqwest::Client,
+    blocks: &[Block],
+    export_state: &ExportState,
+    downloads: &mut Downloads,
+) {
+    for url in assets::attachment_urls(blocks) {
+        let key = assets::attachment_key(&url);
+        let downloaded = export_state
+            .attachments
+            .get(key)
+            .is_some_and(|attachment| attachment.path.exists());
+        if downloaded || !downloads.keys.insert(key.to_owned()) {
+            continue;
+        }
+        match download_size(http, &url).await {
+            Some(bytes) => downloads.bytes += bytes,
+            None => downloads.unknown += 1,
+        }
+    }
+}
+
+// signed urls only allow GET, so the first byte is requested and the total read from the range
+async fn download_size(http: &reqwest::Client, url: &str) -> Option<u64> {
+    let response = http
+        .get(url)
+        .header(reqwest::header::RANGE, "bytes=0-0")
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let total = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.rsplit('/').next())
+        .and_then(|total| total.parse().ok());
+    total.or_else(|| {
+        // servers ignoring the range send the whole file
+        (response.status() == reqwest::StatusCode::OK)
+            .then(|| response.content_length())
+            .flatten()
+    })
+}
+
+fn print_plan(
+    output_dir: &Path,
+    actions: &[(PathBuf, Action)],
+    unchanged: usize,
+    downloads: &Downloads,
+) {
+    let count =
+        |wanted: fn(&Action) -> bool| actions.iter().filter(|(_, action)| wanted(action)).count();
+    println!(
+        "  {} created, {} overwritten, {} moved, {} deleted, {unchanged} unchanged",
+        count(|action| matches!(action, Action::Create)),
+        count(|action| matches!(action, Action::Overwrite)),
+        count(|action| matches!(action, Action::Move { .. })),
+        count(|action| matches!(action, Action::Delete)),
+    );
+    let relative = |path: &Path| {
+        path.strip_prefix(output_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    for (path, action) in actions {
+        match action {
+            Action::Create => println!("  create    {}", relative(path)),
+            Action::Overwrite => println!("  overwrite {}", relative(path)),
+            Action::Move { from } => {
+                println!("  move      {} -> {}", relative(from), relative(path))
+            }
+            Action::Delete => println!("  delete    {}", relative(path)),
+        }
+    }
+    if !downloads.keys.is_empty() {
+        let unknown = if downloads.unknown > 0 {
+            format!(", {} of unknown size", downloads.unknown)
+        } else {
+            String::new()
+        };
+        println!(
+            "  {} attachments to download, about {}{unknown}",
+            downloads.keys.len(),
+            format_bytes(downloads.bytes)
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    configuration::AppConfig, frontmatter::strip_frontmatter, manifest::ExportManifest,
+    stats::job_output_dirs,
+};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+// words per shingle, shorter ones make notes sharing common phrases look alike
+const SHINGLE_WORDS: usize = 5;
+// the signature is split into bands, notes agreeing on all rows of any band are compared
+const BANDS: usize = 32;
+const ROWS: usize = 4;
+const SIGNATURE_LEN: usize = BANDS * ROWS;
+
+struct Note {
+    title: String,
+    path: String,
+    shingles: HashSet<u64>,
+    signature: Vec<u64>,
+}
+
+// compares the notes of the last export, nothing is fetched from notion
+// minhash finds candidates without comparing every pair, candidates get their exact similarity
+pub fn print_duplicates(
+    config: &AppConfig,
+    threshold: f64,
+    min_words: usize,
+) -> anyhow::Result<()> {
+    for job in config.export_jobs() {
+        let mut notes = vec![];
+        for output_dir in job_output_dirs(config, &job) {
+            let Some(manifest) = ExportManifest::load(&output_dir)? else {
+                continue;
+            };
+            for entry in manifest.pages.values() {
+                let note_path = output_dir.join(&entry.path);
+                let Ok(content) = entry.read_note(&output_dir) else {
+                    continue;
+                };
+                let words = words(strip_frontmatter(&content));
+                // short notes like empty templates share most of their few words
+                if words.len() < min_words {
+                    continue;
+                }
+                let shingles = shingles(&words);
+                notes.push(Note {
+                    title: entry.title.clone(),
+                    path: note_path.display().to_string(),
+                    signature: signature(&shingles),
+                    shingles,
+                });
+            }
+        }
+
+        let mut duplicates: Vec<(f64, &Note, &Note)> = candidate_pairs(&notes)
+            .into_iter()
+            .filter_map(|(a, b)| {
+                let similarity = jaccard(&notes[a].shingles, &notes[b].shingles);
+                (similarity >= threshold).then_some((similarity, &notes[a], &notes[b]))
+            })
+            .collect();
+        duplicates.sort_by(|(a, ..), (b, ..)| b.total_cmp(a));
+        println!(
+            "Job {}: {} near duplicate pairs among {} notes",
+            job.name,
+            duplicates.len(),
+            notes.len()
+        );
+        for (similarity, a, b) in duplicates {
+            println!("  {:>3.0}% {} <-> {}", similarity * 100.0, a.title, b.title);
+            println!("       {}", a.path);
+            println!("       {}", b.path);
+        }
+    }
+    Ok(())
+}
+
+// markdown syntax and case don't make two texts different
+fn words(text: &str) -> Vec<String> {
+    text.split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn shingles(words: &[String]) -> HashSet<u64> {
+    words
+        .windows(SHINGLE_WORDS.min(words.len()).max(1))
+        .map(|window| {
+            let mut hasher = DefaultHasher::new();
+            window.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+// the minimum of every shingle under each of the hash functions
+// two signatures agree in a position with the probability of the notes' jaccard similarity
+fn signature(shingles: &HashSet<u64>) -> Vec<u64> {
+    (0..SIGNATURE_LEN as u64)
+        .map(|seed| {
+            let multiplier = splitmix(seed) | 1;
+            let offset = splitmix(seed + SIGNATURE_LEN as u64);
+            shingles
+                .iter()
+                .map(|shingle| shingle.wrapping_mul(multiplier).wrapping_add(offset))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+// fixed constants for the hash functions, the same notes always give the same report
+fn splitmix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn candidate_pairs(notes: &[Note]) -> HashSet<(usize, usize)> {
+    let mut pairs = HashSet::new();
+    for band in 0..BANDS {
+        let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+        for (index, note) in notes.iter().enumerate() {
+            let rows = &note.signature[band * ROWS..(band + 1) * ROWS];
+            buckets.entry(rows).or_default().push(index);
+        }
+        for bucket in buckets.values() {
+            for (position, a) in bucket.iter().enumerate() {
+                for b in &bucket[position + 1..] {
+                    pairs.insert((*a, *b));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shingled(text: &str) -> HashSet<u64> {
+        shingles(&words(text))
+    }
+
+    #[test]
+    fn signatures_agree_about_as_often_as_the_texts_overlap() {
+        let base = "the quarterly planning meeting moved to thursday because half of the team is travelling on monday and the room was booked";
+        let copy = format!("{base} anyway");
+        let other = "recipes for sourdough need a starter that was fed the night before and flour with enough protein to hold the shape";
+        let agreement = |a: &HashSet<u64>, b: &HashSet<u64>| {
+            let (a, b) = (signature(a), signature(b));
+            a.iter().zip(&b).filter(|(a, b)| a == b).count() as f64 / SIGNATURE_LEN as f64
+        };
+        let (base, copy, other) = (shingled(base), shingled(&copy), shingled(other));
+        assert!(jaccard(&base, &copy) > 0.9);
+        assert!(agreement(&base, &copy) > 0.8);
+        assert_eq!(jaccard(&base, &other), 0.0);
+        assert!(agreement(&base, &other) < 0.1);
+    }
+
+    #[test]
+    fn markdown_and_case_are_ignored() {
+        assert_eq!(
+            words("## Roadmap\n- **Ship** the `beta`"),
+            vec!["roadmap", "ship", "the", "beta"]
+        );
+    }
+}
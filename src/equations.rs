@@ -0,0 +1,96 @@
+use crate::{blocks::walk_blocks, manifest::content_hash};
+use notion::models::block::Block;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// a command hanging on one expression would stall the page forever
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+// runs the configured command for every equation block and stores the svg with the attachments
+// the tex expression is passed as the first argument and stdout is the svg
+// files are named by the hash of the command and expression, an equation already rendered isn't rendered again
+pub async fn render_equations(
+    blocks: &[Block],
+    command: &str,
+    attachments_dir: &Path,
+) -> HashMap<String, PathBuf> {
+    let mut equations = vec![];
+    walk_blocks(blocks, &mut |block| {
+        if let Block::Equation { common, equation } = block {
+            equations.push((common.id.to_string(), equation.expression.trim().to_owned()));
+        }
+    });
+
+    let mut images = HashMap::new();
+    for (block_id, expression) in equations {
+        let path = equation_path(attachments_dir, command, &expression);
+        if !path.is_file() {
+            if let Err(error) = render_equation(command, &expression, &path).await {
+                eprintln!("Equation command failed for {expression} with error {error:?}");
+                continue;
+            }
+        }
+        images.insert(block_id, path);
+    }
+    images
+}
+
+// another command renders differently, switching commands renders every equation again
+fn equation_path(attachments_dir: &Path, command: &str, expression: &str) -> PathBuf {
+    let hash = content_hash(&format!("{command}\n{expression}"));
+    attachments_dir.join(format!("equation-{}.svg", &hash[..16]))
+}
+
+async fn render_equation(command: &str, expression: &str, path: &Path) -> anyhow::Result<()> {
+    let output = tokio::process::Command::new("sh")
+        .args(["-c", command, "sh", expression])
+        .kill_on_drop(true)
+        .output();
+    let Ok(output) = tokio::time::timeout(COMMAND_TIMEOUT, output).await else {
+        anyhow::bail!(
+            "equation command didn't finish within {} seconds",
+            COMMAND_TIMEOUT.as_secs()
+        );
+    };
+    let output = output?;
+    if !output.status.success() {
+        anyhow::bail!("equation command exited with {}", output.status);
+    }
+    if !output.stdout.starts_with(b"<") {
+        anyhow::bail!("equation command didn't print an svg");
+    }
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, &output.stdout).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn equations_are_rendered_again_by_another_command() {
+        let attachments_dir = std::env::temp_dir().join(format!(
+            "notion-bridge-equations-test-{}",
+            std::process::id()
+        ));
+        let command = r#"printf '<svg>%s</svg>' "$1""#;
+        let path = equation_path(&attachments_dir, command, "x^2");
+        assert_eq!(path, equation_path(&attachments_dir, command, "x^2"));
+        assert_ne!(
+            path,
+            equation_path(&attachments_dir, "tex2svg \"$1\"", "x^2")
+        );
+
+        render_equation(command, "x^2", &path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "<svg>x^2</svg>");
+        assert!(render_equation("echo plain", "x^2", &path).await.is_err());
+
+        std::fs::remove_dir_all(&attachments_dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+// makes notion text safe for the places it ends up in, file names, links and code fences
+
+// longest file name most filesystems accept is 255 bytes, leave room for extensions
+const MAX_FILE_NAME_BYTES: usize = 200;
+
+// names windows refuses no matter the extension
+const RESERVED_FILE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// a single path component that is valid on windows, macos and linux
+pub fn file_name(title: &str) -> String {
+    let mut name: String = title
+        .chars()
+        .map(|character| match character {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            // obsidian can't link to notes with these in their name
+            '#' | '^' | '[' | ']' => '-',
+            character if character.is_control() => ' ',
+            character => character,
+        })
+        .collect();
+    if name.len() > MAX_FILE_NAME_BYTES {
+        let mut end = MAX_FILE_NAME_BYTES;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+    // windows drops trailing dots and spaces, a leading dot hides the file
+    let name = name
+        .trim_matches(|character: char| character == '.' || character.is_whitespace())
+        .to_owned();
+    if name.is_empty() {
+        return String::from("untitled");
+    }
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_FILE_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return format!("_{name}");
+    }
+    name
+}
+
+// obsidian splits link targets on | and # and ends them at ]]
+pub fn wikilink_target(target: &str) -> String {
+    target
+        .chars()
+        .map(|character| match character {
+            '[' | ']' | '|' | '#' | '^' => '-',
+            '\n' | '\r' => ' ',
+            character => character,
+        })
+        .collect()
+}
+
+// the display text may contain anything but the characters closing the link
+pub fn wikilink_text(text: &str) -> String {
+    text.chars()
+        .map(|character| match character {
+            '[' => '(',
+            ']' => ')',
+            '|' => '/',
+            '\n' | '\r' => ' ',
+            character => character,
+        })
+        .collect()
+}
+
+// brackets would end the text of a markdown link early
+pub fn link_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '[' | ']' | '\\' => {
+                escaped.push('\\');
+                escaped.push(character);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+// commonmark ends a bare destination at spaces and unbalanced parentheses, angle brackets allow both
+// but may not hold angle brackets or line breaks themselves, a backslash would escape the end
+pub fn link_destination(destination: &str) -> String {
+    let destination = destination
+        .replace('\\', "%5C")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+        .replace(['\n', '\r'], "");
+    if destination.contains([' ', '(', ')']) {
+        format!("<{destination}>")
+    } else {
+        destination
+    }
+}
+
+// a fence longer than any run of backticks in the content so it can't be closed early
+pub fn code_fence(content: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for character in content.chars() {
+        if character == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn file_names_are_valid_path_components(title in any::<String>()) {
+            let name = file_name(&title);
+            prop_assert!(!name.is_empty());
+            prop_assert!(name.len() <= MAX_FILE_NAME_BYTES + 1);
+            prop_assert!(!name.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|', '#', '^', '[', ']']));
+            prop_assert_eq!(wikilink_target(&name), name.clone());
+            prop_assert!(!name.chars().any(char::is_control));
+            prop_assert!(!name.starts_with('.') && !name.ends_with('.'));
+            prop_assert!(!name.ends_with(char::is_whitespace));
+            prop_assert!(name != "." && name != "..");
+        }
+
+        #[test]
+        fn wikilinks_stay_one_link(target in any::<String>(), text in any::<String>()) {
+            let link = format!("[[{}|{}]]", wikilink_target(&target), wikilink_text(&text));
+            prop_assert_eq!(link.matches("[[").count(), 1);
+            prop_assert_eq!(link.matches("]]").count(), 1);
+            prop_assert_eq!(link.matches('|').count(), 1);
+            prop_assert!(!link.contains('\n'));
+        }
+
+        #[test]
+        fn markdown_links_stay_one_link(text in any::<String>(), destination in any::<String>()) {
+            let text = link_text(&text);
+            let mut escaped = false;
+            for character in text.chars() {
+                prop_assert!(escaped || !matches!(character, '[' | ']'));
+                escaped = !escaped && character == '\\';
+            }
+            prop_assert!(!escaped && !text.contains(['\n', '\r']));
+            let destination = link_destination(&destination);
+            prop_assert!(!destination.contains(['\\', '\n', '\r']));
+            match destination.strip_prefix('<') {
+                Some(inner) => prop_assert_eq!(inner.find('>'), Some(inner.len() - 1)),
+                None => prop_assert!(!destination.contains([' ', '(', ')', '>'])),
+            }
+        }
+
+        #[test]
+        fn code_fences_are_never_closed_by_the_content(content in "[`a \n]*") {
+            let fence = code_fence(&content);
+            prop_assert!(fence.len() >= 3);
+            let closes_early = content
+                .lines()
+                .any(|line| line.trim_start().starts_with(&fence));
+            prop_assert!(!closes_early);
+        }
+    }
+
+    #[test]
+    fn reserved_windows_names_get_a_prefix() {
+        assert_eq!(file_name("con"), "_con");
+        assert_eq!(file_name("NUL.txt"), "_NUL.txt");
+        assert_eq!(file_name("Console"), "Console");
+    }
+}
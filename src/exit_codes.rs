@@ -0,0 +1,96 @@
+use crate::{api::ApiStatusError, changes::ChangeSet};
+use serde::Serialize;
+use std::process::ExitCode;
+
+// documented in the readme, schedulers branch on these so never renumber them
+// 2 is left to clap for invalid arguments
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_AUTH: u8 = 3;
+pub const EXIT_RATE_LIMITED: u8 = 4;
+pub const EXIT_PARTIAL_EXPORT: u8 = 5;
+pub const EXIT_RENDER_ERROR: u8 = 6;
+pub const EXIT_IO_ERROR: u8 = 7;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    Auth,
+    RateLimited,
+    Render,
+    Io,
+    Other,
+}
+
+// raised for pages that can't be rendered faithfully in strict mode
+#[derive(Debug)]
+pub struct RenderError(pub String);
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "render error: {}", self.0)
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl FailureClass {
+    // looks through the whole context chain for the error that started it
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<ApiStatusError>() {
+                if let Some(class) = Self::from_status(error.status) {
+                    return class;
+                }
+            }
+            if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+                if let Some(class) = error
+                    .status()
+                    .and_then(|status| Self::from_status(status.as_u16()))
+                {
+                    return class;
+                }
+            }
+            if cause.is::<RenderError>() {
+                return Self::Render;
+            }
+            if cause.is::<std::io::Error>() {
+                return Self::Io;
+            }
+        }
+        Self::Other
+    }
+
+    fn from_status(status: u16) -> Option<Self> {
+        match status {
+            401 | 403 => Some(Self::Auth),
+            429 => Some(Self::RateLimited),
+            _ => None,
+        }
+    }
+
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Auth => EXIT_AUTH,
+            Self::RateLimited => EXIT_RATE_LIMITED,
+            Self::Render => EXIT_RENDER_ERROR,
+            Self::Io => EXIT_IO_ERROR,
+            Self::Other => EXIT_FAILURE,
+        }
+    }
+}
+
+// a run that finished but left pages behind still has to be visible to schedulers
+pub fn export_exit_code(changes: &ChangeSet, strict: bool) -> ExitCode {
+    if changes.failed.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+    if strict
+        && changes
+            .failed
+            .iter()
+            .any(|failure| failure.class == FailureClass::Render)
+    {
+        return ExitCode::from(EXIT_RENDER_ERROR);
+    }
+    ExitCode::from(EXIT_PARTIAL_EXPORT)
+}
@@ -0,0 +1,1628 @@
+use crate::{
+    alt_text,
+    api::{NotionClient, Source},
+    assets,
+    atomic::{self, Staging},
+    blocks::{self, BlockTree, LinkTargets},
+    changes::{unified_diff, ChangeSet, PageChange, PageFailure},
+    columns, comments,
+    configuration::{
+        AppConfig, ColumnLayout, ExportConfig, ExportJob, Flavor, FolderIcons, FrontmatterFormat,
+        OutputFormat, RedactionAction,
+    },
+    crawl::{self, PageGraph},
+    database_export,
+    database_notes::{self, ChildDatabase, DatabaseIndexes},
+    database_views::DatabaseRows,
+    equations, escape,
+    exit_codes::{FailureClass, RenderError},
+    fingerprint, folder_icons, formats,
+    frontmatter::Frontmatter,
+    git,
+    glossary::Glossary,
+    hugo,
+    i18n::{self, Text},
+    ids::{self, normalize_id, same_id},
+    layout, lint, lock,
+    manifest::{self, ExportManifest, ManifestEntry},
+    markdown::{
+        blocks_to_markdown, external_link, internal_link, notion_database_id_to_url,
+        notion_page_id_to_url, LinkedPage, PageIdCache, PageResources, RenderContext,
+    },
+    notifications,
+    progress::ExportProgress,
+    properties,
+    redaction::{self, Redactor},
+    remote_config, report, snippets, split,
+    state::{ExportState, PageState},
+    stats, status_page, titles, wiki,
+    workspace::{Workspace, WorkspaceSnapshot},
+};
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use futures::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, StreamExt},
+    FutureExt,
+};
+use notion::{
+    ids::{BlockId, DatabaseId, PageId},
+    models::{
+        block::Block,
+        paging::Pageable,
+        properties::PropertyValue,
+        search::{NotionSearch, SearchRequest, SortDirection, SortTimestamp},
+        Page, Parent,
+    },
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::Mutex, time::Instant};
+
+// settings of a single invocation that come from the command line
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    pub wait_for_lock: bool,
+    // pages taking longer than this are written as far as they got
+    pub page_timeout: Option<Duration>,
+    // pages with blocks the renderer can't represent fail instead of getting placeholders
+    pub strict: bool,
+    // turns on database_as_notes for every job
+    pub database_as_notes: bool,
+    // turns on recursive for every job
+    pub recursive: bool,
+    // the workspace only holds some of the pages, everything else stays as the last run left it
+    pub partial: bool,
+    // pages exported at the same time, their requests still share the rate limit of the source
+    pub concurrency: usize,
+    // render pages that weren't edited since the last run as well
+    pub full: bool,
+    // no progress bar and no line per page
+    pub quiet: bool,
+    // attachments that weren't downloaded before keep linking to notion
+    pub offline: bool,
+    // render into a copy of the output directory and swap it in when no page failed
+    pub atomic: bool,
+}
+
+pub async fn run_export(
+    sources: &[Source],
+    config: &AppConfig,
+    options: RunOptions,
+) -> anyhow::Result<ChangeSet> {
+    let workspaces = search_sources(sources, config).await?;
+    export_workspaces(sources, &workspaces, config, options).await
+}
+
+// exports one page instead of everything the search returns
+// `page` is an id or a page url as copied from notion
+pub async fn export_page(
+    sources: &[Source],
+    config: &AppConfig,
+    page: &str,
+    options: RunOptions,
+) -> anyhow::Result<ChangeSet> {
+    let page_id = ids::parse_page_reference(page)
+        .with_context(|| format!("{page} is neither a page id nor a notion url"))?;
+    let page_id = PageId::from_str(&page_id)?;
+    // the first source that can see the page exports it
+    let mut workspaces = vec![];
+    let mut found = false;
+    for source in sources {
+        let mut workspace = Workspace::default();
+        if !found {
+            if let Ok(page) = source.client.get_page(page_id.clone()).await {
+                workspace.add_page(page);
+                found = true;
+            }
+        }
+        workspaces.push(workspace);
+    }
+    if !found {
+        bail!("page {page_id} doesn't exist or isn't shared with the integration");
+    }
+    let options = RunOptions {
+        partial: true,
+        ..options
+    };
+    export_workspaces(sources, &workspaces, config, options).await
+}
+
+// one workspace per source in the same order
+pub async fn search_sources(
+    sources: &[Source],
+    config: &AppConfig,
+) -> anyhow::Result<Vec<Workspace>> {
+    let mut workspaces = vec![];
+    for source in sources {
+        if let Some(name) = &source.name {
+            println!("Searching source {name}");
+        }
+        let workspace = if config.incremental_search {
+            let snapshot_path = WorkspaceSnapshot::path(source.name.as_deref())?;
+            let snapshot = WorkspaceSnapshot::load(&snapshot_path)?;
+            let (snapshot, _) =
+                search_workspace_incremental(&source.client, snapshot, config).await?;
+            snapshot.save(&snapshot_path)?;
+            snapshot.into_workspace()
+        } else {
+            search_workspace(&source.client, None).await?
+        };
+        workspaces.push(workspace);
+    }
+    Ok(workspaces)
+}
+
+// merges the objects edited since the snapshot was taken into it, or searches everything without one
+// deleted and unshared pages never show up as edited, only a full search lets them go
+// the flag is false when no page was edited since the snapshot
+pub async fn search_workspace_incremental(
+    notion_client: &NotionClient,
+    snapshot: Option<WorkspaceSnapshot>,
+    config: &AppConfig,
+) -> anyhow::Result<(WorkspaceSnapshot, bool)> {
+    // taken before searching so edits made during the search are picked up next time
+    let synced_at = Utc::now();
+    let max_age = chrono::Duration::hours(config.full_search_after_hours as i64);
+    let snapshot = snapshot.filter(|snapshot| {
+        snapshot
+            .full_search_at
+            .is_some_and(|full_search_at| synced_at - full_search_at < max_age)
+    });
+    let Some(snapshot) = snapshot else {
+        let workspace = search_workspace(notion_client, None).await?;
+        return Ok((workspace.snapshot(synced_at, Some(synced_at)), true));
+    };
+    let newer = search_workspace(notion_client, Some(snapshot.synced_at)).await?;
+    println!(
+        "Found {} pages edited since {}",
+        newer.pages.len(),
+        snapshot.synced_at
+    );
+    let edited = !newer.pages.is_empty();
+    let full_search_at = snapshot.full_search_at;
+    let mut workspace = snapshot.into_workspace();
+    workspace.merge(newer);
+    Ok((workspace.snapshot(synced_at, full_search_at), edited))
+}
+
+// the search results are shared so every job only pays for rendering its own pages
+pub async fn export_workspaces(
+    sources: &[Source],
+    workspaces: &[Workspace],
+    config: &AppConfig,
+    options: RunOptions,
+) -> anyhow::Result<ChangeSet> {
+    let result = export_all_jobs(sources, workspaces, config, options).await;
+    // failing to report the run shouldn't fail the run itself
+    // read-only tokens would only fail and print the error on every run
+    let status_page = config.status_page.as_ref().filter(|_| !config.read_only);
+    if let (Some(page_id), Some(source)) = (status_page, sources.first()) {
+        if let Err(error) = status_page::write_status_page(&source.client, page_id, &result).await {
+            eprintln!("Failed to update status page with error {error:?}");
+        }
+    }
+    notifications::notify(&config.notifications, &result).await;
+    result
+}
+
+// the jobs of the config with the config page and command line applied
+pub async fn prepare_jobs(
+    sources: &[Source],
+    config: &AppConfig,
+    options: RunOptions,
+) -> anyhow::Result<Vec<ExportJob>> {
+    // read on every run so edits on the page are picked up by watch mode
+    // any source that can see the page works, the first one is as good as any
+    let remote_config = match (&config.config_page, sources.first()) {
+        (Some(page_id), Some(source)) => {
+            Some(remote_config::load_remote_config(&source.client, page_id).await?)
+        }
+        _ => None,
+    };
+    let mut jobs = config.export_jobs();
+    for job in jobs.iter_mut() {
+        if let Some(remote_config) = &remote_config {
+            remote_config.apply(&mut job.export);
+        }
+        if options.database_as_notes {
+            job.export.database_as_notes = true;
+        }
+        if options.recursive {
+            job.export.recursive = true;
+        }
+        // roots and excludes are for picking pages out of the whole workspace
+        if options.partial {
+            job.export.roots.clear();
+            job.export.excludes.clear();
+        }
+    }
+    Ok(jobs)
+}
+
+// with several sources every one gets a subdirectory of the output
+pub fn source_config(export: &ExportConfig, source: &Source) -> ExportConfig {
+    let mut source_config = export.clone();
+    if let Some(name) = &source.name {
+        source_config.output_dir = source_config.output_dir.join(name);
+    }
+    source_config.output_dir = formats::notes_dir(&source_config.output_dir, &export.formats);
+    source_config
+}
+
+async fn export_all_jobs(
+    sources: &[Source],
+    workspaces: &[Workspace],
+    config: &AppConfig,
+    options: RunOptions,
+) -> anyhow::Result<ChangeSet> {
+    let mut changes = ChangeSet::default();
+    // titles are the same for every job and source so one cache serves the whole run
+    let page_id_cache = PageIdCache::new();
+    for job in prepare_jobs(sources, config, options).await? {
+        println!("Running export job {}", job.name);
+        // pages shared with several integrations are only exported by the first source
+        let mut exported_page_ids = HashSet::new();
+        for (source, workspace) in sources.iter().zip(workspaces) {
+            let config = source_config(&job.export, source);
+            let job_changes = if options.atomic {
+                run_staged_export_job(
+                    &source.client,
+                    workspace,
+                    &config,
+                    &mut exported_page_ids,
+                    &page_id_cache,
+                    options,
+                )
+                .await?
+            } else {
+                run_export_job(
+                    &source.client,
+                    workspace,
+                    &config,
+                    &mut exported_page_ids,
+                    &page_id_cache,
+                    options,
+                )
+                .await?
+            };
+            // failing to commit shouldn't fail the export, the next run commits both
+            if let Some(git) = &config.git {
+                if let Err(error) = git::commit_output(git, &config.output_dir, &job_changes).await
+                {
+                    eprintln!(
+                        "Failed to commit {}: {error:#}",
+                        config.output_dir.display()
+                    );
+                }
+            }
+            changes.extend(job_changes);
+        }
+    }
+
+    if !changes.truncated.is_empty() {
+        println!("Pages truncated after the page timeout:");
+        for change in &changes.truncated {
+            println!("  {} {}", change.title, change.page_id);
+        }
+    }
+
+    Ok(changes)
+}
+
+// with `since` results come newest first and the search stops at the first older object
+pub async fn search_workspace(
+    notion_client: &NotionClient,
+    since: Option<DateTime<Utc>>,
+) -> anyhow::Result<Workspace> {
+    // let search_query = NotionSearch::Filter {
+    //     property: notion::models::search::FilterProperty::Object,
+    //     value: notion::models::search::FilterValue::Page,
+    // };
+
+    let newest_first = || NotionSearch::Sort {
+        timestamp: SortTimestamp::LastEditedTime,
+        direction: SortDirection::Descending,
+    };
+    let search_query = match since {
+        Some(_) => newest_first(),
+        None => NotionSearch::Query(String::from("")),
+    };
+    // notion rounds edit times to the minute
+    let cutoff = since.map(|since| since - chrono::Duration::minutes(1));
+    let is_older = |last_edited_time: &DateTime<Utc>| {
+        cutoff
+            .map(|cutoff| *last_edited_time < cutoff)
+            .unwrap_or(false)
+    };
+
+    let mut search_result = notion_client.search(search_query).await?;
+    let mut workspace = Workspace::default();
+
+    loop {
+        let mut reached_older = false;
+        for object in search_result.results {
+            match object {
+                notion::models::Object::Block { block: _ } => {
+                    println!("Block");
+                }
+                notion::models::Object::Database { database }
+                    if is_older(&database.last_edited_time) =>
+                {
+                    reached_older = true;
+                    break;
+                }
+                notion::models::Object::Page { page } if is_older(&page.last_edited_time) => {
+                    reached_older = true;
+                    break;
+                }
+                notion::models::Object::Database { database } => {
+                    println!(
+                        "Database: {} {}",
+                        database.title_plain_text(),
+                        notion_database_id_to_url(&database.id)
+                    );
+                    workspace.add_database(database);
+                }
+                notion::models::Object::Page { page } => {
+                    workspace.add_page(page);
+                }
+                notion::models::Object::List { list: _ } => {
+                    println!("List");
+                }
+                notion::models::Object::User { user: _ } => {
+                    println!("User");
+                }
+                notion::models::Object::Error { error: _ } => {
+                    println!("Error");
+                }
+            }
+        }
+        if reached_older {
+            break;
+        }
+        if let Some(cursor) = search_result.next_cursor {
+            let search_request = match since {
+                Some(_) => SearchRequest::from(newest_first()),
+                None => SearchRequest::default(),
+            }
+            .start_from(Some(cursor));
+            search_result = notion_client.search(search_request).await?;
+        } else {
+            break;
+        }
+    }
+
+    Ok(workspace)
+}
+
+// the live output directory stays untouched until the whole job succeeded
+async fn run_staged_export_job(
+    notion_client: &NotionClient,
+    workspace: &Workspace,
+    config: &ExportConfig,
+    exported_page_ids: &mut HashSet<String>,
+    page_id_cache: &PageIdCache,
+    options: RunOptions,
+) -> anyhow::Result<ChangeSet> {
+    // other atomic exports wait on the lock next to the directory, it stays in place through the swap
+    // with several formats all of them are swapped together
+    let swapped_dir = formats::formats_root(config);
+    let lock_path = atomic::lock_path(swapped_dir)?;
+    let _atomic_lock =
+        lock::ExportLock::acquire_at(&lock_path, swapped_dir, options.wait_for_lock).await?;
+    // other exports into the live directory have to wait until it was swapped
+    let _lock = lock::ExportLock::acquire(&config.output_dir, options.wait_for_lock).await?;
+    let staging = Staging::prepare(swapped_dir, &config.output_dir)?;
+    let result = run_export_job(
+        notion_client,
+        workspace,
+        &staging.config(config),
+        exported_page_ids,
+        page_id_cache,
+        options,
+    )
+    .await;
+    match result {
+        Ok(mut changes) if changes.failed.is_empty() => {
+            staging.rebase_changes(&mut changes);
+            staging.commit()?;
+            Ok(changes)
+        }
+        Ok(changes) => {
+            eprintln!(
+                "{} pages failed, {} is left as the last run wrote it",
+                changes.failed.len(),
+                config.output_dir.display()
+            );
+            staging.discard()?;
+            Ok(ChangeSet {
+                failed: changes.failed,
+                ..Default::default()
+            })
+        }
+        Err(error) => {
+            staging.discard()?;
+            Err(error)
+        }
+    }
+}
+
+async fn run_export_job(
+    notion_client: &NotionClient,
+    workspace: &Workspace,
+    config: &ExportConfig,
+    exported_page_ids: &mut HashSet<String>,
+    page_id_cache: &PageIdCache,
+    options: RunOptions,
+) -> anyhow::Result<ChangeSet> {
+    // concurrent runs would clobber each others state file
+    let _lock = lock::ExportLock::acquire(&config.output_dir, options.wait_for_lock).await?;
+    let mut export_state = ExportState::load(&config.output_dir)?;
+    // notes written with other settings are outdated however old their pages are
+    let settings_hash = fingerprint::settings_hash(config)?;
+    let options = RunOptions {
+        full: options.full || export_state.settings_hash.as_deref() != Some(settings_hash.as_str()),
+        ..options
+    };
+    if !options.partial {
+        export_state.settings_hash = Some(settings_hash);
+    }
+    // the graph of the last full run, partial runs only crawl some of the pages
+    let previous_graph = PageGraph::load(&config.output_dir).unwrap_or_else(|error| {
+        eprintln!("Ignoring unreadable page graph {error:?}");
+        None
+    });
+    let previous_manifest = ExportManifest::load(&config.output_dir).unwrap_or_else(|error| {
+        eprintln!("Ignoring unreadable export manifest {error:?}");
+        None
+    });
+    // pages that aren't part of a partial run keep their entries
+    let mut manifest = match (&previous_manifest, options.partial) {
+        (Some(previous), true) => ExportManifest {
+            generated_at: Utc::now(),
+            ..previous.clone()
+        },
+        _ => ExportManifest::default(),
+    };
+    let mut changes = ChangeSet::default();
+    let mut seen_page_ids = HashSet::new();
+    let mut database_indexes = DatabaseIndexes::default();
+    let mut database_views = DatabaseRows::default();
+    // inline databases whose rows were queued, database_indexes tracks them with database_as_notes
+    let mut followed_databases = HashSet::new();
+    let mut queued_page_ids = HashSet::new();
+
+    let retitled;
+    let workspace = if config.title_sources.is_empty() {
+        workspace
+    } else {
+        retitled = titles::retitle_workspace(notion_client, &config.title_sources, workspace).await;
+        &retitled
+    };
+
+    // crawl first so every title and path is known before the first page is rendered
+    let mut graph = crawl::crawl(workspace, config);
+    for node in &graph.pages {
+        page_id_cache.insert(&node.page_id, &node.title).await;
+    }
+    if options.partial {
+        // the parents deciding wiki and database folders aren't known, the last full run knew them
+        for node in graph.pages.iter_mut() {
+            if let Some(previous) = previous_graph
+                .as_ref()
+                .and_then(|graph| graph.node(&node.page_id))
+            {
+                node.path = previous.path.clone();
+            }
+        }
+    } else {
+        graph.save(&config.output_dir)?;
+        if config.folder_icons == FolderIcons::File {
+            folder_icons::write_folder_icons(&graph, workspace)?;
+        }
+    }
+
+    let glossary = Arc::new(Glossary::build(config.glossary.as_ref(), workspace, &graph));
+    let redactor = Redactor::new(config).context("invalid redaction pattern")?;
+    let redactor = redactor.as_ref();
+
+    // rows of inline databases are queued up behind the page showing them
+    let mut queue: VecDeque<(Page, PathBuf)> = graph
+        .pages
+        .iter()
+        .filter_map(|node| Some((workspace.page(&node.page_id)?.clone(), node.path.clone())))
+        .collect();
+    // subpages that are part of the graph are queued already
+    queued_page_ids.extend(graph.pages.iter().map(|node| node.page_id.clone()));
+
+    let progress = ExportProgress::new(queue.len(), options.quiet);
+    let export_state_lock = Mutex::new(export_state);
+    let export_state = &export_state_lock;
+    let glossary = &glossary;
+    let progress = &progress;
+    let previous_manifest = previous_manifest.as_ref();
+    // pages finishing can queue more pages, the loop ends once both are empty
+    // row queries and subpage lookups run in here too so pages in flight keep going meanwhile
+    let mut in_flight: FuturesUnordered<BoxFuture<Task>> = FuturesUnordered::new();
+    loop {
+        while in_flight.len() < options.concurrency.max(1) {
+            let Some((page, output_path)) = queue.pop_front() else {
+                break;
+            };
+            if !exported_page_ids.insert(normalize_id(&page.id.to_string())) {
+                progress.skipped();
+                continue;
+            }
+            seen_page_ids.insert(page.id.to_string());
+            progress.started(
+                &page.title().unwrap_or_default(),
+                &notion_page_id_to_url(&page.id),
+            );
+            in_flight.push(
+                async move {
+                    let result = process_page(
+                        notion_client,
+                        &page,
+                        &output_path,
+                        config,
+                        export_state,
+                        Lookups {
+                            page_id_cache,
+                            glossary,
+                            progress,
+                            redactor,
+                            previous_manifest,
+                        },
+                        options,
+                    )
+                    .await;
+                    Task::Page(page, output_path, result)
+                }
+                .boxed(),
+            );
+        }
+        let Some(task) = in_flight.next().await else {
+            break;
+        };
+        let (page, output_path, result) = match task {
+            Task::Page(page, output_path, result) => (page, output_path, result),
+            Task::DatabaseRows {
+                database,
+                shown_on,
+                rows,
+            } => {
+                match rows {
+                    Ok(rows) => {
+                        let queued = queue.len();
+                        queue.extend(
+                            rows.into_iter()
+                                .filter(|row| !crawl::is_excluded(config, row))
+                                .map(|row| {
+                                    let path = if config.database_as_notes {
+                                        let row_title =
+                                            row.title().unwrap_or_else(|| row.id.to_string());
+                                        database_notes::row_path(
+                                            &config.output_dir,
+                                            &database.title,
+                                            &row_title,
+                                        )
+                                    } else {
+                                        layout::page_output_path(config, workspace, &row)
+                                    };
+                                    (row, path)
+                                }),
+                        );
+                        progress.queued(queue.len() - queued);
+                    }
+                    Err(error) => progress.eprintln(&format!(
+                        "Failed to query rows of database '{}' {} shown on page '{shown_on}': {error:#}",
+                        database.title, database.database_id
+                    )),
+                }
+                continue;
+            }
+            Task::ChildPage {
+                page_id,
+                shown_on,
+                child,
+            } => {
+                match child {
+                    Ok(child) if crawl::is_excluded(config, &child) => {}
+                    Ok(child) => {
+                        let path = previous_graph
+                            .as_ref()
+                            .filter(|_| options.partial)
+                            .and_then(|graph| graph.node(&page_id))
+                            .map(|node| node.path.clone())
+                            .unwrap_or_else(|| layout::page_output_path(config, workspace, &child));
+                        queue.push_back((child, path));
+                        progress.queued(1);
+                    }
+                    Err(error) => progress.eprintln(&format!(
+                        "Failed to get subpage {page_id} of page '{shown_on}': {error:#}"
+                    )),
+                }
+                continue;
+            }
+        };
+        progress.finished();
+        let page = &page;
+        let title = page.title().unwrap_or_default();
+        let change = PageChange {
+            page_id: page.id.to_string(),
+            title,
+            path: Some(output_path.clone()),
+            diff: None,
+        };
+        match result {
+            Ok(page_export) => {
+                let entry = manifest.insert(
+                    page,
+                    &config.output_dir,
+                    &output_path,
+                    page_export.sha256,
+                    page_export.words,
+                    stats::page_metrics(&config.metrics, page),
+                );
+                entry.parts = page_export
+                    .parts
+                    .iter()
+                    .map(|part| manifest::relative_path(&config.output_dir, part))
+                    .collect();
+                if page_export.truncated {
+                    changes.truncated.push(change.clone());
+                }
+                export_state.lock().await.set_last_edited_time(
+                    &page.id,
+                    (!page_export.truncated).then_some(page.last_edited_time),
+                    &page_export.child_databases,
+                    &page_export.child_pages,
+                );
+                database_views.add_row(config, page, &output_path);
+                if config.database_as_notes {
+                    if let Parent::Database { database_id } = &page.parent {
+                        let database_id = database_id.to_string();
+                        database_indexes.add_row(
+                            &database_id,
+                            &database_notes::database_title(workspace, &database_id),
+                            &change.title,
+                            &output_path,
+                        );
+                    }
+                }
+                for child_database in page_export.child_databases {
+                    let newly_followed = if config.database_as_notes {
+                        database_indexes
+                            .add_database(&child_database.database_id, &child_database.title)
+                    } else {
+                        followed_databases.insert(normalize_id(&child_database.database_id))
+                    };
+                    if !newly_followed {
+                        continue;
+                    }
+                    let shown_on = change.title.clone();
+                    in_flight.push(
+                        async move {
+                            let rows =
+                                database_rows(notion_client, config, &child_database.database_id)
+                                    .await;
+                            Task::DatabaseRows {
+                                database: child_database,
+                                shown_on,
+                                rows,
+                            }
+                        }
+                        .boxed(),
+                    );
+                }
+                // exported_page_ids stops cycles, queued_page_ids stops queueing a page twice
+                for child_page_id in page_export.child_pages {
+                    let key = normalize_id(&child_page_id);
+                    if exported_page_ids.contains(&key)
+                        || config.excludes.iter().any(|exclude| same_id(exclude, &key))
+                        || !queued_page_ids.insert(key)
+                    {
+                        continue;
+                    }
+                    let shown_on = change.title.clone();
+                    in_flight.push(
+                        async move {
+                            let child = child_page(notion_client, workspace, &child_page_id).await;
+                            Task::ChildPage {
+                                page_id: child_page_id,
+                                shown_on,
+                                child,
+                            }
+                        }
+                        .boxed(),
+                    );
+                }
+                match page_export.write {
+                    PageWriteResult::Added => changes.added.push(change),
+                    PageWriteResult::Updated { diff } => changes.updated.push(PageChange {
+                        diff: Some(diff),
+                        ..change
+                    }),
+                    PageWriteResult::Unchanged => {}
+                }
+            }
+            Err(error) => {
+                manifest.carry_over(previous_manifest, page);
+                progress.eprintln(&format!(
+                    "Failed to export page '{}' {}: {error:#}",
+                    change.title, change.page_id
+                ));
+                changes.failed.push(PageFailure {
+                    page_id: change.page_id,
+                    title: change.title,
+                    class: FailureClass::classify(&error),
+                    error: format!("{error:#}"),
+                });
+            }
+        }
+    }
+    drop(in_flight);
+    progress.finish();
+    let mut export_state = export_state_lock.into_inner();
+
+    if let Err(error) = database_indexes.write(config).await {
+        eprintln!("Failed to write database index notes with error {error:?}");
+    }
+
+    // a partial run only knows some of the rows
+    if !options.partial {
+        if let Err(error) = database_views.write(config, workspace).await {
+            eprintln!("Failed to write database views with error {error:?}");
+        }
+    }
+
+    // pages a partial run didn't look at aren't gone
+    if !options.partial {
+        for (page_id, page_state) in export_state.remove_missing(&seen_page_ids) {
+            // a page created since under the same title writes to the same path
+            let taken = |path: &Path| {
+                export_state
+                    .pages
+                    .values()
+                    .any(|other| other.output_path.as_deref() == Some(path))
+            };
+            if let Some(path) = page_state
+                .output_path
+                .as_deref()
+                .filter(|path| !taken(path))
+            {
+                remove_note(config, path);
+            }
+            for path in page_state.code_files.iter().filter(|path| path.is_file()) {
+                remove_output_file(config, path);
+            }
+            changes.removed.push(PageChange {
+                page_id,
+                title: page_state.title,
+                path: page_state.output_path,
+                diff: None,
+            });
+        }
+    }
+
+    // whole databases are only exported on full runs
+    if !options.partial {
+        for database_export in &config.database_exports {
+            // with several sources only the one that can see the database exports it
+            if !workspace.has_database(&database_export.database_id) {
+                continue;
+            }
+            if let Err(error) =
+                database_export::export_database(notion_client, config, database_export).await
+            {
+                eprintln!(
+                    "Failed to export database {}: {error:#}",
+                    database_export.database_id
+                );
+            }
+        }
+    }
+
+    export_state.save(&config.output_dir)?;
+    let diff = report::compare(previous_manifest, &manifest);
+    if let Some(format) = config.report {
+        report::write_report(&config.output_dir, format, &diff)?;
+    }
+    // a partial run only looked at some pages, its totals would look like a drop
+    if !options.partial {
+        let entry = report::HistoryEntry::new(&manifest, &diff, &export_state);
+        if let Err(error) = report::append_history(&config.output_dir, &entry) {
+            eprintln!("Failed to append to the report history with error {error:?}");
+        }
+    }
+    manifest.save(&config.output_dir)?;
+    if let Some(lint) = &config.lint {
+        let violations = lint::Linter::new(lint)
+            .and_then(|linter| lint::lint_output(&linter, &config.output_dir, &manifest));
+        match violations {
+            Ok(0) => {}
+            Ok(violations) => println!("{violations} lint violations"),
+            Err(error) => eprintln!("Failed to lint the notes: {error:#}"),
+        }
+    }
+    Ok(changes)
+}
+
+// subpages the search returned don't need another request
+async fn child_page(
+    notion_client: &NotionClient,
+    workspace: &Workspace,
+    page_id: &str,
+) -> anyhow::Result<Page> {
+    if let Some(page) = workspace.page(page_id) {
+        return Ok(page.clone());
+    }
+    notion_client.get_page(PageId::from_str(page_id)?).await
+}
+
+async fn database_rows(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    database_id: &str,
+) -> anyhow::Result<Vec<Page>> {
+    let database_id = DatabaseId::from_str(&normalize_id(database_id))?;
+    let mut rows = notion_client.query_all_rows(database_id).await?;
+    for row in rows.iter_mut() {
+        titles::retitle_page(notion_client, &config.title_sources, row).await;
+    }
+    Ok(rows)
+}
+
+fn check_renderable(blocks: &[Block]) -> anyhow::Result<()> {
+    let mut unsupported = 0;
+    blocks::walk_blocks(blocks, &mut |block| {
+        if matches!(block, Block::Unsupported { .. } | Block::Unknown) {
+            unsupported += 1;
+        }
+    });
+    if unsupported > 0 {
+        return Err(RenderError(format!("{unsupported} unsupported blocks")).into());
+    }
+    Ok(())
+}
+
+// what the scheduler loop waits on
+enum Task {
+    Page(Page, PathBuf, anyhow::Result<PageExport>),
+    DatabaseRows {
+        database: ChildDatabase,
+        // title of the page showing the database
+        shown_on: String,
+        rows: anyhow::Result<Vec<Page>>,
+    },
+    ChildPage {
+        page_id: String,
+        shown_on: String,
+        child: anyhow::Result<Page>,
+    },
+}
+
+enum PageWriteResult {
+    Added,
+    Updated { diff: String },
+    Unchanged,
+}
+
+struct PageExport {
+    write: PageWriteResult,
+    truncated: bool,
+    sha256: String,
+    words: usize,
+    child_databases: Vec<ChildDatabase>,
+    // only collected in recursive mode
+    child_pages: Vec<String>,
+    // the files a split note continues in
+    parts: Vec<PathBuf>,
+}
+
+// every property of a database row, for dataview and other tools querying frontmatter
+// databases with a mapping only get the mapped properties under their own keys
+// relations become links to the related pages
+async fn add_property_frontmatter(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    page: &Page,
+    page_id_cache: &PageIdCache,
+    frontmatter: &mut Frontmatter,
+) -> anyhow::Result<()> {
+    let Parent::Database { database_id } = &page.parent else {
+        return Ok(());
+    };
+    let mapping = config
+        .frontmatter_mappings
+        .iter()
+        .find(|mapping| same_id(&mapping.database_id, &database_id.to_string()));
+    if let Some(mapping) = mapping {
+        for property in &mapping.properties {
+            let Some(value) = page.properties.properties.get(&property.property) else {
+                continue;
+            };
+            let value = property_value(notion_client, config, page_id_cache, value).await?;
+            let value = match property.coerce {
+                Some(coercion) => value.and_then(|value| properties::coerce(value, coercion)),
+                None => value,
+            };
+            if let Some(value) = value {
+                frontmatter.insert(property.key(), value)?;
+            }
+        }
+        return Ok(());
+    }
+    if !config.property_frontmatter {
+        return Ok(());
+    }
+    // sorted so the frontmatter doesn't reorder between runs
+    let mut values: Vec<_> = page.properties.properties.iter().collect();
+    values.sort_by_key(|(name, _)| *name);
+    for (name, value) in values {
+        if let Some(value) = property_value(notion_client, config, page_id_cache, value).await? {
+            frontmatter.insert(name, value)?;
+        }
+    }
+    Ok(())
+}
+
+async fn property_value(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    page_id_cache: &PageIdCache,
+    value: &PropertyValue,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    let PropertyValue::Relation { relation, .. } = value else {
+        return Ok(properties::property_frontmatter_value(value));
+    };
+    let mut links = vec![];
+    for related in relation.iter().flatten() {
+        let title = page_id_cache
+            .get_page_title(&related.id, notion_client)
+            .await?;
+        links.push(match config.flavor {
+            Flavor::Obsidian => internal_link(None, &escape::file_name(&title)),
+            Flavor::Standard => title,
+        });
+    }
+    Ok(Some(serde_json::json!(links)))
+}
+
+// titles and notes of mentioned and linked pages, the renderer can't make requests itself
+// pages the integration can't read are left out and keep linking to notion
+async fn linked_pages(
+    notion_client: &NotionClient,
+    page_ids: &[String],
+    page_title: &str,
+    notes: &HashMap<String, PathBuf>,
+    page_id_cache: &PageIdCache,
+) -> anyhow::Result<HashMap<String, LinkedPage>> {
+    let mut linked = HashMap::new();
+    for page_id in page_ids {
+        if linked.contains_key(&normalize_id(page_id)) {
+            continue;
+        }
+        let id = PageId::from_str(page_id)?;
+        match page_id_cache.get_page_title(&id, notion_client).await {
+            Ok(title) => {
+                let page = LinkedPage {
+                    title,
+                    note: notes.get(&normalize_id(page_id)).cloned(),
+                };
+                linked.insert(normalize_id(page_id), page);
+            }
+            Err(error) => eprintln!(
+                "Failed to look up page {page_id} linked on page '{page_title}': {error:#}"
+            ),
+        }
+    }
+    Ok(linked)
+}
+
+async fn linked_databases(
+    notion_client: &NotionClient,
+    database_ids: &[String],
+    page_title: &str,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut linked = HashMap::new();
+    for database_id in database_ids {
+        let id = DatabaseId::from_str(database_id)?;
+        match notion_client.get_database(id).await {
+            Ok(database) => {
+                linked.insert(normalize_id(database_id), database.title_plain_text());
+            }
+            Err(error) => eprintln!(
+                "Failed to look up database {database_id} linked on page '{page_title}': {error:#}"
+            ),
+        }
+    }
+    Ok(linked)
+}
+
+// `notes` are the exported notes of the linked pages, keyed by normalized page id
+pub async fn resolve_links(
+    notion_client: &NotionClient,
+    page_title: &str,
+    link_targets: &LinkTargets,
+    notes: &HashMap<String, PathBuf>,
+    page_id_cache: &PageIdCache,
+    resources: &mut PageResources,
+) -> anyhow::Result<()> {
+    resources.linked_pages = linked_pages(
+        notion_client,
+        &link_targets.pages,
+        page_title,
+        notes,
+        page_id_cache,
+    )
+    .await?;
+    resources.linked_databases =
+        linked_databases(notion_client, &link_targets.databases, page_title).await?;
+    Ok(())
+}
+
+// renaming or moving a page changes the notes linking to it, even though nobody edited those
+// the links of an unedited note are resolved again and compared to what it was written with
+pub async fn links_changed(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    page_title: &str,
+    state: &PageState,
+    notes: &HashMap<String, PathBuf>,
+    page_id_cache: &PageIdCache,
+    glossary: &Arc<Glossary>,
+) -> anyhow::Result<bool> {
+    let Some(links_hash) = &state.links_hash else {
+        return Ok(true);
+    };
+    let mut resources = PageResources {
+        glossary: Some(glossary.clone()),
+        ..Default::default()
+    };
+    if config.database_as_notes {
+        resources.database_notes =
+            database_notes::index_note_paths(&config.output_dir, &state.child_databases);
+    }
+    resolve_links(
+        notion_client,
+        page_title,
+        &state.link_targets,
+        notes,
+        page_id_cache,
+        &mut resources,
+    )
+    .await?;
+    Ok(fingerprint::links_hash(&resources)? != *links_hash)
+}
+
+// just the rendered blocks without frontmatter, heading or attachments
+pub async fn page_body_markdown(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    page: &Page,
+) -> anyhow::Result<String> {
+    let block_id: BlockId = page.id.clone().into();
+    let blocks = blocks::fetch_block_tree(notion_client, block_id)
+        .await
+        .with_context(|| format!("failed to fetch body of page {}", page.id))?;
+    let resources = PageResources::default();
+    let mut body = String::new();
+    blocks_to_markdown(
+        blocks,
+        &mut RenderContext::new(&mut body, config, &resources),
+    )?;
+    Ok(body.trim_end().to_owned())
+}
+
+// shared by every page of a job
+struct Lookups<'a> {
+    page_id_cache: &'a PageIdCache,
+    glossary: &'a Arc<Glossary>,
+    progress: &'a ExportProgress,
+    redactor: Option<&'a Redactor>,
+    previous_manifest: Option<&'a ExportManifest>,
+}
+
+impl Lookups<'_> {
+    fn previous_entry(&self, page_id: &PageId) -> Option<&ManifestEntry> {
+        self.previous_manifest?
+            .pages
+            .get(&normalize_id(&page_id.to_string()))
+    }
+}
+
+// an unchanged page reports the note it already has, None when the files are gone
+// without markdown notes there is nothing to read, the previous manifest remembers them
+async fn unchanged_export(
+    config: &ExportConfig,
+    output_path: &Path,
+    previous_entry: Option<&ManifestEntry>,
+    child_databases: Vec<ChildDatabase>,
+    child_pages: Vec<String>,
+) -> Option<PageExport> {
+    if !formats::formats_exist(config, output_path) {
+        return None;
+    }
+    let (sha256, words) = if formats::writes_markdown(config) {
+        let previous = tokio::fs::read_to_string(output_path).await.ok()?;
+        let previous = split::with_parts(config, output_path, &previous);
+        let words = previous.split_whitespace().count();
+        (manifest::content_hash(&previous), words)
+    } else {
+        let entry = previous_entry?;
+        (entry.sha256.clone(), entry.words)
+    };
+    Some(PageExport {
+        write: PageWriteResult::Unchanged,
+        truncated: false,
+        sha256,
+        words,
+        child_databases,
+        child_pages,
+        parts: split::existing_parts(config, output_path),
+    })
+}
+
+// the note of a page and every file written next to it or mirrored from it
+pub fn note_files(config: &ExportConfig, note_path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![note_path.to_path_buf()];
+    files.extend(split::existing_parts(config, note_path));
+    files.extend(
+        formats::extra_formats(config)
+            .map(|format| formats::format_path(config, note_path, format)),
+    );
+    files.push(comments::sidecar_path(note_path));
+    files.retain(|path| path.is_file());
+    files
+}
+
+// a renamed page or one whose parent moved leaves its old files behind, a removed page all of them
+fn remove_note(config: &ExportConfig, note_path: &Path) {
+    if !note_path.starts_with(&config.output_dir) {
+        return;
+    }
+    for path in note_files(config, note_path) {
+        remove_output_file(config, &path);
+    }
+}
+
+// a page written to a new path leaves its old note behind
+// two pages that swapped titles swap paths, the other note is still needed
+async fn remove_replaced_note(
+    config: &ExportConfig,
+    export_state: &Mutex<ExportState>,
+    page_id: &PageId,
+    previous_path: &Path,
+) {
+    let export_state = export_state.lock().await;
+    let taken = export_state.pages.iter().any(|(other_id, other)| {
+        !same_id(other_id, &page_id.to_string())
+            && other.output_path.as_deref() == Some(previous_path)
+    });
+    if !taken {
+        remove_note(config, previous_path);
+    }
+}
+
+// folders emptied by the removal go as well, hierarchy moves whole subtrees
+fn remove_output_file(config: &ExportConfig, path: &Path) {
+    if let Err(error) = std::fs::remove_file(path) {
+        eprintln!("Failed to remove {} with error {error:?}", path.display());
+        return;
+    }
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == config.output_dir || std::fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+// comments, wiki verification and column widths come from separate requests so those pages always render
+// before the blocks are fetched any page might have columns
+pub fn renders_extra_content(config: &ExportConfig, page: &Page, blocks: Option<&[Block]>) -> bool {
+    config.export_comments
+        || config.comments_sidecar
+        || wiki::wiki_database(&config.wikis, page).is_some()
+        || (config.columns == ColumnLayout::Html && blocks.is_none_or(columns::has_columns))
+}
+
+async fn process_page(
+    notion_client: &NotionClient,
+    page: &Page,
+    output_path: &Path,
+    config: &ExportConfig,
+    export_state: &Mutex<ExportState>,
+    lookups: Lookups<'_>,
+    options: RunOptions,
+) -> anyhow::Result<PageExport> {
+    let page_id_cache = lookups.page_id_cache;
+    let deadline = options.page_timeout.map(|timeout| Instant::now() + timeout);
+    let page_id = page.id.clone();
+    let page_title = page.title().unwrap_or_default();
+
+    // checked and updated in one go, pages exported at the same time might be swapping paths
+    let (aliases, unedited, notes, previous_path) = {
+        let mut export_state = export_state.lock().await;
+        // keep old titles around so existing wikilinks still resolve
+        let aliases = export_state.record_title(&page_id, &page_title);
+        let previous_path = export_state
+            .output_path(&page_id.to_string())
+            .filter(|previous_path| previous_path != output_path);
+        export_state.set_output_path(&page_id, output_path);
+        let unedited = export_state
+            .unedited_page(&page_id, page.last_edited_time)
+            .cloned();
+        let notes = unedited
+            .as_ref()
+            .map(|state| export_state.output_paths(&state.link_targets.pages))
+            .unwrap_or_default();
+        (aliases, unedited, notes, previous_path)
+    };
+
+    // the old note goes only once the new one is written, a failed export keeps it
+    let export: anyhow::Result<PageExport> = async {
+        // nothing was edited since the note was written, the blocks aren't even fetched
+        if !options.full && !renders_extra_content(config, page, None) {
+            if let Some(state) = unedited {
+                let links_changed = links_changed(
+                    notion_client,
+                    config,
+                    &page_title,
+                    &state,
+                    &notes,
+                    page_id_cache,
+                    lookups.glossary,
+                )
+                .await?;
+                if !links_changed {
+                    let unchanged = unchanged_export(
+                        config,
+                        output_path,
+                        lookups.previous_entry(&page_id),
+                        state.child_databases,
+                        state.child_pages,
+                    );
+                    if let Some(unchanged) = unchanged.await {
+                        return Ok(unchanged);
+                    }
+                }
+            }
+        }
+
+        // a redacted page keeps its note so links to it resolve, nothing of its content is fetched
+        let redacted = redaction::page_action(config, page) == Some(RedactionAction::Redact);
+        let block_id: BlockId = page_id.clone().into();
+        // the json format archives the raw tree
+        let archive = config.formats.contains(&OutputFormat::Json);
+        let BlockTree {
+            blocks,
+            raw,
+            truncated,
+        } = if redacted {
+            BlockTree {
+                blocks: vec![],
+                raw: vec![],
+                truncated: false,
+            }
+        } else {
+            blocks::fetch_block_tree_until(notion_client, block_id, deadline, archive).await?
+        };
+        if options.strict {
+            check_renderable(&blocks)?;
+        }
+        let child_databases = if config.database_as_notes || config.recursive {
+            database_notes::child_databases(&blocks)
+        } else {
+            vec![]
+        };
+        let child_pages = if config.recursive {
+            blocks::child_page_ids(&blocks)
+        } else {
+            vec![]
+        };
+
+        // pages linking here later can skip the request
+        page_id_cache
+            .insert(&page_id.to_string(), &page_title)
+            .await;
+
+        let mut resources = PageResources {
+            note_dir: output_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            page_id: normalize_id(&page_id.to_string()),
+            glossary: Some(lookups.glossary.clone()),
+            ..Default::default()
+        };
+        // only database_as_notes writes the index notes inline databases link to
+        if config.database_as_notes {
+            resources.database_notes =
+                database_notes::index_note_paths(&config.output_dir, &child_databases);
+        }
+        let link_targets = blocks::link_targets(&blocks)?;
+        let notes = export_state.lock().await.output_paths(&link_targets.pages);
+        resolve_links(
+            notion_client,
+            &page_title,
+            &link_targets,
+            &notes,
+            page_id_cache,
+            &mut resources,
+        )
+        .await?;
+
+        // notion moves last_edited_time for edits that don't change anything we render
+        let content_hash =
+            fingerprint::page_content_hash(config, page, &aliases, &blocks, &resources)?;
+        let same_hash =
+            export_state.lock().await.content_hash(&page_id) == Some(content_hash.as_str());
+        if !options.full
+            && !truncated
+            && same_hash
+            && !renders_extra_content(config, page, Some(&blocks))
+        {
+            let unchanged = unchanged_export(
+                config,
+                output_path,
+                lookups.previous_entry(&page_id),
+                child_databases.clone(),
+                child_pages.clone(),
+            );
+            if let Some(unchanged) = unchanged.await {
+                return Ok(unchanged);
+            }
+        }
+
+        let mut page_buffer = String::new();
+
+        let mut frontmatter = Frontmatter::default();
+        if let Some(hugo) = &config.hugo {
+            hugo::add_hugo_frontmatter(hugo, page, &page_title, &mut frontmatter)?;
+        }
+        if !redacted && (config.property_frontmatter || !config.frontmatter_mappings.is_empty()) {
+            add_property_frontmatter(notion_client, config, page, page_id_cache, &mut frontmatter)
+                .await
+                .context("fetching property values")?;
+        }
+        if !aliases.is_empty() {
+            frontmatter.insert("aliases", aliases)?;
+        }
+        for (key, value) in stats::page_metrics(&config.metrics, page) {
+            frontmatter.insert(&key, value)?;
+        }
+        if let Some(wiki) = wiki::wiki_database(&config.wikis, page) {
+            wiki::add_wiki_frontmatter(notion_client, config, wiki, page, &mut frontmatter).await?;
+        }
+        if let Some(redactor) = lookups.redactor {
+            frontmatter.redact(redactor);
+        }
+        match config.hugo.as_ref().map(|hugo| hugo.frontmatter) {
+            Some(FrontmatterFormat::Toml) => page_buffer.push_str(&frontmatter.render_toml()?),
+            _ => page_buffer.push_str(&frontmatter.render()?),
+        }
+        // patterns are applied to the rendered body, the frontmatter was redacted value by value
+        let body_start = page_buffer.len();
+
+        // hugo shows the title from the frontmatter
+        if config.hugo.is_none() {
+            let link_to_page = external_link(Some(&page_title), &notion_page_id_to_url(&page_id));
+
+            // add heading link as first line
+            page_buffer.push_str("# ");
+            page_buffer.push_str(&link_to_page);
+            page_buffer.push_str("\n\n");
+        }
+
+        if config.download_attachments {
+            let attachments_dir =
+                assets::attachments_dir(config.attachments_layout, &config.output_dir, output_path);
+            // downloads run without the lock, other pages keep going meanwhile
+            let mut known = export_state.lock().await.attachments.clone();
+            let downloads = assets::download_attachments(
+                &blocks,
+                &attachments_dir,
+                &mut known,
+                lookups.progress.attachments(),
+                options.offline,
+                deadline,
+            )
+            .await;
+            export_state.lock().await.attachments.extend(known);
+            resources.local_files = downloads?;
+        }
+        let truncated = truncated || resources.local_files.truncated;
+        if config.extract_code_blocks {
+            let code_dir =
+                snippets::code_dir(&config.output_dir, &page_title, &page_id.to_string());
+            let previous = export_state.lock().await.code_files(&page_id);
+            resources.code_files =
+                snippets::extract_code_blocks(&blocks, &code_dir, &previous, lookups.redactor)
+                    .await?;
+            let mut code_files: Vec<PathBuf> = resources.code_files.values().cloned().collect();
+            code_files.sort();
+            export_state
+                .lock()
+                .await
+                .set_code_files(&page_id, code_files);
+        }
+        if config.columns == ColumnLayout::Html {
+            resources.column_widths = columns::fetch_column_widths(notion_client, &blocks)
+                .await
+                .context("fetching column widths")?;
+        }
+        // captions are the alt text, the command fills in for images without one
+        resources.alt_texts = alt_text::image_captions(&raw);
+        if let Some(command) = &config.image_alt_text_command {
+            let generated =
+                alt_text::generate_alt_texts(&blocks, &resources.alt_texts, command).await;
+            resources.alt_texts.extend(generated);
+        }
+        if let Some(command) = &config.equation_svg_command {
+            let attachments_dir =
+                assets::attachments_dir(config.attachments_layout, &config.output_dir, output_path);
+            resources.equation_images =
+                equations::render_equations(&blocks, command, &attachments_dir).await;
+        }
+
+        let comment_threads = if !redacted && (config.export_comments || config.comments_sidecar) {
+            comments::fetch_comment_threads(notion_client, &page_id.to_string(), &blocks)
+                .await
+                .context("fetching comments")?
+        } else {
+            vec![]
+        };
+
+        let blocks_json = archive.then_some(serde_json::Value::Array(raw));
+
+        if redacted {
+            if let Some(redaction) = &config.redaction {
+                page_buffer.push_str(&redaction.replacement);
+                page_buffer.push('\n');
+            }
+        }
+        blocks_to_markdown(
+            blocks,
+            &mut RenderContext::new(&mut page_buffer, config, &resources),
+        )?;
+
+        if truncated {
+            page_buffer.push_str(&format!(
+                "\n> [!warning] {}\n> {}\n",
+                i18n::text(config, Text::ExportTruncated),
+                i18n::text(config, Text::ExportTruncatedDetails)
+            ));
+        }
+
+        if config.export_comments {
+            page_buffer.push_str(&comments::render_comment_threads(
+                config,
+                &comment_threads,
+                &page_id.to_string(),
+            ));
+        }
+        if config.comments_sidecar {
+            comments::write_sidecar(output_path, &comment_threads, lookups.redactor).await?;
+        }
+        if let Some(redactor) = lookups.redactor {
+            let body = redactor.redact(&page_buffer[body_start..]);
+            page_buffer.truncate(body_start);
+            page_buffer.push_str(&body);
+        }
+
+        // a truncated tree isn't the whole page, its hash must not let the next run skip it
+        if !truncated {
+            let links_hash = fingerprint::links_hash(&resources)?;
+            let mut export_state = export_state.lock().await;
+            export_state.set_content_hash(&page_id, content_hash);
+            export_state.set_links(&page_id, link_targets, links_hash);
+        }
+
+        // the json format would still carry every property of a redacted page
+        let format_page = if redacted {
+            Cow::Owned(redaction::without_properties(page))
+        } else {
+            Cow::Borrowed(page)
+        };
+        let formats_existed = formats::formats_exist(config, output_path);
+        let formats_changed = formats::write_formats(
+            config,
+            output_path,
+            &format_page,
+            &page_buffer,
+            blocks_json.as_ref(),
+            lookups.redactor,
+        )
+        .await?;
+        // without a markdown note there is nothing to diff, the other formats tell what changed
+        if !formats::writes_markdown(config) {
+            let write = match (formats_existed, formats_changed) {
+                (false, _) => PageWriteResult::Added,
+                (true, true) => PageWriteResult::Updated {
+                    diff: String::new(),
+                },
+                (true, false) => PageWriteResult::Unchanged,
+            };
+            return Ok(PageExport {
+                write,
+                truncated,
+                sha256: manifest::content_hash(&page_buffer),
+                words: page_buffer.split_whitespace().count(),
+                child_databases,
+                child_pages,
+                parts: vec![],
+            });
+        }
+
+        // html and json stay whole, only the markdown is split
+        let (note, parts) = match split::split_note(config, output_path, &page_title, &page_buffer)
+        {
+            Some(split) => (split.index, split.parts),
+            None => (page_buffer, vec![]),
+        };
+        let mut content = note.clone();
+        for (_, part) in &parts {
+            content.push_str(part);
+        }
+
+        let sha256 = manifest::content_hash(&content);
+        let words = content.split_whitespace().count();
+        let part_paths = parts.iter().map(|(path, _)| path.clone()).collect();
+        let previous_content = tokio::fs::read_to_string(output_path)
+            .await
+            .ok()
+            .map(|previous| split::with_parts(config, output_path, &previous));
+        let write = match &previous_content {
+            None => PageWriteResult::Added,
+            Some(previous) if *previous == content => {
+                return Ok(PageExport {
+                    write: PageWriteResult::Unchanged,
+                    truncated,
+                    sha256,
+                    words,
+                    child_databases,
+                    child_pages,
+                    parts: part_paths,
+                })
+            }
+            Some(previous) => PageWriteResult::Updated {
+                diff: unified_diff(previous, &content),
+            },
+        };
+
+        if let Some(parent) = output_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(output_path, note.as_bytes()).await?;
+        for (path, part) in &parts {
+            tokio::fs::write(path, part.as_bytes()).await?;
+        }
+        split::remove_parts(config, output_path, parts.len() + 1)?;
+
+        Ok(PageExport {
+            write,
+            truncated,
+            sha256,
+            words,
+            child_databases,
+            child_pages,
+            parts: part_paths,
+        })
+    }
+    .await;
+    let export = export?;
+    if let Some(previous_path) = previous_path {
+        remove_replaced_note(config, export_state, &page_id, &previous_path).await;
+    }
+    Ok(export)
+}
@@ -0,0 +1,113 @@
+use crate::{
+    configuration::ExportConfig, glossary::Glossary, manifest::content_hash,
+    markdown::PageResources,
+};
+use notion::models::{block::Block, Page};
+use serde_json::{json, Value};
+
+// bumped whenever a change to the renderer changes the notes it writes
+// notes of older versions are rendered again even though neither page nor settings changed
+const RENDERER_VERSION: u32 = 1;
+
+// settings that only decide what happens around the notes, changing them doesn't outdate any
+// formats are checked on their own, a missing html file renders its page again
+const NON_RENDERING_KEYS: [&str; 7] = [
+    "report",
+    "metrics",
+    "database_exports",
+    "database_views",
+    "formats",
+    "git",
+    "lint",
+];
+
+// fields notion changes without the content changing
+const VOLATILE_KEYS: [&str; 4] = [
+    "last_edited_time",
+    "last_edited_by",
+    "expiry_time",
+    "request_id",
+];
+
+// hash of everything a page's note is rendered from, the export settings and links included
+// notion bumps edit times on property recalculations and hands out freshly signed file urls
+// on every request, both are left out so those pages hash the same as before
+pub fn page_content_hash(
+    config: &ExportConfig,
+    page: &Page,
+    aliases: &[String],
+    blocks: &[Block],
+    resources: &PageResources,
+) -> anyhow::Result<String> {
+    let mut content = json!({
+        "config": renderer_settings(config)?,
+        "title": page.title(),
+        "aliases": aliases,
+        "properties": serde_json::to_value(&page.properties)?,
+        "blocks": serde_json::to_value(blocks)?,
+        "links": links(resources)?,
+    });
+    normalize(&mut content);
+    Ok(content_hash(&content.to_string()))
+}
+
+pub fn settings_hash(config: &ExportConfig) -> anyhow::Result<String> {
+    Ok(content_hash(&renderer_settings(config)?.to_string()))
+}
+
+// hash of the titles and paths the links of a note are written with
+// none of them are part of the page, renaming or moving a linked page doesn't touch it
+pub fn links_hash(resources: &PageResources) -> anyhow::Result<String> {
+    Ok(content_hash(&links(resources)?.to_string()))
+}
+
+fn links(resources: &PageResources) -> anyhow::Result<Value> {
+    Ok(json!({
+        "pages": serde_json::to_value(&resources.linked_pages)?,
+        "databases": serde_json::to_value(&resources.linked_databases)?,
+        "database_notes": serde_json::to_value(&resources.database_notes)?,
+        "glossary": serde_json::to_value(resources.glossary.as_deref().map(Glossary::terms))?,
+    }))
+}
+
+fn renderer_settings(config: &ExportConfig) -> anyhow::Result<Value> {
+    let mut settings = serde_json::to_value(config)?;
+    if let Value::Object(object) = &mut settings {
+        for key in NON_RENDERING_KEYS {
+            object.remove(key);
+        }
+    }
+    Ok(json!({
+        "renderer_version": RENDERER_VERSION,
+        "settings": settings,
+    }))
+}
+
+fn normalize(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for key in VOLATILE_KEYS {
+                object.remove(key);
+            }
+            // the signature in the query string changes, the file behind it doesn't
+            if object.get("type").and_then(Value::as_str) == Some("file") {
+                if let Some(Value::String(url)) =
+                    object.get_mut("file").and_then(|file| file.get_mut("url"))
+                {
+                    if let Some(query_start) = url.find('?') {
+                        url.truncate(query_start);
+                    }
+                }
+            }
+            for nested in object.values_mut() {
+                normalize(nested);
+            }
+        }
+        Value::Array(array) => {
+            for nested in array {
+                normalize(nested);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{crawl::PageGraph, workspace::Workspace};
+use notion::models::{block::FileOrEmojiObject, Page};
+use std::{collections::HashMap, path::Path};
+
+// read by file managers and obsidian plugins that show folder icons
+const FOLDER_ICON_FILE_NAME: &str = ".folder-icon";
+
+// uploaded and external icons are images, only emojis fit into a name
+pub fn page_emoji(page: &Page) -> Option<&str> {
+    match page.icon.as_ref()? {
+        FileOrEmojiObject::Emoji { emoji } => Some(emoji),
+        _ => None,
+    }
+}
+
+// `🚀 Launch` for a folder of a page with an icon
+pub fn folder_name(page: &Page, title: &str) -> String {
+    match page_emoji(page) {
+        Some(emoji) => format!("{emoji} {title}"),
+        None => title.to_owned(),
+    }
+}
+
+// a page has a folder when its children are written somewhere other than next to it
+// that's where its emoji goes
+pub fn write_folder_icons(graph: &PageGraph, workspace: &Workspace) -> anyhow::Result<()> {
+    let paths: HashMap<&str, &Path> = graph
+        .pages
+        .iter()
+        .map(|node| (node.page_id.as_str(), node.path.as_path()))
+        .collect();
+    for node in &graph.pages {
+        let Some(emoji) = workspace.page(&node.page_id).and_then(page_emoji) else {
+            continue;
+        };
+        let folder = node
+            .children
+            .first()
+            .and_then(|child_id| paths.get(child_id.as_str()))
+            .and_then(|child_path| child_path.parent());
+        let Some(folder) = folder else {
+            continue;
+        };
+        if Some(folder) == node.path.parent() {
+            continue;
+        }
+        std::fs::create_dir_all(folder)?;
+        std::fs::write(folder.join(FOLDER_ICON_FILE_NAME), emoji)?;
+    }
+    Ok(())
+}
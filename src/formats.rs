@@ -0,0 +1,219 @@
+use crate::{
+    configuration::{ExportConfig, OutputFormat},
+    frontmatter::strip_frontmatter,
+    layout,
+    redaction::Redactor,
+    report::escape_html,
+};
+use notion::models::Page;
+use pulldown_cmark::{CowStr, Event, LinkType, Options, Parser, Tag};
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+
+// markdown alone keeps the notes directly in the output directory like it always did
+// with other formats every format gets a directory of its own, `notes/markdown`, `notes/html`, `notes/json`
+pub fn separate_dirs(formats: &[OutputFormat]) -> bool {
+    formats != [OutputFormat::Markdown]
+}
+
+// where a job's notes go, state, manifest and attachments live with them
+pub fn notes_dir(output_dir: &Path, formats: &[OutputFormat]) -> PathBuf {
+    if separate_dirs(formats) {
+        output_dir.join(OutputFormat::Markdown.dir_name())
+    } else {
+        output_dir.to_path_buf()
+    }
+}
+
+// the directory holding every format, the job's output directory is the markdown one below it
+pub fn formats_root(config: &ExportConfig) -> &Path {
+    if separate_dirs(&config.formats) {
+        config.output_dir.parent().unwrap_or(&config.output_dir)
+    } else {
+        &config.output_dir
+    }
+}
+
+pub fn writes_markdown(config: &ExportConfig) -> bool {
+    config.formats.contains(&OutputFormat::Markdown)
+}
+
+// every format besides markdown, which is rendered anyway since the others are made from it
+pub fn extra_formats(config: &ExportConfig) -> impl Iterator<Item = OutputFormat> + '_ {
+    config
+        .formats
+        .iter()
+        .copied()
+        .filter(|format| *format != OutputFormat::Markdown)
+}
+
+// `notes/markdown/Page.md` -> `notes/html/Page.html`, the tree of every format mirrors the notes
+pub fn format_path(config: &ExportConfig, note_path: &Path, format: OutputFormat) -> PathBuf {
+    let relative = note_path
+        .strip_prefix(&config.output_dir)
+        .unwrap_or(note_path);
+    formats_root(config)
+        .join(format.dir_name())
+        .join(relative)
+        .with_extension(format.extension())
+}
+
+// the note itself counts when markdown is written
+pub fn formats_exist(config: &ExportConfig, note_path: &Path) -> bool {
+    (!writes_markdown(config) || note_path.exists())
+        && extra_formats(config).all(|format| format_path(config, note_path, format).exists())
+}
+
+// the expensive part is fetching the page, every format is rendered from what the markdown was
+// `blocks` is only needed for json, the markdown was already redacted but the raw json wasn't
+pub async fn write_formats(
+    config: &ExportConfig,
+    note_path: &Path,
+    page: &Page,
+    markdown: &str,
+    blocks: Option<&Value>,
+    redactor: Option<&Redactor>,
+) -> anyhow::Result<bool> {
+    let mut changed = false;
+    for format in extra_formats(config) {
+        let path = format_path(config, note_path, format);
+        let content = match format {
+            OutputFormat::Markdown => continue,
+            OutputFormat::Html => {
+                let title = page.title().unwrap_or_default();
+                markdown_to_html(config, &title, markdown, note_path, &path)
+            }
+            OutputFormat::Json => {
+                let mut value = json!({
+                    "page": page,
+                    "blocks": blocks.cloned().unwrap_or_default(),
+                });
+                if let Some(redactor) = redactor {
+                    redactor.redact_json(&mut value);
+                }
+                serde_json::to_string_pretty(&value)?
+            }
+        };
+        // rewriting identical files would only churn modification times
+        if tokio::fs::read_to_string(&path).await.ok().as_deref() == Some(content.as_str()) {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, content).await?;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+fn markdown_to_html(
+    config: &ExportConfig,
+    title: &str,
+    markdown: &str,
+    note_path: &Path,
+    html_path: &Path,
+) -> String {
+    let links = HtmlLinks {
+        notes_dir: &config.output_dir,
+        html_root: formats_root(config).join(OutputFormat::Html.dir_name()),
+        note_dir: note_path.parent().unwrap_or(Path::new("")),
+        html_dir: html_path.parent().unwrap_or(Path::new("")),
+    };
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_WIKILINKS);
+    let events = Parser::new_ext(strip_frontmatter(markdown), options).map(|event| match event {
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            dest_url: links.destination(dest_url, link_type, false),
+            link_type,
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Image {
+            dest_url: links.destination(dest_url, link_type, true),
+            link_type,
+            title,
+            id,
+        }),
+        event => event,
+    });
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, events);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape_html(title)
+    )
+}
+
+// where the links of one note point once it is html
+struct HtmlLinks<'a> {
+    notes_dir: &'a Path,
+    html_root: PathBuf,
+    note_dir: &'a Path,
+    html_dir: &'a Path,
+}
+
+impl HtmlLinks<'_> {
+    // links between notes point at the mirrored html file
+    // everything else local, like attachments, is still next to the markdown notes
+    fn destination<'a>(
+        &self,
+        destination: CowStr<'a>,
+        link_type: LinkType,
+        embed: bool,
+    ) -> CowStr<'a> {
+        if destination.contains("://")
+            || destination.starts_with('#')
+            || destination.starts_with("mailto:")
+        {
+            return destination;
+        }
+        // wikilinks name the note by its path inside the vault without the extension
+        if let LinkType::WikiLink { .. } = link_type {
+            let file = self.notes_dir.join(destination.as_ref());
+            let target = if embed || file.is_file() {
+                file
+            } else {
+                self.html_root.join(format!("{destination}.html"))
+            };
+            return self.relative(&target);
+        }
+        if let Some(note) = destination.strip_suffix(".md") {
+            return CowStr::from(format!("{note}.html"));
+        }
+        self.relative(&normalize(&self.note_dir.join(destination.as_ref())))
+    }
+
+    fn relative(&self, target: &Path) -> CowStr<'static> {
+        let relative = layout::relative_path(self.html_dir, target);
+        CowStr::from(relative.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+// resolves `..` without touching the file system, the target might not exist yet
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,72 @@
+use crate::redaction::Redactor;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+// yaml between `---` or toml between `+++` lines
+const FENCES: [&str; 2] = ["---", "+++"];
+
+#[derive(Debug, Default)]
+pub struct Frontmatter {
+    fields: Mapping,
+}
+
+impl Frontmatter {
+    pub fn insert<T: Serialize>(&mut self, key: &str, value: T) -> anyhow::Result<()> {
+        self.fields
+            .insert(Value::String(key.to_owned()), serde_yaml::to_value(value)?);
+        Ok(())
+    }
+
+    pub fn redact(&mut self, redactor: &Redactor) {
+        for value in self.fields.values_mut() {
+            redactor.redact_yaml(value);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn render(&self) -> anyhow::Result<String> {
+        if self.is_empty() {
+            return Ok(String::new());
+        }
+        Ok(format!(
+            "---\n{}---\n\n",
+            serde_yaml::to_string(&self.fields)?
+        ))
+    }
+
+    // toml has no null, empty fields are left out
+    pub fn render_toml(&self) -> anyhow::Result<String> {
+        let fields: Mapping = self
+            .fields
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if fields.is_empty() {
+            return Ok(String::new());
+        }
+        // the toml value orders plain values before tables, which toml requires
+        let table = toml::Value::try_from(fields)?;
+        Ok(format!("+++\n{}+++\n\n", toml::to_string(&table)?))
+    }
+}
+
+// bytes of the frontmatter at the start of a note including its closing line, 0 without one
+pub fn frontmatter_len(note: &str) -> usize {
+    FENCES
+        .iter()
+        .find_map(|fence| {
+            let end = note
+                .strip_prefix(&format!("{fence}\n"))?
+                .find(&format!("\n{fence}\n"))?;
+            Some(end + 2 * (fence.len() + 1) + 1)
+        })
+        .unwrap_or(0)
+}
+
+pub fn strip_frontmatter(note: &str) -> &str {
+    &note[frontmatter_len(note)..]
+}
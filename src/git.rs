@@ -0,0 +1,106 @@
+use crate::{
+    changes::{ChangeSet, PageChange},
+    configuration::GitConfig,
+};
+use anyhow::bail;
+use std::path::Path;
+
+// rewritten by every run, committing it would make every run a commit
+const EXCLUDED: &str = ":(exclude).notion-bridge/lock";
+
+// the output directory becomes a repository of its own unless it already is inside one
+// only paths below it are staged and committed, the rest of a surrounding repository is left alone
+pub async fn commit_output(
+    git: &GitConfig,
+    output_dir: &Path,
+    changes: &ChangeSet,
+) -> anyhow::Result<()> {
+    if !output_dir.exists() {
+        return Ok(());
+    }
+    if run_git(output_dir, &["rev-parse", "--is-inside-work-tree"])
+        .await
+        .is_err()
+    {
+        run_git(output_dir, &["init", "--quiet"]).await?;
+    }
+    run_git(output_dir, &["add", "--all", "--", ".", EXCLUDED]).await?;
+    let staged = run_git(
+        output_dir,
+        &["diff", "--cached", "--name-only", "--relative", "--", "."],
+    )
+    .await?;
+    if staged.lines().all(is_run_metadata) {
+        return Ok(());
+    }
+    let message = commit_message(changes);
+    run_git(
+        output_dir,
+        &["commit", "--quiet", "-m", &message, "--", "."],
+    )
+    .await?;
+    println!("Committed {}", output_dir.display());
+    if git.push {
+        run_git(output_dir, &["push", "--quiet"]).await?;
+    }
+    Ok(())
+}
+
+// rewritten by every run even when no page changed, they go along with changed notes but never make a commit on their own
+// paths are relative to the output directory
+fn is_run_metadata(path: &str) -> bool {
+    path.starts_with(".notion-bridge/")
+        || path == "export-manifest.json"
+        || path.starts_with("export-report.")
+}
+
+async fn run_git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await?;
+    if !output.status.success() {
+        bail!(
+            "git {} exited with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// `Export notion: 2 added, 1 updated` and the titles below
+// files changing without a page changing, like database exports, only get the summary
+fn commit_message(changes: &ChangeSet) -> String {
+    let mut message = format!(
+        "Export notion: {} added, {} updated, {} removed\n",
+        changes.added.len(),
+        changes.updated.len(),
+        changes.removed.len()
+    );
+    let sections = [
+        ("Added", &changes.added),
+        ("Updated", &changes.updated),
+        ("Removed", &changes.removed),
+    ];
+    for (heading, pages) in sections {
+        if pages.is_empty() {
+            continue;
+        }
+        message.push_str(&format!("\n{heading}:\n"));
+        for page in pages.iter() {
+            message.push_str(&format!("- {}\n", page_line(page)));
+        }
+    }
+    message
+}
+
+fn page_line(page: &PageChange) -> String {
+    match &page.path {
+        Some(path) => format!("{} ({})", page.title, path.display()),
+        None => page.title.clone(),
+    }
+}
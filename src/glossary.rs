@@ -0,0 +1,179 @@
+use crate::{
+    configuration::GlossaryConfig,
+    crawl::PageGraph,
+    ids::{normalize_id, same_id},
+    properties::page_property_text,
+    workspace::Workspace,
+};
+use notion::models::Parent;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+// terms from the rows of the glossary database, only rows that are exported can be linked
+#[derive(Debug, Default)]
+pub struct Glossary {
+    pattern: Option<Regex>,
+    // keyed by lowercase term
+    terms: HashMap<String, GlossaryTerm>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GlossaryTerm {
+    pub page_id: String,
+    pub note: PathBuf,
+    pub definition: Option<String>,
+}
+
+impl Glossary {
+    pub fn build(
+        config: Option<&GlossaryConfig>,
+        workspace: &Workspace,
+        graph: &PageGraph,
+    ) -> Self {
+        let Some(config) = config else {
+            return Self::default();
+        };
+        let mut terms = HashMap::new();
+        for node in &graph.pages {
+            let Some(page) = workspace.page(&node.page_id) else {
+                continue;
+            };
+            match &page.parent {
+                Parent::Database { database_id }
+                    if same_id(&config.database_id, &database_id.to_string()) => {}
+                _ => continue,
+            }
+            let term = node.title.trim();
+            if term.is_empty() {
+                continue;
+            }
+            let definition = config
+                .definition_property
+                .as_deref()
+                .and_then(|property| page_property_text(page, property))
+                .filter(|definition| !definition.is_empty());
+            terms.insert(
+                term.to_lowercase(),
+                GlossaryTerm {
+                    page_id: normalize_id(&node.page_id),
+                    note: node.path.clone(),
+                    definition,
+                },
+            );
+        }
+        Self {
+            pattern: term_pattern(terms.keys()),
+            terms,
+        }
+    }
+
+    // keyed by lowercase term
+    pub fn terms(&self) -> &HashMap<String, GlossaryTerm> {
+        &self.terms
+    }
+
+    // links the first occurrence of every term not linked yet on this page
+    // `render` gets the term and the text as written and returns the markdown replacing it
+    pub fn expand(
+        &self,
+        text: &str,
+        page_id: &str,
+        used: &mut HashSet<String>,
+        render: &dyn Fn(&GlossaryTerm, &str) -> String,
+    ) -> Option<String> {
+        let pattern = self.pattern.as_ref()?;
+        let mut expanded = String::new();
+        let mut last_end = 0;
+        for found in pattern.find_iter(text) {
+            let key = found.as_str().to_lowercase();
+            let Some(term) = self.terms.get(&key) else {
+                continue;
+            };
+            // a term's own note doesn't link to itself
+            if term.page_id == normalize_id(page_id) || !used.insert(key) {
+                continue;
+            }
+            expanded.push_str(&text[last_end..found.start()]);
+            expanded.push_str(&render(term, found.as_str()));
+            last_end = found.end();
+        }
+        if last_end == 0 {
+            return None;
+        }
+        expanded.push_str(&text[last_end..]);
+        Some(expanded)
+    }
+}
+
+// longest terms first so `API gateway` wins over `API`
+fn term_pattern<'a>(terms: impl Iterator<Item = &'a String>) -> Option<Regex> {
+    let mut terms: Vec<&String> = terms.collect();
+    if terms.is_empty() {
+        return None;
+    }
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+    let alternatives: Vec<String> = terms.iter().map(|term| regex::escape(term)).collect();
+    Regex::new(&format!(r"(?i)\b(?:{})\b", alternatives.join("|"))).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glossary(terms: &[(&str, &str)]) -> Glossary {
+        let terms: HashMap<String, GlossaryTerm> = terms
+            .iter()
+            .map(|(term, page_id)| {
+                let term_entry = GlossaryTerm {
+                    page_id: normalize_id(page_id),
+                    note: PathBuf::from(format!("{term}.md")),
+                    definition: None,
+                };
+                (term.to_lowercase(), term_entry)
+            })
+            .collect();
+        Glossary {
+            pattern: term_pattern(terms.keys()),
+            terms,
+        }
+    }
+
+    fn link(term: &GlossaryTerm, written: &str) -> String {
+        format!("[{written}]({})", term.note.display())
+    }
+
+    #[test]
+    fn only_the_first_occurrence_on_a_page_is_linked() {
+        let glossary = glossary(&[("API", "a1")]);
+        let mut used = HashSet::new();
+        assert_eq!(
+            glossary.expand("the API calls the API", "p1", &mut used, &link),
+            Some(String::from("the [API](API.md) calls the API"))
+        );
+        // later blocks of the same page share what was linked
+        assert_eq!(glossary.expand("another API", "p1", &mut used, &link), None);
+    }
+
+    #[test]
+    fn terms_match_whole_words_in_any_case() {
+        let glossary = glossary(&[("API", "a1"), ("API gateway", "a2")]);
+        let mut used = HashSet::new();
+        assert_eq!(
+            glossary.expand("rapid APIs pass an api Gateway", "p1", &mut used, &link),
+            Some(String::from(
+                "rapid APIs pass an [api Gateway](API gateway.md)"
+            ))
+        );
+    }
+
+    #[test]
+    fn a_term_does_not_link_its_own_note() {
+        let glossary = glossary(&[("API", "a1")]);
+        let mut used = HashSet::new();
+        assert_eq!(glossary.expand("the API", "a1", &mut used, &link), None);
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{
+    configuration::{ExportConfig, HugoConfig},
+    escape,
+    frontmatter::Frontmatter,
+    properties::{page_checkbox, page_property_text, property_frontmatter_value},
+};
+use notion::models::Page;
+use serde_json::Value;
+use std::path::Path;
+
+// hugo themes show the title from the frontmatter, the note has no heading of its own
+pub fn add_hugo_frontmatter(
+    hugo: &HugoConfig,
+    page: &Page,
+    title: &str,
+    frontmatter: &mut Frontmatter,
+) -> anyhow::Result<()> {
+    frontmatter.insert("title", title)?;
+    let date = hugo
+        .date_property
+        .as_deref()
+        .and_then(|property| page_property_text(page, property))
+        .filter(|date| !date.is_empty())
+        .unwrap_or_else(|| page.created_time.to_rfc3339());
+    frontmatter.insert("date", date)?;
+    frontmatter.insert("draft", page_checkbox(page, &hugo.draft_property))?;
+    let tags = match page
+        .properties
+        .properties
+        .get(&hugo.tags_property)
+        .and_then(property_frontmatter_value)
+    {
+        Some(Value::Array(tags)) => tags,
+        Some(Value::String(tag)) => vec![Value::String(tag)],
+        _ => vec![],
+    };
+    if !tags.is_empty() {
+        frontmatter.insert("tags", tags)?;
+    }
+    Ok(())
+}
+
+// hugo resolves the path below the content directory and fails the build when the note is gone
+pub fn relref_link(config: &ExportConfig, title: &str, note: &Path) -> String {
+    let content_path = note
+        .strip_prefix(&config.output_dir)
+        .unwrap_or(note)
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace('"', "\\\"");
+    format!(
+        "[{}]({{{{< relref \"{content_path}\" >}}}})",
+        escape::link_text(title)
+    )
+}
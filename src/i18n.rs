@@ -0,0 +1,77 @@
+use crate::configuration::ExportConfig;
+
+// text the bridge adds to notes on its own, every one can be replaced through
+// `translations` in the export config using the key below
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    ChildPage,
+    ChildDatabase,
+    Comments,
+    UnknownAuthor,
+    ExportTruncated,
+    ExportTruncatedDetails,
+    // frontmatter keys of wiki pages
+    Owners,
+    Verification,
+    VerifiedBy,
+    VerifiedAt,
+    VerifiedUntil,
+}
+
+impl Text {
+    pub const ALL: [Text; 11] = [
+        Text::ChildPage,
+        Text::ChildDatabase,
+        Text::Comments,
+        Text::UnknownAuthor,
+        Text::ExportTruncated,
+        Text::ExportTruncatedDetails,
+        Text::Owners,
+        Text::Verification,
+        Text::VerifiedBy,
+        Text::VerifiedAt,
+        Text::VerifiedUntil,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Text::ChildPage => "child_page",
+            Text::ChildDatabase => "child_database",
+            Text::Comments => "comments",
+            Text::UnknownAuthor => "unknown_author",
+            Text::ExportTruncated => "export_truncated",
+            Text::ExportTruncatedDetails => "export_truncated_details",
+            Text::Owners => "owners",
+            Text::Verification => "verification",
+            Text::VerifiedBy => "verified_by",
+            Text::VerifiedAt => "verified_at",
+            Text::VerifiedUntil => "verified_until",
+        }
+    }
+
+    fn english(self) -> &'static str {
+        match self {
+            Text::ChildPage => "Child page",
+            Text::ChildDatabase => "Child database",
+            Text::Comments => "Comments",
+            Text::UnknownAuthor => "Unknown",
+            Text::ExportTruncated => "Export truncated",
+            Text::ExportTruncatedDetails => {
+                "This page took longer than the page timeout, the rest of it is missing."
+            }
+            Text::Owners => "owners",
+            Text::Verification => "verification",
+            Text::VerifiedBy => "verified_by",
+            Text::VerifiedAt => "verified_at",
+            Text::VerifiedUntil => "verified_until",
+        }
+    }
+}
+
+pub fn text(config: &ExportConfig, text: Text) -> &str {
+    config
+        .translations
+        .get(text.key())
+        .map(String::as_str)
+        .unwrap_or_else(|| text.english())
+}
@@ -0,0 +1,32 @@
+// notion ids show up both with and without dashes
+pub fn normalize_id(id: &str) -> String {
+    id.replace('-', "").to_lowercase()
+}
+
+pub fn same_id(a: &str, b: &str) -> bool {
+    normalize_id(a) == normalize_id(b)
+}
+
+// 32 hex digits once the dashes are gone
+pub fn is_valid_id(id: &str) -> bool {
+    let normalized = normalize_id(id);
+    normalized.len() == 32 && normalized.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// page ids as copied from notion, bare or dashed, or the page url
+// urls end in `Title-<id>` with an optional query like `?pvs=4`
+pub fn parse_page_reference(reference: &str) -> Option<String> {
+    let reference = reference.trim();
+    let without_query = reference.split(['?', '#']).next().unwrap_or_default();
+    let last_segment = without_query
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let normalized: Vec<char> = normalize_id(last_segment).chars().collect();
+    if normalized.len() < 32 {
+        return None;
+    }
+    let id: String = normalized[normalized.len() - 32..].iter().collect();
+    is_valid_id(&id).then_some(id)
+}
@@ -0,0 +1,614 @@
+use crate::{
+    api::{find_source, Source},
+    append::{append_blocks, NewBlock, APPEND_CHUNK_SIZE},
+    capabilities::{self, Capability},
+    frontmatter::strip_frontmatter,
+    ids,
+};
+use anyhow::Context;
+use notion::ids::{BlockId, PageId};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde_json::{json, Value};
+use std::{path::Path, str::FromStr};
+
+// the api accepts at most this many characters in one rich text object
+const RICH_TEXT_LIMIT: usize = 2000;
+// and at most this many rich text objects in one block
+const RICH_TEXT_RUNS_LIMIT: usize = 100;
+
+// languages notion highlights under the same name, everything else becomes plain text
+const CODE_LANGUAGES: &[&str] = &[
+    "bash",
+    "c",
+    "c#",
+    "c++",
+    "css",
+    "diff",
+    "docker",
+    "go",
+    "graphql",
+    "haskell",
+    "html",
+    "java",
+    "javascript",
+    "json",
+    "kotlin",
+    "lua",
+    "makefile",
+    "markdown",
+    "php",
+    "powershell",
+    "python",
+    "ruby",
+    "rust",
+    "scala",
+    "shell",
+    "sql",
+    "swift",
+    "toml",
+    "typescript",
+    "xml",
+    "yaml",
+];
+
+// blocks still collecting their content, innermost last
+enum Container {
+    Root(Vec<NewBlock>),
+    Quote {
+        text: Option<Vec<Value>>,
+        children: Vec<NewBlock>,
+    },
+    List {
+        ordered: bool,
+    },
+    Item {
+        ordered: bool,
+        checked: Option<bool>,
+        text: Option<Vec<Value>>,
+        children: Vec<NewBlock>,
+    },
+    Table {
+        rows: Vec<Vec<Vec<Value>>>,
+        row: Vec<Vec<Value>>,
+    },
+}
+
+#[derive(Default)]
+struct Marks {
+    bold: usize,
+    italic: usize,
+    strikethrough: usize,
+    link: Option<String>,
+}
+
+#[derive(Default)]
+struct Converter {
+    stack: Vec<Container>,
+    // rich text of the paragraph, heading, item or cell being read
+    inline: Option<Vec<Value>>,
+    marks: Marks,
+    // url and caption of an image, images become blocks of their own after their paragraph
+    image: Option<(String, String)>,
+    images: Vec<NewBlock>,
+    code: Option<(String, String)>,
+}
+
+// creates a page below the parent with the blocks of a markdown file
+// notion can't take uploads through the api, so only images with an http url are imported
+pub async fn import_markdown(
+    sources: &[Source],
+    file: &Path,
+    parent: &str,
+    title: Option<&str>,
+    source: Option<&str>,
+) -> anyhow::Result<()> {
+    let notion_client = &find_source(sources, source)?.client;
+    notion_client.ensure_writable()?;
+    let parent_id = ids::parse_page_reference(parent)
+        .with_context(|| format!("{parent} is neither a page id nor a notion url"))?;
+    let parent_id = PageId::from_str(&parent_id)?;
+    capabilities::require(notion_client, Capability::InsertContent, &parent_id).await?;
+
+    let markdown = std::fs::read_to_string(file)
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let (heading, blocks) = markdown_to_blocks(&markdown);
+    let title = title.map(str::to_owned).or(heading).unwrap_or_else(|| {
+        file.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into()
+    });
+
+    let created_page = notion_client
+        .create_page(&json!({
+            "parent": { "page_id": parent_id.to_string() },
+            "properties": {
+                "title": { "title": rich_text(&title) }
+            },
+        }))
+        .await?;
+    let page_id = created_page
+        .get("id")
+        .and_then(Value::as_str)
+        .context("created page has no id")?;
+    if let Err(error) = append_blocks(notion_client, BlockId::from_str(page_id)?, &blocks).await {
+        // a half filled page would pass for the whole file
+        let archived = notion_client
+            .archive_page(&PageId::from_str(page_id)?)
+            .await;
+        let context = match archived {
+            Ok(_) => format!(
+                "appending the blocks of {}, the partly filled page {page_id} was moved to the trash",
+                file.display()
+            ),
+            Err(archive_error) => format!(
+                "appending the blocks of {}, the partly filled page {page_id} is left behind since archiving it failed: {archive_error:#}",
+                file.display()
+            ),
+        };
+        return Err(error.context(context));
+    }
+
+    let url = created_page
+        .get("url")
+        .and_then(Value::as_str)
+        .unwrap_or(page_id);
+    println!("Imported {} as {title} {url}", file.display());
+    Ok(())
+}
+
+// the title is taken from a leading `# ` heading, which isn't repeated in the body
+fn markdown_to_blocks(markdown: &str) -> (Option<String>, Vec<NewBlock>) {
+    let markdown = strip_frontmatter(markdown);
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    let mut converter = Converter {
+        stack: vec![Container::Root(vec![])],
+        ..Default::default()
+    };
+    for event in Parser::new_ext(markdown, options) {
+        converter.event(event);
+    }
+    let Some(Container::Root(mut blocks)) = converter.stack.pop() else {
+        return (None, vec![]);
+    };
+    let title = blocks
+        .first()
+        .filter(|first| first.block["type"] == "heading_1")
+        .map(|first| plain_text(&first.block["heading_1"]["rich_text"]));
+    if title.is_some() {
+        blocks.remove(0);
+    }
+    (title, blocks)
+}
+
+impl Converter {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => {
+                if let Some((_, code)) = &mut self.code {
+                    code.push_str(&text);
+                } else if let Some((_, caption)) = &mut self.image {
+                    caption.push_str(&text);
+                } else {
+                    self.push_text(&text, false);
+                }
+            }
+            Event::Code(code) => self.push_text(&code, true),
+            Event::InlineHtml(html) => self.push_text(&html, false),
+            Event::SoftBreak => self.push_text(" ", false),
+            Event::HardBreak => self.push_text("\n", false),
+            Event::Rule => self.push_block(block("divider", json!({}))),
+            Event::TaskListMarker(checked) => {
+                if let Some(Container::Item { checked: item, .. }) = self.stack.last_mut() {
+                    *item = Some(checked);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph | Tag::Heading { .. } | Tag::TableCell => {
+                self.inline = Some(vec![]);
+            }
+            Tag::BlockQuote(_) => self.stack.push(Container::Quote {
+                text: None,
+                children: vec![],
+            }),
+            Tag::CodeBlock(kind) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_owned(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((language, String::new()));
+            }
+            Tag::List(start) => {
+                self.finish_item_text();
+                self.stack.push(Container::List {
+                    ordered: start.is_some(),
+                });
+            }
+            Tag::Item => {
+                let ordered = matches!(self.stack.last(), Some(Container::List { ordered: true }));
+                self.stack.push(Container::Item {
+                    ordered,
+                    checked: None,
+                    text: None,
+                    children: vec![],
+                });
+            }
+            Tag::Table(_) => self.stack.push(Container::Table {
+                rows: vec![],
+                row: vec![],
+            }),
+            Tag::Emphasis => self.marks.italic += 1,
+            Tag::Strong => self.marks.bold += 1,
+            Tag::Strikethrough => self.marks.strikethrough += 1,
+            Tag::Link { dest_url, .. } => self.marks.link = Some(dest_url.to_string()),
+            Tag::Image { dest_url, .. } => self.image = Some((dest_url.to_string(), String::new())),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                let text = self.inline.take().unwrap_or_default();
+                match self.stack.last_mut() {
+                    // the first paragraph is the text of the item or quote itself
+                    Some(
+                        Container::Item { text: item, .. } | Container::Quote { text: item, .. },
+                    ) if item.is_none() => *item = Some(text),
+                    _ if text.is_empty() => {}
+                    // a paragraph with more runs than a block takes continues in the next one
+                    _ => {
+                        for text in text.chunks(RICH_TEXT_RUNS_LIMIT) {
+                            self.push_block(block("paragraph", json!({ "rich_text": text })));
+                        }
+                    }
+                }
+                self.flush_images();
+            }
+            TagEnd::Heading(level) => {
+                let text = self.inline.take().unwrap_or_default();
+                // notion has three levels of headings
+                let block_type = match level {
+                    HeadingLevel::H1 => "heading_1",
+                    HeadingLevel::H2 => "heading_2",
+                    _ => "heading_3",
+                };
+                self.push_block(block(block_type, json!({ "rich_text": text })));
+                self.flush_images();
+            }
+            TagEnd::BlockQuote(_) => {
+                if let Some(Container::Quote { text, children }) = self.stack.pop() {
+                    self.push_block(NewBlock {
+                        children,
+                        ..block("quote", json!({ "rich_text": text.unwrap_or_default() }))
+                    });
+                }
+            }
+            TagEnd::CodeBlock => {
+                if let Some((language, code)) = self.code.take() {
+                    let language = language.to_lowercase();
+                    let language = match language.as_str() {
+                        "sh" | "zsh" => "shell",
+                        "js" => "javascript",
+                        "ts" => "typescript",
+                        "py" => "python",
+                        "rs" => "rust",
+                        "yml" => "yaml",
+                        language if CODE_LANGUAGES.contains(&language) => language,
+                        _ => "plain text",
+                    };
+                    let code = code.strip_suffix('\n').unwrap_or(&code);
+                    self.push_block(block(
+                        "code",
+                        json!({ "rich_text": rich_text(code), "language": language }),
+                    ));
+                }
+            }
+            TagEnd::List(_) => {
+                self.stack.pop();
+            }
+            TagEnd::Item => {
+                self.finish_item_text();
+                if let Some(Container::Item {
+                    ordered,
+                    checked,
+                    text,
+                    children,
+                }) = self.stack.pop()
+                {
+                    let text = text.unwrap_or_default();
+                    let item = match (checked, ordered) {
+                        (Some(checked), _) => {
+                            block("to_do", json!({ "rich_text": text, "checked": checked }))
+                        }
+                        (None, true) => block("numbered_list_item", json!({ "rich_text": text })),
+                        (None, false) => block("bulleted_list_item", json!({ "rich_text": text })),
+                    };
+                    self.push_block(NewBlock { children, ..item });
+                }
+                self.flush_images();
+            }
+            TagEnd::TableCell => {
+                let text = self.inline.take().unwrap_or_default();
+                if let Some(Container::Table { row, .. }) = self.stack.last_mut() {
+                    row.push(text);
+                }
+            }
+            TagEnd::TableHead | TagEnd::TableRow => {
+                if let Some(Container::Table { rows, row }) = self.stack.last_mut() {
+                    rows.push(std::mem::take(row));
+                }
+            }
+            TagEnd::Table => {
+                if let Some(Container::Table { rows, .. }) = self.stack.pop() {
+                    let width = rows.iter().map(Vec::len).max().unwrap_or(1);
+                    // the first rows are created together with the table, the rest appended to it
+                    let rows: Vec<NewBlock> = rows
+                        .into_iter()
+                        .map(|mut cells| {
+                            cells.resize(width, vec![]);
+                            NewBlock::new(json!({
+                                "object": "block",
+                                "type": "table_row",
+                                "table_row": { "cells": cells },
+                            }))
+                        })
+                        .collect();
+                    let mut table = block(
+                        "table",
+                        json!({
+                            "table_width": width,
+                            "has_column_header": true,
+                            "has_row_header": false,
+                            "children": rows
+                                .iter()
+                                .take(APPEND_CHUNK_SIZE)
+                                .map(|row| row.block.clone())
+                                .collect::<Vec<_>>(),
+                        }),
+                    );
+                    table.children = rows;
+                    self.push_block(table);
+                }
+            }
+            TagEnd::Emphasis => self.marks.italic = self.marks.italic.saturating_sub(1),
+            TagEnd::Strong => self.marks.bold = self.marks.bold.saturating_sub(1),
+            TagEnd::Strikethrough => {
+                self.marks.strikethrough = self.marks.strikethrough.saturating_sub(1)
+            }
+            TagEnd::Link => self.marks.link = None,
+            TagEnd::Image => {
+                let Some((url, caption)) = self.image.take() else {
+                    return;
+                };
+                if is_web_url(&url) {
+                    self.images.push(block(
+                        "image",
+                        json!({
+                            "type": "external",
+                            "external": { "url": url },
+                            "caption": rich_text(&caption),
+                        }),
+                    ));
+                } else {
+                    eprintln!("Skipping local image {url}, notion can't take uploads");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // tight list items have their text without a paragraph around it
+    fn finish_item_text(&mut self) {
+        if let Some(Container::Item { text, .. }) = self.stack.last_mut() {
+            if text.is_none() {
+                *text = Some(self.inline.take().unwrap_or_default());
+            }
+        }
+    }
+
+    fn push_text(&mut self, text: &str, code: bool) {
+        let marks = &self.marks;
+        let inline = self.inline.get_or_insert_with(Vec::new);
+        // notion rejects anything but absolute urls, links to other notes stay plain text
+        let link = marks.link.as_deref().filter(|link| is_web_url(link));
+        let annotations = json!({
+            "bold": marks.bold > 0,
+            "italic": marks.italic > 0,
+            "strikethrough": marks.strikethrough > 0,
+            "code": code,
+        });
+        for content in chunks(text) {
+            // the parser splits text at quotes, brackets and line breaks, runs looking the same are joined again
+            if let Some(last) = inline.last_mut() {
+                let previous = last["text"]["content"].as_str().unwrap_or_default();
+                let joined = format!("{previous}{content}");
+                if last["annotations"] == annotations
+                    && last["text"]["link"]["url"].as_str() == link
+                    && joined.chars().count() <= RICH_TEXT_LIMIT
+                {
+                    last["text"]["content"] = json!(joined);
+                    continue;
+                }
+            }
+            let mut text = json!({ "content": content });
+            if let Some(link) = link {
+                text["link"] = json!({ "url": link });
+            }
+            inline.push(json!({
+                "type": "text",
+                "text": text,
+                "annotations": annotations,
+            }));
+        }
+    }
+
+    // items go to the container around their list
+    fn push_block(&mut self, block: NewBlock) {
+        let container = self
+            .stack
+            .iter_mut()
+            .rev()
+            .find(|container| !matches!(container, Container::List { .. }));
+        if let Some(
+            Container::Root(children)
+            | Container::Quote { children, .. }
+            | Container::Item { children, .. },
+        ) = container
+        {
+            children.push(block);
+        }
+    }
+
+    fn flush_images(&mut self) {
+        for image in std::mem::take(&mut self.images) {
+            self.push_block(image);
+        }
+    }
+}
+
+fn block(block_type: &str, content: Value) -> NewBlock {
+    NewBlock::new(json!({ "object": "block", "type": block_type, block_type: content }))
+}
+
+fn rich_text(text: &str) -> Vec<Value> {
+    chunks(text)
+        .into_iter()
+        .map(|content| json!({ "type": "text", "text": { "content": content } }))
+        .collect()
+}
+
+fn is_web_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+fn plain_text(rich_text: &Value) -> String {
+    rich_text
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|text| text["text"]["content"].as_str())
+        .collect()
+}
+
+// split on character boundaries, the limit counts characters
+fn chunks(text: &str) -> Vec<String> {
+    let characters: Vec<char> = text.chars().collect();
+    characters
+        .chunks(RICH_TEXT_LIMIT)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(blocks: &[NewBlock]) -> Vec<&str> {
+        blocks
+            .iter()
+            .map(|block| block.block["type"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn leading_heading_becomes_the_title() {
+        let (title, blocks) = markdown_to_blocks(
+            "---\ntags: [a]\n---\n# Plan\n\nSome **bold** text.\n\n## Steps\n\n---\n\n```rs\nfn main() {}\n```\n",
+        );
+        assert_eq!(title.as_deref(), Some("Plan"));
+        assert_eq!(
+            types(&blocks),
+            vec!["paragraph", "heading_2", "divider", "code"]
+        );
+        let bold = &blocks[0].block["paragraph"]["rich_text"][1];
+        assert_eq!(bold["text"]["content"], "bold");
+        assert_eq!(bold["annotations"]["bold"], true);
+        assert_eq!(blocks[3].block["code"]["language"], "rust");
+    }
+
+    #[test]
+    fn nested_lists_become_children() {
+        let (_, blocks) = markdown_to_blocks("- [x] done\n  1. first\n  2. second\n- plain\n");
+        assert_eq!(types(&blocks), vec!["to_do", "bulleted_list_item"]);
+        assert_eq!(blocks[0].block["to_do"]["checked"], true);
+        assert_eq!(plain_text(&blocks[0].block["to_do"]["rich_text"]), "done");
+        assert_eq!(
+            types(&blocks[0].children),
+            vec!["numbered_list_item", "numbered_list_item"]
+        );
+    }
+
+    #[test]
+    fn tables_carry_their_rows() {
+        let (_, blocks) = markdown_to_blocks("| a | b |\n| - | - |\n| 1 | 2 |\n");
+        let table = &blocks[0].block["table"];
+        assert_eq!(table["table_width"], 2);
+        assert_eq!(table["children"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            plain_text(&table["children"][1]["table_row"]["cells"][1]),
+            "2"
+        );
+    }
+
+    #[test]
+    fn long_tables_append_their_remaining_rows() {
+        let mut markdown = String::from("| n |\n| - |\n");
+        for row in 0..150 {
+            markdown.push_str(&format!("| {row} |\n"));
+        }
+        let (_, blocks) = markdown_to_blocks(&markdown);
+        let table = &blocks[0];
+        assert_eq!(
+            table.block["table"]["children"].as_array().unwrap().len(),
+            APPEND_CHUNK_SIZE
+        );
+        assert_eq!(table.children.len(), 151);
+    }
+
+    #[test]
+    fn only_web_links_are_kept() {
+        let (_, blocks) =
+            markdown_to_blocks("[site](https://example.com) and [note](Other%20note.md)\n");
+        let text = &blocks[0].block["paragraph"]["rich_text"];
+        assert_eq!(text[0]["text"]["link"]["url"], "https://example.com");
+        assert_eq!(text[1]["text"]["content"], " and note");
+        assert!(text[1]["text"].get("link").is_none());
+    }
+
+    #[test]
+    fn paragraphs_with_many_runs_are_split() {
+        let markdown = "**bold** plain ".repeat(80);
+        let (_, blocks) = markdown_to_blocks(&markdown);
+        assert_eq!(types(&blocks), vec!["paragraph", "paragraph"]);
+        let runs = |block: &NewBlock| {
+            block.block["paragraph"]["rich_text"]
+                .as_array()
+                .unwrap()
+                .len()
+        };
+        assert_eq!(runs(&blocks[0]), RICH_TEXT_RUNS_LIMIT);
+        assert_eq!(runs(&blocks[1]), 60);
+    }
+
+    #[test]
+    fn toml_frontmatter_is_skipped() {
+        let (title, blocks) = markdown_to_blocks("+++\ntitle = \"Plan\"\n+++\n\n# Plan\n\nText\n");
+        assert_eq!(title.as_deref(), Some("Plan"));
+        assert_eq!(types(&blocks), vec!["paragraph"]);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    configuration::{DailyNoteDatabase, ExportConfig, FolderIcons, FolderLayout},
+    database_notes, escape, folder_icons,
+    ids::{normalize_id, same_id},
+    wiki,
+    workspace::Workspace,
+};
+use chrono::NaiveDate;
+use notion::models::{
+    properties::{DateOrDateTime, PropertyValue},
+    Page, Parent,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+pub fn page_output_path(config: &ExportConfig, workspace: &Workspace, page: &Page) -> PathBuf {
+    if let Some(date) = daily_note_date(config, page) {
+        return config.output_dir.join(daily_note_path(date));
+    }
+    if let Some(path) = wiki::wiki_page_path(config, workspace, page) {
+        return path;
+    }
+    let title = page.title().unwrap_or_else(|| page.id.to_string());
+    if let (true, Parent::Database { database_id }) = (config.database_as_notes, &page.parent) {
+        let database_title = database_notes::database_title(workspace, &database_id.to_string());
+        return database_notes::row_path(&config.output_dir, &database_title, &title);
+    }
+    match config.folders {
+        FolderLayout::Flat => config.output_dir.join(note_file_name(&title)),
+        FolderLayout::Hierarchy => {
+            hierarchy_dir(config, workspace, page).join(note_file_name(&title))
+        }
+    }
+}
+
+// a folder for every ancestor like in the notion sidebar, starting at the export roots
+// database objects don't tell us their parent so database folders start at the top
+fn hierarchy_dir(config: &ExportConfig, workspace: &Workspace, page: &Page) -> PathBuf {
+    let mut folders = vec![];
+    let mut current = page;
+    let mut visited = HashSet::new();
+    while visited.insert(normalize_id(&current.id.to_string())) {
+        if config
+            .roots
+            .iter()
+            .any(|root| same_id(root, &current.id.to_string()))
+        {
+            break;
+        }
+        match &current.parent {
+            Parent::Page { page_id } => {
+                // parents that weren't shared with the integration have no folder
+                let Some(parent) = workspace.page(&page_id.to_string()) else {
+                    break;
+                };
+                let title = parent.title().unwrap_or_else(|| parent.id.to_string());
+                folders.push(match config.folder_icons {
+                    FolderIcons::Prefix => folder_icons::folder_name(parent, &title),
+                    _ => title,
+                });
+                current = parent;
+            }
+            Parent::Database { database_id } => {
+                folders.push(database_notes::database_title(
+                    workspace,
+                    &database_id.to_string(),
+                ));
+                break;
+            }
+            // pages inside other blocks or at the workspace top level
+            _ => break,
+        }
+    }
+    let mut dir = config.output_dir.clone();
+    for folder in folders.iter().rev() {
+        dir.push(escape::file_name(folder));
+    }
+    dir
+}
+
+pub fn note_file_name(title: &str) -> String {
+    format!("{}.md", escape::file_name(title))
+}
+
+// matches the obsidian daily notes format of YYYY/MM/YYYY-MM-DD
+fn daily_note_path(date: NaiveDate) -> PathBuf {
+    PathBuf::from(date.format("%Y/%m/%Y-%m-%d.md").to_string())
+}
+
+fn daily_note_date(config: &ExportConfig, page: &Page) -> Option<NaiveDate> {
+    let daily_notes = daily_note_database(config, page)?;
+    match &daily_notes.date_property {
+        Some(property) => match page.properties.properties.get(property)? {
+            PropertyValue::Date {
+                date: Some(date), ..
+            } => Some(match &date.start {
+                DateOrDateTime::Date(date) => *date,
+                DateOrDateTime::DateTime(date_time) => date_time.date_naive(),
+            }),
+            _ => None,
+        },
+        None => Some(page.created_time.date_naive()),
+    }
+}
+
+fn daily_note_database<'a>(config: &'a ExportConfig, page: &Page) -> Option<&'a DailyNoteDatabase> {
+    let Parent::Database { database_id } = &page.parent else {
+        return None;
+    };
+    config
+        .daily_note_databases
+        .iter()
+        .find(|database| same_id(&database.database_id, &database_id.to_string()))
+}
+
+// path of `to` relative to the directory `from_dir`, both need to share the same base
+pub fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from_components: Vec<_> = from_dir.components().collect();
+    let to_components: Vec<_> = to.components().collect();
+    let common_length = from_components
+        .iter()
+        .zip(to_components.iter())
+        .take_while(|(from, to)| from == to)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common_length..from_components.len() {
+        relative.push("..");
+    }
+    for component in &to_components[common_length..] {
+        relative.push(component);
+    }
+    relative
+}
@@ -0,0 +1,178 @@
+use crate::{
+    configuration::{AppConfig, BannedTerm, LintConfig},
+    frontmatter::frontmatter_len,
+    manifest::ExportManifest,
+    stats::job_output_dirs,
+};
+use regex::Regex;
+use std::path::Path;
+
+// a finding in a note, lines count from 1 and include the frontmatter
+#[derive(Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub line: usize,
+    pub message: String,
+}
+
+pub struct Linter {
+    banned: Vec<(Regex, BannedTerm)>,
+    terms: Vec<(Regex, String)>,
+    // code spans and urls are blanked out before matching, `github.com` isn't a misspelled name
+    ignored: Regex,
+}
+
+impl Linter {
+    pub fn new(config: &LintConfig) -> anyhow::Result<Self> {
+        let pattern = |word: &str| Regex::new(&format!(r"(?i)\b{}\b", regex::escape(word)));
+        Ok(Self {
+            banned: config
+                .banned
+                .iter()
+                .map(|banned| Ok((pattern(&banned.word)?, banned.clone())))
+                .collect::<anyhow::Result<_>>()?,
+            terms: config
+                .terms
+                .iter()
+                .map(|term| Ok((pattern(term)?, term.clone())))
+                .collect::<anyhow::Result<_>>()?,
+            ignored: Regex::new(r"`[^`]*`|\]\([^)]*\)|https?://\S+|\[\[[^\]]*\]\]")?,
+        })
+    }
+
+    // frontmatter and fenced code blocks aren't prose
+    pub fn check(&self, note: &str) -> Vec<Violation> {
+        let mut violations = vec![];
+        let frontmatter_lines = note[..frontmatter_len(note)].lines().count();
+        let mut fence: Option<&str> = None;
+        for (index, line) in note.lines().enumerate().skip(frontmatter_lines) {
+            let trimmed = line.trim_start();
+            match fence {
+                Some(open) if trimmed.starts_with(open) => {
+                    fence = None;
+                    continue;
+                }
+                Some(_) => continue,
+                None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                    fence = Some(&trimmed[..3]);
+                    continue;
+                }
+                None => {}
+            }
+            let prose = self
+                .ignored
+                .replace_all(line, |found: &regex::Captures| " ".repeat(found[0].len()));
+            for (pattern, banned) in &self.banned {
+                for found in pattern.find_iter(&prose) {
+                    let message = match &banned.suggestion {
+                        Some(suggestion) => {
+                            format!("`{}` is banned, use `{suggestion}`", found.as_str())
+                        }
+                        None => format!("`{}` is banned", found.as_str()),
+                    };
+                    violations.push(Violation {
+                        line: index + 1,
+                        message,
+                    });
+                }
+            }
+            for (pattern, term) in &self.terms {
+                for found in pattern.find_iter(&prose) {
+                    if found.as_str() != term {
+                        violations.push(Violation {
+                            line: index + 1,
+                            message: format!("`{}` should be written `{term}`", found.as_str()),
+                        });
+                    }
+                }
+            }
+        }
+        violations
+    }
+}
+
+// every note in the manifest, not only the ones this run wrote, returns how many violations there were
+pub fn lint_output(
+    linter: &Linter,
+    output_dir: &Path,
+    manifest: &ExportManifest,
+) -> anyhow::Result<usize> {
+    let mut entries: Vec<_> = manifest.pages.values().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut count = 0;
+    for entry in entries {
+        // parts are checked one by one so line numbers point into the right file
+        for note_path in entry.note_paths(output_dir) {
+            let Ok(note) = std::fs::read_to_string(&note_path) else {
+                continue;
+            };
+            for violation in linter.check(&note) {
+                println!(
+                    "{}:{}: {} (page '{}')",
+                    note_path.display(),
+                    violation.line,
+                    violation.message,
+                    entry.title
+                );
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+// the notes of the last export of every job with a lint section
+pub fn lint_jobs(config: &AppConfig) -> anyhow::Result<usize> {
+    let mut count = 0;
+    for job in config.export_jobs() {
+        let Some(lint) = &job.export.lint else {
+            continue;
+        };
+        let linter = Linter::new(lint)?;
+        for output_dir in job_output_dirs(config, &job) {
+            if let Some(manifest) = ExportManifest::load(&output_dir)? {
+                count += lint_output(&linter, &output_dir, &manifest)?;
+            }
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linter() -> Linter {
+        Linter::new(&LintConfig {
+            banned: vec![BannedTerm {
+                word: String::from("simply"),
+                suggestion: None,
+            }],
+            terms: vec![String::from("GitHub")],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_banned_words_and_wrong_casing_with_lines() {
+        let note = "---\ntitle: github\n---\n# Setup\nSimply push to github.\n";
+        assert_eq!(
+            linter().check(note),
+            vec![
+                Violation {
+                    line: 5,
+                    message: String::from("`Simply` is banned"),
+                },
+                Violation {
+                    line: 5,
+                    message: String::from("`github` should be written `GitHub`"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn code_and_urls_are_not_prose() {
+        let note = "Clone `github` from [GitHub](https://github.com/x)\n```\nsimply github\n```\n";
+        assert!(linter().check(note).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use crate::state::state_dir;
+use anyhow::Context;
+use fs2::FileExt;
+use std::{fs::File, io::Write, path::Path, time::Duration};
+
+const LOCK_FILE_NAME: &str = "lock";
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// exclusive lock on an output directory, released when dropped
+pub struct ExportLock {
+    _file: File,
+}
+
+impl ExportLock {
+    pub async fn acquire(output_dir: &Path, wait: bool) -> anyhow::Result<Self> {
+        let lock_path = state_dir(output_dir).join(LOCK_FILE_NAME);
+        Self::acquire_at(&lock_path, output_dir, wait).await
+    }
+
+    // `lock_path` guards `output_dir`, for locks that have to outlive the directory
+    pub async fn acquire_at(
+        lock_path: &Path,
+        output_dir: &Path,
+        wait: bool,
+    ) -> anyhow::Result<Self> {
+        if let Some(lock_dir) = lock_path.parent() {
+            std::fs::create_dir_all(lock_dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path)
+            .with_context(|| format!("failed to open lock file {}", lock_path.display()))?;
+
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => break,
+                Err(error) if error.kind() == fs2::lock_contended_error().kind() => {
+                    if !wait {
+                        let holder = std::fs::read_to_string(lock_path).unwrap_or_default();
+                        anyhow::bail!(
+                            "another export (pid {}) is already writing to {}, use --wait-for-lock to wait for it",
+                            holder.trim(),
+                            output_dir.display()
+                        );
+                    }
+                    println!(
+                        "Waiting for another export to release {}",
+                        output_dir.display()
+                    );
+                    tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        // record who holds the lock to make the error above more useful
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(Self { _file: file })
+    }
+}
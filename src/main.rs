@@ -1,23 +1,78 @@
+mod alt_text;
+mod api;
+mod append;
+mod assets;
+mod atomic;
+mod blocks;
+mod bulk_update;
+mod callouts;
+mod capabilities;
+mod changes;
+mod clean_attachments;
+mod columns;
+mod comments;
 mod configuration;
-
-use clap::Parser;
+mod copy;
+mod crawl;
+mod daemon;
+mod database_export;
+mod database_notes;
+mod database_views;
+mod dry_run;
+mod duplicates;
+mod equations;
+mod escape;
+mod exit_codes;
+mod export;
+mod fingerprint;
+mod folder_icons;
+mod formats;
+mod frontmatter;
+mod git;
+mod glossary;
+mod hugo;
+mod i18n;
+mod ids;
+mod import;
+mod layout;
+mod lint;
+mod lock;
+mod manifest;
+mod markdown;
+mod notifications;
+mod presets;
+mod progress;
+mod properties;
+mod recording;
+mod redaction;
+mod remote_config;
+mod report;
+mod rich_text;
+mod serve;
+mod slug;
+mod snippets;
+mod split;
+mod state;
+mod stats;
+mod status_page;
+mod tasks;
+mod templates;
+mod titles;
+mod validate;
+mod watch;
+mod wiki;
+mod workspace;
+mod writer;
+
+use anyhow::bail;
+use clap::{CommandFactory, Parser, Subcommand};
 use configuration::AppConfig;
 use dialoguer::{theme::ColorfulTheme, Password};
-use notion::{
-    ids::{BlockId, DatabaseId, PageId},
-    models::{
-        block::{Block, FileObject},
-        paging::Pageable,
-        search::{NotionSearch, SearchRequest},
-        text::RichText,
-    },
-    NotionApi,
-};
-use std::{collections::HashMap, str::FromStr};
-use tokio::io::AsyncWriteExt;
+use exit_codes::FailureClass;
+use std::{path::PathBuf, process::ExitCode, time::Duration};
 
 #[derive(Parser)]
-#[command()]
+#[command(version, about)]
 struct Cli {
     // #[arg(short, long)]
     // element_id: Option<String>,
@@ -26,463 +81,459 @@ struct Cli {
     // text: Option<String>,
     #[arg(short, long)]
     save_token: bool,
-}
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    /// Wait for other exports into the same output directory to finish instead of failing
+    #[arg(long)]
+    wait_for_lock: bool,
 
-    let term_theme = ColorfulTheme::default();
+    /// Seconds a single page may take before it is written as far as it got
+    #[arg(long)]
+    page_timeout: Option<u64>,
 
-    if cli.save_token {
-        let api_key: String = Password::with_theme(&term_theme)
-            .with_prompt("Notion api key:")
-            .interact()?;
-        let config = AppConfig::new(api_key);
-        config.save_user_config()?;
-        return Ok(());
-    }
+    /// Fail pages containing blocks that can't be rendered instead of writing placeholders
+    #[arg(long)]
+    strict: bool,
 
-    let config = configuration::AppConfig::load_user_config()?;
-    let notion_api = NotionApi::new(config.notion_api_key)?;
-
-    // let search_query = NotionSearch::Filter {
-    //     property: notion::models::search::FilterProperty::Object,
-    //     value: notion::models::search::FilterValue::Page,
-    // };
-
-    let search_query = NotionSearch::Query(String::from(""));
-
-    let mut search_result = notion_api.search(search_query).await?;
-
-    loop {
-        for object in search_result.results {
-            match object {
-                notion::models::Object::Block { block: _ } => {
-                    println!("Block");
-                }
-                notion::models::Object::Database { database } => {
-                    println!(
-                        "Database: {} {}",
-                        database.title_plain_text(),
-                        notion_database_id_to_url(&database.id)
-                    );
-                }
-                notion::models::Object::Page { page } => {
-                    let title = page.title().unwrap();
-                    println!("Page: {} {}", title, notion_page_id_to_url(&page.id));
-                    if let Err(error) = process_page(&notion_api, page.id).await {
-                        eprintln!("Failed for {title} with error {error:?}");
-                    }
-                }
-                notion::models::Object::List { list: _ } => {
-                    println!("List");
-                }
-                notion::models::Object::User { user: _ } => {
-                    println!("User");
-                }
-                notion::models::Object::Error { error: _ } => {
-                    println!("Error");
-                }
-            }
-        }
-        if let Some(cursor) = search_result.next_cursor {
-            let search_request = SearchRequest::default().start_from(Some(cursor));
-            search_result = notion_api.search(search_request).await?;
-        } else {
-            break;
-        }
-    }
+    /// Pages exported at the same time, requests still stay within the rate limit of notion
+    #[arg(long, default_value_t = 4)]
+    jobs: usize,
 
-    Ok(())
-}
+    /// Render every page, also the ones nobody edited since the last run
+    #[arg(long)]
+    full: bool,
 
-async fn process_page(notion_client: &NotionApi, page_id: PageId) -> anyhow::Result<()> {
-    let page = notion_client.get_page(page_id.clone()).await?;
-    let page_title = page.title().expect("failed to get page title");
+    /// Render into a copy of the output directory and only swap it in when every page succeeded
+    #[arg(long)]
+    atomic: bool,
 
-    let block_id: BlockId = page_id.clone().into();
-    let mut children = notion_client.get_block_children(block_id.clone()).await?;
+    /// List the notes an export would create, overwrite, move or delete without writing anything
+    #[arg(long)]
+    dry_run: bool,
 
-    let mut page_buffer = String::new();
+    /// No progress bar and no line per exported page, errors are still printed
+    #[arg(short, long)]
+    quiet: bool,
 
-    let link_to_page = external_link(Some(&page_title), &notion_page_id_to_url(&page_id));
+    /// Save every api response into this directory for replaying later
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
 
-    // add heading link as first line
-    page_buffer.push_str("# ");
-    page_buffer.push_str(&link_to_page);
-    page_buffer.push_str("\n\n");
+    /// Serve api responses recorded with --record instead of calling notion
+    #[arg(long, value_name = "DIR")]
+    replay: Option<PathBuf>,
 
-    let _page_id_cache = PageIdCache::new();
+    /// Render from the raw archive without touching the network, nothing is written to notion
+    #[arg(long, conflicts_with = "record")]
+    offline: bool,
 
-    loop {
-        for child in children.results {
-            block_to_markdown(child, &mut page_buffer)?;
-        }
-        if let Some(cursor) = children.next_cursor {
-            children = notion_client
-                .get_block_children_with_cursor(block_id.clone(), cursor)
-                .await?;
-        } else {
-            break;
-        }
-    }
+    /// Export database rows into folders with an index note and follow inline databases
+    #[arg(long)]
+    database_as_notes: bool,
+
+    /// Also export subpages and inline databases found on exported pages
+    #[arg(long)]
+    recursive: bool,
+
+    /// Refuse every request that would change something in notion, overrides the config file
+    #[arg(long)]
+    read_only: bool,
+
+    /// Export defaults for obsidian, hugo, mdbook or plain, settings in the config file win
+    #[arg(long, visible_alias = "profile")]
+    preset: Option<presets::Preset>,
 
-    let page_title = page_title.replace('/', "-");
-    let mut file = tokio::fs::File::create(format!("output/{page_title}.md")).await?;
-    file.write_all(page_buffer.as_bytes()).await?;
+    /// Comma separated output formats (markdown, html, json), overrides the config file
+    #[arg(long, value_delimiter = ',', value_name = "FORMATS")]
+    format: Vec<configuration::OutputFormat>,
 
-    Ok(())
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[allow(clippy::print_with_newline)]
-#[allow(clippy::write_with_newline)]
-fn block_to_markdown(block: Block, writer_buffer: &mut dyn std::fmt::Write) -> anyhow::Result<()> {
-    match block {
-        Block::Paragraph {
-            common: _,
-            paragraph,
-        } => {
-            write!(
-                writer_buffer,
-                "{}\n",
-                render_rich_text(&paragraph.rich_text)
-            )?;
-            for child in paragraph.children.unwrap_or_default() {
-                block_to_markdown(child, writer_buffer)?;
-            }
-        }
-        Block::Heading1 {
-            common: _,
-            heading_1,
-        } => {
-            write!(
-                writer_buffer,
-                "\n# {}\n\n",
-                render_rich_text(&heading_1.rich_text)
-            )?;
-        }
-        Block::Heading2 {
-            common: _,
-            heading_2,
-        } => {
-            write!(
-                writer_buffer,
-                "\n## {}\n\n",
-                render_rich_text(&heading_2.rich_text)
-            )?;
-        }
-        Block::Heading3 {
-            common: _,
-            heading_3,
-        } => {
-            write!(
-                writer_buffer,
-                "\n### {}\n\n",
-                render_rich_text(&heading_3.rich_text)
-            )?;
-        }
-        Block::Callout { common: _, callout } => {
-            // TODO: Add support for callout icon
-            write!(
-                writer_buffer,
-                "> [!info]\n{}\n",
-                render_rich_text(&callout.rich_text)
-                    .lines()
-                    .map(|line| format!("> {}\n", line))
-                    .collect::<String>()
-            )?;
-        }
-        Block::Quote { common: _, quote } => {
-            write!(writer_buffer, "> {}\n", render_rich_text(&quote.rich_text))?;
-            write!(writer_buffer, "START QUOTE CHILDREN:\n")?;
-            for child in quote.children.unwrap_or_default() {
-                block_to_markdown(child, writer_buffer)?;
-            }
-            write!(writer_buffer, "END QUOTE CHILDREN:\n")?;
-        }
-        Block::BulletedListItem {
-            common: _,
-            bulleted_list_item,
-        } => {
-            write!(
-                writer_buffer,
-                "* {}\n",
-                render_rich_text(&bulleted_list_item.rich_text)
-            )?;
-            write!(writer_buffer, "START BULLET CHILDREN:\n")?;
-            for child in bulleted_list_item.children.unwrap_or_default() {
-                block_to_markdown(child, writer_buffer)?;
-            }
-            write!(writer_buffer, "END BULLET CHILDREN:\n")?;
-        }
-        Block::NumberedListItem {
-            common: _,
-            numbered_list_item,
-        } => {
-            write!(
-                writer_buffer,
-                "1. {}\n",
-                render_rich_text(&numbered_list_item.rich_text)
-            )?;
-            write!(writer_buffer, "START NUMBERED CHILDREN:\n")?;
-            for child in numbered_list_item.children.unwrap_or_default() {
-                block_to_markdown(child, writer_buffer)?;
-            }
-            write!(writer_buffer, "END NUMBERED CHILDREN:\n")?;
-        }
-        Block::Toggle { common: _, toggle } => {
-            let summary = render_rich_text(&toggle.rich_text);
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Check the config file, the ids it references and the output directories
+    Validate,
+}
 
-            write!(writer_buffer, "<details> <summary>{summary}</summary> \n",)?;
+#[derive(Subcommand)]
+enum ReportCommand {
+    /// Pages, words and attachment size of the last runs and how they changed
+    History {
+        /// Runs shown per job
+        #[arg(long, default_value_t = 20)]
+        last: usize,
+    },
+}
 
-            for child in toggle.children.unwrap_or_default() {
-                block_to_markdown(child, writer_buffer)?;
-            }
+#[derive(Subcommand)]
+enum Command {
+    /// Export once, this is the default
+    Export {
+        /// Only export this page, given by id or notion url
+        #[arg(long, value_name = "ID_OR_URL")]
+        page: Option<String>,
+    },
+    /// Work with the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Keep exporting on an interval and report what changed in every cycle
+    Watch {
+        /// Seconds between cycles, overrides the config file
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Watch mode with a web page listing recent runs and buttons to sync right away
+    Serve {
+        /// Seconds between cycles, overrides the config file
+        #[arg(long)]
+        interval: Option<u64>,
+        /// Address the status page listens on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+    },
+    /// Move attachments no exported note references anymore into the quarantine directory
+    CleanAttachments {
+        /// Delete the files instead of moving them
+        #[arg(long)]
+        delete: bool,
+        /// Only list the files
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Look at the reports of earlier runs
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Summarize the last export and rank pages by size on disk and the configured metrics
+    Stats {
+        /// Pages listed per metric and by size on disk
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Check the notes of the last export against the lint section of the config
+    Lint,
+    /// List pages whose notes are nearly the same, from the notes of the last export
+    Duplicates {
+        /// Share of word sequences two notes have in common to count as duplicates
+        #[arg(long, default_value_t = 0.8)]
+        threshold: f64,
+        /// Notes with fewer words are left out, short notes look alike too easily
+        #[arg(long, default_value_t = 50)]
+        min_words: usize,
+    },
+    /// Write open rows of the configured task databases that are assigned to you into one agenda
+    AssignedToMe {
+        /// File to write the agenda to, overrides the config file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Create a page by copying a configured template page
+    New {
+        /// Name of the template in the config file
+        #[arg(long)]
+        template: String,
+        #[arg(long)]
+        title: String,
+    },
+    /// Create a page from a markdown file with headings, lists, code, images and tables
+    Import {
+        file: PathBuf,
+        /// Page the new page is created under, given by id or notion url
+        #[arg(long, value_name = "ID_OR_URL")]
+        parent: String,
+        /// Title of the new page, a leading `# ` heading or the file name otherwise
+        #[arg(long)]
+        title: Option<String>,
+        /// Name of the source that can see the parent page
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Copy a page with its blocks below another page, also into the workspace of another source
+    Copy {
+        /// Page to copy, given by id or notion url
+        #[arg(value_name = "ID_OR_URL")]
+        page: String,
+        /// Page the copy is created under, given by id or notion url
+        #[arg(long, value_name = "ID_OR_URL")]
+        to_parent: String,
+        /// Name of the source that can see the page
+        #[arg(long)]
+        source: Option<String>,
+        /// Name of the source that can write below the parent, the reading source when unset
+        #[arg(long)]
+        to_source: Option<String>,
+        /// Link notion hosted files by their signed url, which expires after about an hour
+        #[arg(long)]
+        link_files: bool,
+    },
+    /// Update a property on every database row matching a filter
+    SetProperty {
+        #[arg(long)]
+        database: String,
+        /// Rows to update, for example 'Status = Inbox'
+        #[arg(long = "where")]
+        filter: String,
+        /// New value, for example 'Status = Triaged'
+        #[arg(long)]
+        set: String,
+        /// Only print what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Name of the source that can see the database
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Move pages to the trash, given by id or as database rows matching a filter
+    Archive {
+        page_ids: Vec<String>,
+        #[arg(long)]
+        database: Option<String>,
+        /// Rows to archive, for example 'Status = Done'
+        #[arg(long = "where")]
+        filter: Option<String>,
+        /// Only print what would be archived
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Print the completion script for a shell
+    Completions { shell: clap_complete::Shell },
+    /// Print the man page
+    Man,
+}
 
-            write!(writer_buffer, "</details>\n\n",)?;
-        }
-        Block::ToDo { common: _, to_do } => {
-            let checked = to_do.checked;
-            let checked = if checked { "x" } else { "" };
-            write!(
-                writer_buffer,
-                "- [{checked}] {}\n",
-                render_rich_text(&to_do.rich_text)
-            )?;
-
-            write!(writer_buffer, "START TODO CHILDREN:\n")?;
-            for child in to_do.children.unwrap_or_default() {
-                block_to_markdown(child, writer_buffer)?;
-            }
-            write!(writer_buffer, "END TODO CHILDREN:\n")?;
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("Error: {error:?}");
+            ExitCode::from(FailureClass::classify(&error).exit_code())
         }
-        Block::Code { common: _, code } => {
-            let content = render_rich_text(&code.rich_text);
-            // this works
-            let language = format!("{:?}", code.language).to_lowercase();
-            // todo caption
+    }
+}
 
-            write!(writer_buffer, "\n```{language}\n{content}\n```\n\n",)?;
-        }
-        Block::ChildPage { common, child_page } => {
-            // I think this is right?
-            let block_id = common.id;
-            let _page_id = PageId::from_str(&block_id.to_string())?;
-            // wait is this needed?
-            // let _page_title = page_id_cache.get_page_title(&page_id)?;
-
-            write!(writer_buffer, "Child page: [[{}]]\n", child_page.title)?;
-        }
-        Block::ChildDatabase {
-            common: _,
-            child_database,
-        } => {
-            // TODO same as above?
-            write!(writer_buffer, "Child database: {}\n", child_database.title)?;
-        }
-        Block::Image { common: _, image } => {
-            write!(writer_buffer, "![[{}]]\n", render_file_object(image))?;
-        }
-        Block::Video { common: _, video } => {
-            write!(writer_buffer, "![[{}]]\n", render_file_object(video))?;
-        }
-        Block::File {
-            common: _,
-            file,
-            caption: _,
-        } => {
-            write!(writer_buffer, "![[{}]]\n", render_file_object(file))?;
-        }
-        Block::Pdf { common: _, pdf } => {
-            write!(writer_buffer, "![[{}]]\n", render_file_object(pdf))?;
-        }
+async fn run() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
 
-        Block::Divider { common: _ } => {
-            write!(writer_buffer, "----\n")?;
-        }
+    let term_theme = ColorfulTheme::default();
 
-        Block::Embed { common: _, embed } => {
-            write!(writer_buffer, "![[{}]]\n", embed.url)?;
-        }
+    if cli.save_token {
+        let api_key: String = Password::with_theme(&term_theme)
+            .with_prompt("Notion api key:")
+            .interact()?;
+        let config = AppConfig::new(api_key);
+        config.save_user_config()?;
+        return Ok(ExitCode::SUCCESS);
+    }
 
-        Block::Bookmark {
-            common: _,
-            bookmark,
-        } => {
-            let caption = render_rich_text(&bookmark.caption);
-            write!(
-                writer_buffer,
-                "caption {} \n![[{}]]\n",
-                caption, bookmark.url
-            )?;
+    // generated from the cli definition, no config needed
+    match &cli.command {
+        Some(Command::Completions { shell }) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_owned();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+            return Ok(ExitCode::SUCCESS);
         }
-        Block::Equation {
-            common: _,
-            equation,
-        } => {
-            write!(writer_buffer, "Equation {}\n", equation.expression)?;
+        Some(Command::Man) => {
+            clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?;
+            return Ok(ExitCode::SUCCESS);
         }
+        _ => {}
+    }
 
-        Block::TableOfContents {
-            common: _,
-            table_of_contents: _,
+    // set-property, archive and clean-attachments have a --dry-run of their own
+    if cli.dry_run && !matches!(cli.command, None | Some(Command::Export { .. })) {
+        bail!("--dry-run only applies to export");
+    }
+
+    // validation has to work on configs that don't load
+    if let Some(Command::Config {
+        command: ConfigCommand::Validate,
+    }) = &cli.command
+    {
+        return Ok(if validate::validate_config().await? {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::from(exit_codes::EXIT_FAILURE)
+        });
+    }
+
+    let recording = match (cli.record, cli.replay) {
+        (Some(dir), _) => Some(recording::Recording::Record(dir)),
+        (None, Some(dir)) => Some(recording::Recording::Replay(dir)),
+        (None, None) => None,
+    };
+    let config_loader = daemon::ConfigLoader {
+        preset: cli.preset,
+        read_only: cli.read_only,
+        formats: cli.format,
+        recording,
+        offline: cli.offline,
+        dry_run: cli.dry_run,
+    };
+    let config = config_loader.load_config()?;
+    let sources = config_loader.connect_sources(&config)?;
+    let run_options = export::RunOptions {
+        wait_for_lock: cli.wait_for_lock,
+        page_timeout: cli.page_timeout.map(Duration::from_secs),
+        strict: cli.strict,
+        database_as_notes: cli.database_as_notes,
+        recursive: cli.recursive,
+        partial: false,
+        full: cli.full,
+        concurrency: cli.jobs,
+        quiet: cli.quiet,
+        offline: cli.offline,
+        atomic: cli.atomic,
+    };
+
+    match cli.command.unwrap_or(Command::Export { page: None }) {
+        Command::Export { page: None } if cli.dry_run => {
+            // the incremental search would save its snapshot
+            let config = AppConfig {
+                incremental_search: false,
+                ..config
+            };
+            let workspaces = export::search_sources(&sources, &config).await?;
+            dry_run::dry_run(&sources, &workspaces, &config, run_options).await?;
+        }
+        Command::Export { .. } if cli.dry_run => bail!("--dry-run doesn't work with --page"),
+        Command::Export { page } => {
+            let changes = match page {
+                Some(page) => export::export_page(&sources, &config, &page, run_options).await?,
+                None => export::run_export(&sources, &config, run_options).await?,
+            };
+            return Ok(exit_codes::export_exit_code(&changes, cli.strict));
+        }
+        // handled before the config is loaded
+        Command::Config { .. } | Command::Completions { .. } | Command::Man => {}
+        Command::Watch { interval } => {
+            watch::watch(&config_loader, sources, config, interval, run_options).await?;
+        }
+        Command::Serve { interval, listen } => {
+            serve::serve(
+                &config_loader,
+                sources,
+                config,
+                interval,
+                listen,
+                run_options,
+            )
+            .await?;
+        }
+        Command::CleanAttachments { delete, dry_run } => {
+            let options = clean_attachments::CleanOptions {
+                delete,
+                dry_run,
+                wait_for_lock: run_options.wait_for_lock,
+            };
+            clean_attachments::clean_attachments(&config, options).await?;
+        }
+        Command::Report {
+            command: ReportCommand::History { last },
         } => {
-            write!(writer_buffer, "\nTABLE OF CONTENTS\n")?;
-        }
-        Block::Breadcrumb { common: _ } => {
-            write!(writer_buffer, "\nBREADCRUMB\n")?;
+            report::print_history(&config, last)?;
         }
-        Block::ColumnList {
-            common: _,
-            column_list,
-        } => {
-            for child in column_list.children {
-                write!(writer_buffer, "COLUMN LIST\n\n")?;
-                block_to_markdown(child, writer_buffer)?;
-                write!(writer_buffer, "COLUMN LIST END\n\n")?;
-            }
+        Command::Stats { top } => {
+            stats::print_stats(&config, top)?;
         }
-        Block::Column { common: _, column } => {
-            for child in column.children {
-                write!(writer_buffer, "COLUMN LIST\n\n")?;
-                block_to_markdown(child, writer_buffer)?;
-                write!(writer_buffer, "COLUMN LIST END\n\n")?;
+        Command::Lint => {
+            let violations = lint::lint_jobs(&config)?;
+            if violations > 0 {
+                println!("{violations} lint violations");
+                return Ok(ExitCode::from(exit_codes::EXIT_FAILURE));
             }
         }
-        Block::LinkPreview {
-            common: _,
-            link_preview,
+        Command::Duplicates {
+            threshold,
+            min_words,
         } => {
-            write!(writer_buffer, "![[{}]]\n", link_preview.url)?;
+            duplicates::print_duplicates(&config, threshold, min_words)?;
         }
-        Block::Template {
-            common: _,
-            template,
-        } => {
-            let content = render_rich_text(&template.rich_text);
-            write!(writer_buffer, "\nTEMPLATE {}\n", content)?;
+        Command::AssignedToMe { output } => {
+            tasks::export_assigned_to_me(&sources, &config.tasks, output.as_deref()).await?;
         }
-        Block::LinkToPage {
-            common: _,
-            link_to_page: _,
-        } => {
-            write!(writer_buffer, "\nLINK TO PAGE\n")?;
+        Command::New { template, title } => {
+            templates::create_from_template(&sources, &config.templates, &template, &title).await?;
         }
-        Block::Table {
-            common: _,
-            table: _,
+        Command::Import {
+            file,
+            parent,
+            title,
+            source,
         } => {
-            write!(writer_buffer, "\nTABLE\n")?;
-        }
-        Block::SyncedBlock {
-            common: _,
-            synced_block: _,
+            import::import_markdown(
+                &sources,
+                &file,
+                &parent,
+                title.as_deref(),
+                source.as_deref(),
+            )
+            .await?;
+        }
+        Command::Copy {
+            page,
+            to_parent,
+            source,
+            to_source,
+            link_files,
         } => {
-            write!(writer_buffer, "\nSYNCED BLOCK\n")?;
-        }
-        Block::TableRow {
-            common: _,
-            table_row: _,
+            let to_source = to_source.as_deref().or(source.as_deref());
+            copy::copy_page(
+                &sources,
+                &page,
+                &to_parent,
+                source.as_deref(),
+                to_source,
+                link_files,
+            )
+            .await?;
+        }
+        Command::SetProperty {
+            database,
+            filter,
+            set,
+            dry_run,
+            source,
         } => {
-            write!(writer_buffer, "\nTABLE ROW\n")?;
-        }
-        Block::Unsupported { common: _ } => {
-            write!(writer_buffer, "\nUNSUPPORTED\n")?;
-        }
-        Block::Unknown => {
-            write!(writer_buffer, "\nUNKNOWN\n")?;
-        }
-    }
-    Ok(())
-}
-
-fn render_file_object(file_object: FileObject) -> String {
-    match file_object {
-        FileObject::File { file } => {
-            // url is private?
-            file.url
-        }
-        FileObject::External { external } => external.url,
-    }
-}
-
-fn render_rich_text(rich_text: &[RichText]) -> String {
-    rich_text
-        .iter()
-        .map(|text| text.plain_text())
-        .collect::<String>()
-}
-
-fn notion_page_id_to_url(id: &PageId) -> String {
-    let id_stripped = id.to_string().replace('-', "");
-    format!("http://notion.so/{}", id_stripped)
-}
-
-fn notion_database_id_to_url(id: &DatabaseId) -> String {
-    let id_stripped = id.to_string().replace('-', "");
-    format!("http://notion.so/{}", id_stripped)
-}
-
-#[allow(dead_code)]
-struct PageIdCache {
-    page_to_title: HashMap<PageId, String>,
-}
-
-impl PageIdCache {
-    fn new() -> Self {
-        Self {
-            page_to_title: HashMap::new(),
-        }
-    }
-
-    #[allow(dead_code)]
-    async fn get_page_title(&mut self, id: &PageId, client: &NotionApi) -> anyhow::Result<String> {
-        if let Some(title) = self.page_to_title.get(id) {
-            Ok(title.clone())
-        } else {
-            let page = client.get_page(id.clone()).await?;
-            let title = page.title().unwrap_or("UNKNOWN_TITLE".to_owned());
-            self.page_to_title.insert(id.clone(), title.clone());
-            Ok(title)
+            let update = bulk_update::BulkUpdate {
+                database_id: &database,
+                filter: &filter,
+                assignment: &set,
+                dry_run,
+                source: source.as_deref(),
+            };
+            bulk_update::set_property(&sources, update).await?;
+        }
+        Command::Archive {
+            page_ids,
+            database,
+            filter,
+            dry_run,
+            yes,
+            source,
+        } => {
+            let request = bulk_update::ArchiveRequest {
+                page_ids: &page_ids,
+                database_id: database.as_deref(),
+                filter: filter.as_deref(),
+                dry_run,
+                assume_yes: yes,
+                source: source.as_deref(),
+                export_config: &config.export,
+            };
+            bulk_update::archive(&sources, request).await?;
         }
     }
-}
 
-#[allow(dead_code)]
-fn internal_embed(text: Option<&str>, link: &str) -> String {
-    if let Some(text) = text {
-        format!("![[{}|{}]]", link, text)
-    } else {
-        format!("![[{}]]", link)
-    }
-}
-
-#[allow(dead_code)]
-fn external_embed(text: Option<&str>, link: &str) -> String {
-    // should I care about url encoding here?
-    if let Some(text) = text {
-        format!("![{}]({})", text, link)
-    } else {
-        format!("![]({})", link)
-    }
-}
-
-#[allow(dead_code)]
-fn internal_link(text: Option<&str>, link: &str) -> String {
-    if let Some(text) = text {
-        format!("[[{}|{}]]", link, text)
-    } else {
-        format!("[[{}]]", link)
-    }
-}
-
-#[allow(dead_code)]
-fn external_link(text: Option<&str>, link: &str) -> String {
-    // should I care about url encoding here?
-    if let Some(text) = text {
-        format!("[{}]({})", text, link)
-    } else {
-        format!("<{}>", link)
-    }
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,147 @@
+use crate::ids::normalize_id;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use notion::models::Page;
+use serde::{Deserialize, Serialize};
+use serde_json::Number;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+const MANIFEST_FILE_NAME: &str = "export-manifest.json";
+// part of the contract with downstream scripts, bump on incompatible changes
+const MANIFEST_VERSION: u64 = 1;
+
+// written on every run so publishers and indexers don't have to guess what the export contains
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportManifest {
+    pub version: u64,
+    pub generated_at: DateTime<Utc>,
+    // keyed by normalized page id, sorted so the file diffs nicely
+    pub pages: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestEntry {
+    pub title: String,
+    // relative to the output directory with forward slashes
+    pub path: String,
+    // of the whole note, the parts of a split note included
+    pub sha256: String,
+    // whitespace separated words of the note, zero in manifests written before it was tracked
+    #[serde(default)]
+    pub words: usize,
+    pub created_time: DateTime<Utc>,
+    pub last_edited_time: DateTime<Utc>,
+    // configured number properties keyed like in the frontmatter
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, Number>,
+    // files a split note continues in, relative like `path`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<String>,
+}
+
+impl Default for ExportManifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            generated_at: Utc::now(),
+            pages: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn manifest_path(output_dir: &Path) -> PathBuf {
+    output_dir.join(MANIFEST_FILE_NAME)
+}
+
+// relative to the output directory with forward slashes
+pub fn relative_path(output_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(output_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+impl ManifestEntry {
+    // the note followed by its parts, everything that was rendered for the page
+    pub fn note_paths(&self, output_dir: &Path) -> Vec<PathBuf> {
+        std::iter::once(&self.path)
+            .chain(&self.parts)
+            .map(|path| output_dir.join(path))
+            .collect()
+    }
+
+    // a split note is read as a whole, parts deleted by hand are left out
+    pub fn read_note(&self, output_dir: &Path) -> std::io::Result<String> {
+        let mut note = std::fs::read_to_string(output_dir.join(&self.path))?;
+        for part in &self.parts {
+            if let Ok(part) = std::fs::read_to_string(output_dir.join(part)) {
+                note.push_str(&part);
+            }
+        }
+        Ok(note)
+    }
+}
+
+impl ExportManifest {
+    pub fn load(output_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = manifest_path(output_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let manifest = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse manifest {}", path.display()))?;
+        Ok(Some(manifest))
+    }
+
+    // written to a temporary file first so consumers never read half a manifest
+    pub fn save(&self, output_dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(output_dir)?;
+        let path = manifest_path(output_dir);
+        let temporary_path = path.with_extension("json.tmp");
+        std::fs::write(&temporary_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temporary_path, &path)?;
+        Ok(())
+    }
+
+    pub fn insert(
+        &mut self,
+        page: &Page,
+        output_dir: &Path,
+        output_path: &Path,
+        sha256: String,
+        words: usize,
+        metrics: BTreeMap<String, Number>,
+    ) -> &mut ManifestEntry {
+        let entry = ManifestEntry {
+            title: page.title().unwrap_or_default(),
+            path: relative_path(output_dir, output_path),
+            sha256,
+            words,
+            created_time: page.created_time,
+            last_edited_time: page.last_edited_time,
+            metrics,
+            parts: vec![],
+        };
+        self.pages
+            .entry(normalize_id(&page.id.to_string()))
+            .insert_entry(entry)
+            .into_mut()
+    }
+
+    // keeps the previous entry of a page that failed this time, its file is still on disk
+    pub fn carry_over(&mut self, previous: Option<&ExportManifest>, page: &Page) {
+        let page_id = normalize_id(&page.id.to_string());
+        if let Some(entry) = previous.and_then(|previous| previous.pages.get(&page_id)) {
+            self.pages.insert(page_id, entry.clone());
+        }
+    }
+}
@@ -0,0 +1,933 @@
+use crate::{
+    alt_text,
+    api::NotionClient,
+    assets::AttachmentIndex,
+    callouts, columns,
+    configuration::{ColumnLayout, ExportConfig, Flavor, GlossaryStyle, ListStyle, ToggleStyle},
+    escape,
+    glossary::{Glossary, GlossaryTerm},
+    hugo,
+    i18n::{self, Text},
+    ids::{normalize_id, same_id},
+    layout, report,
+    rich_text::{self, plain_text, InlineContext, InlineLink},
+    writer::PrefixedWriter,
+};
+use notion::{
+    ids::{DatabaseId, PageId},
+    models::{
+        block::{Block, FileObject, TableFields},
+        text::{MentionObject, RichText},
+        Parent,
+    },
+};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::sync::{Mutex, OnceCell};
+
+// data gathered before rendering since the renderer itself is synchronous
+#[derive(Debug, Default)]
+pub struct PageResources {
+    // image captions and generated alt texts, looked up by the image url
+    pub alt_texts: HashMap<String, String>,
+    // downloaded files, looked up by their notion url
+    pub local_files: AttachmentIndex,
+    // extracted code blocks keyed by block id
+    pub code_files: HashMap<String, PathBuf>,
+    // equation blocks rendered to svg keyed by block id
+    pub equation_images: HashMap<String, PathBuf>,
+    // index notes of inline databases keyed by block id, only set with database_as_notes
+    pub database_notes: HashMap<String, PathBuf>,
+    // width ratios of resized columns keyed by normalized column id, only set with html columns
+    pub column_widths: HashMap<String, f64>,
+    // pages mentioned in the text or linked with link to page blocks, keyed by normalized page id
+    pub linked_pages: HashMap<String, LinkedPage>,
+    // titles of databases linked with link to page blocks, keyed by normalized database id
+    pub linked_databases: HashMap<String, String>,
+    pub glossary: Option<Arc<Glossary>>,
+    // normalized id of the page being rendered
+    pub page_id: String,
+    pub note_dir: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkedPage {
+    pub title: String,
+    // the exported note, when the page was exported by this or an earlier run
+    pub note: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListKind {
+    Bulleted,
+    Numbered,
+    ToDo,
+}
+
+fn list_kind(block: &Block) -> Option<ListKind> {
+    match block {
+        Block::BulletedListItem { .. } => Some(ListKind::Bulleted),
+        Block::NumberedListItem { .. } => Some(ListKind::Numbered),
+        Block::ToDo { .. } => Some(ListKind::ToDo),
+        _ => None,
+    }
+}
+
+// where in the page a block is rendered, handed down the block tree instead of a bare writer
+pub struct RenderContext<'a> {
+    writer: PrefixedWriter<'a>,
+    pub config: &'a ExportConfig,
+    pub resources: &'a PageResources,
+    // lowercase glossary terms already linked, only the first occurrence on a page gets a link
+    glossary_used: RefCell<HashSet<String>>,
+    // 1-based position of the current block within its run of list items of the same kind
+    list_number: usize,
+}
+
+impl<'a> RenderContext<'a> {
+    pub fn new(
+        inner: &'a mut dyn Write,
+        config: &'a ExportConfig,
+        resources: &'a PageResources,
+    ) -> Self {
+        Self {
+            writer: PrefixedWriter::new(inner),
+            config,
+            resources,
+            glossary_used: RefCell::default(),
+            list_number: 0,
+        }
+    }
+
+    pub fn flavor(&self) -> Flavor {
+        self.config.flavor
+    }
+
+    pub fn list_number(&self) -> usize {
+        self.list_number
+    }
+
+    pub fn push_prefix(&mut self, prefix: &str) {
+        self.writer.push_prefix(prefix);
+    }
+
+    pub fn push_indent(&mut self, width: usize) {
+        self.writer.push_indent(width);
+    }
+
+    pub fn pop_prefix(&mut self) {
+        self.writer.pop_prefix();
+    }
+}
+
+impl Write for RenderContext<'_> {
+    fn write_str(&mut self, text: &str) -> std::fmt::Result {
+        self.writer.write_str(text)
+    }
+}
+
+// renders sibling blocks, handling the spacing between consecutive list items
+pub fn blocks_to_markdown(blocks: Vec<Block>, context: &mut RenderContext) -> anyhow::Result<()> {
+    let mut previous_list_kind = None;
+    let mut list_number = 0;
+    for (index, block) in blocks.into_iter().enumerate() {
+        let current_list_kind = list_kind(&block);
+        let list_boundary = current_list_kind != previous_list_kind
+            && (current_list_kind.is_some() || previous_list_kind.is_some());
+        list_number = match current_list_kind {
+            Some(_) if !list_boundary => list_number + 1,
+            Some(_) => 1,
+            None => 0,
+        };
+        let loose_item = current_list_kind.is_some()
+            && !list_boundary
+            && context.config.list_style == ListStyle::Loose;
+        // lists need a blank line around them so renderers don't merge them with paragraphs
+        if index > 0 && (list_boundary || loose_item) {
+            context.write_str("\n")?;
+        }
+        // children render their own lists so the position is set again for every sibling
+        context.list_number = list_number;
+        block_to_markdown(block, context)?;
+        previous_list_kind = current_list_kind;
+    }
+    Ok(())
+}
+
+// four spaces per level nest children under their item in commonmark and in the
+// original markdown.pl family that doesn't line children up with the item text
+const LIST_CHILD_INDENT: usize = 4;
+
+fn list_item_children(children: Vec<Block>, context: &mut RenderContext) -> anyhow::Result<()> {
+    if children.is_empty() {
+        return Ok(());
+    }
+    context.push_indent(LIST_CHILD_INDENT);
+    blocks_to_markdown(children, context)?;
+    context.pop_prefix();
+    Ok(())
+}
+
+#[allow(clippy::print_with_newline)]
+#[allow(clippy::write_with_newline)]
+fn block_to_markdown(block: Block, context: &mut RenderContext) -> anyhow::Result<()> {
+    match block {
+        Block::Paragraph {
+            common: _,
+            paragraph,
+        } => {
+            write!(
+                context,
+                "{}\n",
+                render_rich_text(context, &paragraph.rich_text)
+            )?;
+            let children = paragraph.children.unwrap_or_default();
+            if !children.is_empty() {
+                // markdown has no indented paragraphs, four spaces would turn them into code
+                // so nested blocks are quoted below their parent instead
+                context.push_prefix("> ");
+                blocks_to_markdown(children, context)?;
+                context.pop_prefix();
+                // end the quote so the next paragraph isn't lazily pulled into it
+                write!(context, "\n")?;
+            }
+        }
+        Block::Heading1 {
+            common: _,
+            heading_1,
+        } => {
+            write!(
+                context,
+                "\n# {}\n\n",
+                render_rich_text(context, &heading_1.rich_text)
+            )?;
+        }
+        Block::Heading2 {
+            common: _,
+            heading_2,
+        } => {
+            write!(
+                context,
+                "\n## {}\n\n",
+                render_rich_text(context, &heading_2.rich_text)
+            )?;
+        }
+        Block::Heading3 {
+            common: _,
+            heading_3,
+        } => {
+            write!(
+                context,
+                "\n### {}\n\n",
+                render_rich_text(context, &heading_3.rich_text)
+            )?;
+        }
+        Block::Callout { common: _, callout } => {
+            let emoji = callouts::callout_emoji(&callout.icon);
+            let callout_type = callouts::callout_type(context.config, emoji, callout.color);
+            context.push_prefix("> ");
+            match emoji {
+                Some(emoji) => writeln!(context, "[!{callout_type}] {emoji}")?,
+                None => writeln!(context, "[!{callout_type}]")?,
+            }
+            writeln!(context, "{}", render_rich_text(context, &callout.rich_text))?;
+            context.pop_prefix();
+            write!(context, "\n")?;
+        }
+        Block::Quote { common: _, quote } => {
+            context.push_prefix("> ");
+            write!(context, "{}\n", render_rich_text(context, &quote.rich_text))?;
+            if let Some(children) = quote.children {
+                // nested quotes push another level of prefix
+                write!(context, "\n")?;
+                blocks_to_markdown(children, context)?;
+            }
+            context.pop_prefix();
+            // end the quote so the next paragraph isn't lazily pulled into it
+            write!(context, "\n")?;
+        }
+        Block::BulletedListItem {
+            common: _,
+            bulleted_list_item,
+        } => {
+            write!(
+                context,
+                "* {}\n",
+                render_rich_text(context, &bulleted_list_item.rich_text)
+            )?;
+            list_item_children(bulleted_list_item.children.unwrap_or_default(), context)?;
+        }
+        Block::NumberedListItem {
+            common: _,
+            numbered_list_item,
+        } => {
+            // the position restarts whenever another kind of block breaks the list
+            write!(
+                context,
+                "{}. {}\n",
+                context.list_number(),
+                render_rich_text(context, &numbered_list_item.rich_text)
+            )?;
+            list_item_children(numbered_list_item.children.unwrap_or_default(), context)?;
+        }
+        Block::Toggle { common: _, toggle } => match context.config.toggles {
+            ToggleStyle::Details => {
+                let summary = plain_text(&toggle.rich_text);
+
+                write!(context, "<details> <summary>{summary}</summary> \n",)?;
+
+                blocks_to_markdown(toggle.children.unwrap_or_default(), context)?;
+
+                write!(context, "</details>\n\n",)?;
+            }
+            // obsidian folds callouts with a trailing `-` in live preview too, <details> only in reading view
+            ToggleStyle::Callout => {
+                let title = render_rich_text(context, &toggle.rich_text);
+                context.push_prefix("> ");
+                write!(context, "[!note]- {title}\n")?;
+                blocks_to_markdown(toggle.children.unwrap_or_default(), context)?;
+                context.pop_prefix();
+                write!(context, "\n")?;
+            }
+        },
+        Block::ToDo { common: _, to_do } => {
+            let checked = to_do.checked;
+            let checked = if checked { "x" } else { "" };
+            write!(
+                context,
+                "- [{checked}] {}\n",
+                render_rich_text(context, &to_do.rich_text)
+            )?;
+            list_item_children(to_do.children.unwrap_or_default(), context)?;
+        }
+        Block::Code { common, code } => {
+            if let Some(code_file) = context.resources.code_files.get(&common.id.to_string()) {
+                let embed = code_file_embed(context.config, code_file, &context.resources.note_dir);
+                write!(context, "\n{embed}\n")?;
+                write_caption(context, &code.caption)?;
+                write!(context, "\n")?;
+                return Ok(());
+            }
+            // formatting inside a fence would show up as literal markup
+            let content = plain_text(&code.rich_text);
+            // this works
+            let language = format!("{:?}", code.language).to_lowercase();
+
+            let fence = escape::code_fence(&content);
+            write!(context, "\n{fence}{language}\n{content}\n{fence}\n",)?;
+            write_caption(context, &code.caption)?;
+            write!(context, "\n")?;
+        }
+        Block::ChildPage { common, child_page } => {
+            // I think this is right?
+            let block_id = common.id;
+            let _page_id = PageId::from_str(&block_id.to_string())?;
+            // wait is this needed?
+            // let _page_title = page_id_cache.get_page_title(&page_id)?;
+
+            let label = i18n::text(context.config, Text::ChildPage);
+            // links by file name so titles that had to be escaped still resolve
+            let target = escape::file_name(&child_page.title);
+            let link = if target == child_page.title {
+                internal_link(None, &target)
+            } else {
+                internal_link(Some(&child_page.title), &target)
+            };
+            write!(context, "{label}: {link}\n")?;
+        }
+        Block::ChildDatabase {
+            common,
+            child_database,
+        } => {
+            let label = i18n::text(context.config, Text::ChildDatabase);
+            match context.resources.database_notes.get(&common.id.to_string()) {
+                Some(index_note) => {
+                    let link = note_link(
+                        context.config,
+                        &child_database.title,
+                        index_note,
+                        &context.resources.note_dir,
+                    );
+                    write!(context, "{label}: {link}\n")?;
+                }
+                // TODO same as above?
+                None => write!(context, "{label}: {}\n", child_database.title)?,
+            }
+        }
+        Block::Image { common: _, image } => {
+            let url = render_file_object(image);
+            let alt_text = context
+                .resources
+                .alt_texts
+                .get(&url)
+                .map(|alt_text| alt_text::sanitize_alt_text(alt_text));
+            let embed = match context.resources.local_files.get(&url) {
+                Some(local_path) => local_file_embed(
+                    context.flavor(),
+                    alt_text.as_deref(),
+                    local_path,
+                    &context.resources.note_dir,
+                ),
+                None => external_embed(alt_text.as_deref(), &url),
+            };
+            write!(context, "{}\n", embed)?;
+        }
+        Block::Video { common: _, video } => {
+            let url = render_file_object(video);
+            match local_attachment_embed(context, &url) {
+                Some(embed) => writeln!(context, "{embed}")?,
+                None => write!(context, "![[{}]]\n", url)?,
+            }
+        }
+        Block::File {
+            common: _,
+            file,
+            caption,
+        } => {
+            write_file(context, file)?;
+            write_caption(context, &caption)?;
+        }
+        Block::Pdf { common: _, pdf } => {
+            write_file(context, pdf)?;
+        }
+
+        Block::Divider { common: _ } => {
+            write!(context, "----\n")?;
+        }
+
+        Block::Embed { common: _, embed } => {
+            write!(context, "![[{}]]\n", embed.url)?;
+            write_caption(context, &embed.caption)?;
+        }
+
+        Block::Bookmark {
+            common: _,
+            bookmark,
+        } => {
+            let caption = render_rich_text(context, &bookmark.caption);
+            write!(context, "caption {} \n![[{}]]\n", caption, bookmark.url)?;
+        }
+        Block::Equation { common, equation } => {
+            match context
+                .resources
+                .equation_images
+                .get(&common.id.to_string())
+            {
+                Some(image) => {
+                    let alt_text = alt_text::sanitize_alt_text(equation.expression.trim());
+                    let embed = local_file_embed(
+                        context.flavor(),
+                        Some(&alt_text),
+                        image,
+                        &context.resources.note_dir,
+                    );
+                    write!(context, "\n{embed}\n\n")?;
+                }
+                // display math for mathjax and obsidian, the delimiters sit on their own lines
+                None => writeln!(context, "\n$$\n{}\n$$\n", equation.expression.trim())?,
+            }
+        }
+
+        Block::TableOfContents {
+            common: _,
+            table_of_contents: _,
+        } => {
+            write!(context, "\nTABLE OF CONTENTS\n")?;
+        }
+        Block::Breadcrumb { common: _ } => {
+            write!(context, "\nBREADCRUMB\n")?;
+        }
+        Block::ColumnList {
+            common: _,
+            column_list,
+        } => match context.config.columns {
+            ColumnLayout::Sequential => {
+                // markdown has no columns so they are laid out one after another
+                for column in column_list.children {
+                    blocks_to_markdown(vec![column], context)?;
+                    write!(context, "\n")?;
+                }
+            }
+            ColumnLayout::Html => write_html_columns(context, column_list.children)?,
+        },
+        Block::Column { common: _, column } => {
+            blocks_to_markdown(column.children, context)?;
+        }
+        Block::LinkPreview {
+            common: _,
+            link_preview,
+        } => {
+            write!(context, "![[{}]]\n", link_preview.url)?;
+        }
+        Block::Template {
+            common: _,
+            template,
+        } => {
+            let content = render_rich_text(context, &template.rich_text);
+            write!(context, "\nTEMPLATE {}\n", content)?;
+        }
+        Block::LinkToPage {
+            common: _,
+            link_to_page,
+        } => {
+            let link = match &link_to_page {
+                Parent::Page { page_id } => page_link(context, page_id)
+                    .unwrap_or_else(|| external_link(None, &notion_page_id_to_url(page_id))),
+                Parent::Database { database_id } => database_link(context, database_id),
+                _ => String::from("LINK TO PAGE"),
+            };
+            write!(context, "{link}\n")?;
+        }
+        Block::Table { common: _, table } => {
+            write_table(context, table)?;
+        }
+        Block::SyncedBlock {
+            common: _,
+            synced_block: _,
+        } => {
+            write!(context, "\nSYNCED BLOCK\n")?;
+        }
+        // rows are rendered by their table, one on its own has no header to go under
+        Block::TableRow {
+            common: _,
+            table_row,
+        } => {
+            writeln!(
+                context,
+                "{}",
+                table_row_line(context, &table_row.cells, false)
+            )?;
+        }
+        Block::Unsupported { common: _ } => {
+            write!(context, "\nUNSUPPORTED\n")?;
+        }
+        Block::Unknown => {
+            write!(context, "\nUNKNOWN\n")?;
+        }
+    }
+    Ok(())
+}
+
+// gfm tables always have a header row, tables without a column header get an empty one
+// markdown has no row headers so those cells are made bold instead
+fn write_table(context: &mut RenderContext, table: TableFields) -> anyhow::Result<()> {
+    let mut rows = table.children.iter().filter_map(|row| match row {
+        Block::TableRow { table_row, .. } => Some(table_row.cells.as_slice()),
+        _ => None,
+    });
+    let width = table.table_width.max(1) as usize;
+    writeln!(context)?;
+    match table.has_column_header.then(|| rows.next()).flatten() {
+        Some(header) => writeln!(context, "{}", table_row_line(context, header, false))?,
+        None => writeln!(context, "|{}", " |".repeat(width))?,
+    }
+    writeln!(context, "|{}", " --- |".repeat(width))?;
+    for cells in rows {
+        writeln!(
+            context,
+            "{}",
+            table_row_line(context, cells, table.has_row_header)
+        )?;
+    }
+    writeln!(context)?;
+    Ok(())
+}
+
+fn table_row_line(
+    context: &RenderContext,
+    cells: &[Vec<RichText>],
+    bold_first_cell: bool,
+) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .enumerate()
+        .map(|(index, cell)| {
+            // a cell has to stay on one line and a pipe would end it early
+            let text = render_rich_text(context, cell)
+                .replace('|', "\\|")
+                .replace('\n', "<br>");
+            if bold_first_cell && index == 0 && !text.trim().is_empty() {
+                format!("**{}**", text.trim())
+            } else {
+                text
+            }
+        })
+        .collect();
+    format!("| {} |", cells.join(" | "))
+}
+
+// the blank lines around the divs keep the markdown inside them rendering
+fn write_html_columns(context: &mut RenderContext, columns: Vec<Block>) -> anyhow::Result<()> {
+    let column_ids: Vec<String> = columns
+        .iter()
+        .map(|column| match column {
+            Block::Column { common, .. } => common.id.to_string(),
+            _ => String::new(),
+        })
+        .collect();
+    let ratios = columns::column_ratios(&context.resources.column_widths, &column_ids);
+    writeln!(context, "<div style=\"display: flex; gap: 1em\">\n")?;
+    for (column, ratio) in columns.into_iter().zip(ratios) {
+        let ratio = (ratio * 10000.0).round() / 10000.0;
+        writeln!(context, "<div style=\"flex: {ratio} 1 0; min-width: 0\">\n")?;
+        blocks_to_markdown(vec![column], context)?;
+        writeln!(context, "\n</div>\n")?;
+    }
+    writeln!(context, "</div>")?;
+    Ok(())
+}
+
+// drive and dropbox urls point at a preview page, embedding those shows nothing useful
+fn write_file(context: &mut RenderContext, file_object: FileObject) -> anyhow::Result<()> {
+    let provider = match &file_object {
+        FileObject::External { external } if !context.config.embed_external_files => {
+            file_provider(&external.url)
+        }
+        _ => None,
+    };
+    let url = render_file_object(file_object);
+    if let Some(embed) = local_attachment_embed(context, &url) {
+        writeln!(context, "{embed}")?;
+        return Ok(());
+    }
+    match provider {
+        Some(provider) => writeln!(context, "{}", external_link(Some(provider), &url))?,
+        None => writeln!(context, "![[{url}]]")?,
+    }
+    Ok(())
+}
+
+fn local_attachment_embed(context: &RenderContext, url: &str) -> Option<String> {
+    let local_path = context.resources.local_files.get(url)?;
+    Some(local_file_embed(
+        context.flavor(),
+        None,
+        local_path,
+        &context.resources.note_dir,
+    ))
+}
+
+fn file_provider(url: &str) -> Option<&'static str> {
+    let host = url
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(url)
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    match host {
+        "drive.google.com" | "docs.google.com" => Some("Google Drive"),
+        "dropbox.com" | "dl.dropboxusercontent.com" => Some("Dropbox"),
+        _ => None,
+    }
+}
+
+fn render_file_object(file_object: FileObject) -> String {
+    match file_object {
+        FileObject::File { file } => {
+            // url is private?
+            file.url
+        }
+        FileObject::External { external } => external.url,
+    }
+}
+
+fn local_file_embed(
+    flavor: Flavor,
+    alt_text: Option<&str>,
+    local_path: &Path,
+    note_dir: &Path,
+) -> String {
+    match flavor {
+        Flavor::Obsidian => {
+            // obsidian resolves attachments by file name anywhere in the vault
+            let file_name = local_path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            internal_embed(alt_text, &file_name)
+        }
+        Flavor::Standard => {
+            let relative_path = layout::relative_path(note_dir, local_path);
+            external_embed(alt_text, &link_path(&relative_path))
+        }
+    }
+}
+
+fn code_file_embed(config: &ExportConfig, code_file: &Path, note_dir: &Path) -> String {
+    match config.flavor {
+        Flavor::Obsidian => {
+            // file names repeat between pages so embed by the path inside the vault
+            let vault_path = code_file
+                .strip_prefix(&config.output_dir)
+                .unwrap_or(code_file)
+                .to_string_lossy()
+                .replace('\\', "/");
+            internal_embed(None, &vault_path)
+        }
+        Flavor::Standard => {
+            let relative_path = layout::relative_path(note_dir, code_file);
+            let file_name = code_file
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            external_link(Some(&file_name), &link_path(&relative_path))
+        }
+    }
+}
+
+// link to another exported note
+pub fn note_link(config: &ExportConfig, title: &str, note: &Path, note_dir: &Path) -> String {
+    if config.hugo.is_some() {
+        return hugo::relref_link(config, title, note);
+    }
+    match config.flavor {
+        Flavor::Obsidian => {
+            // note names repeat between folders so link by the path inside the vault
+            let vault_path = note
+                .strip_prefix(&config.output_dir)
+                .unwrap_or(note)
+                .with_extension("")
+                .to_string_lossy()
+                .replace('\\', "/");
+            internal_link(Some(title), &vault_path)
+        }
+        Flavor::Standard => {
+            let relative_path = layout::relative_path(note_dir, note);
+            external_link(Some(title), &link_path(&relative_path))
+        }
+    }
+}
+
+// links use forward slashes on every platform, external_link escapes the rest
+fn link_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+// captions go on their own line below the block they describe
+fn write_caption(context: &mut RenderContext, caption: &[RichText]) -> anyhow::Result<()> {
+    if !caption.is_empty() {
+        writeln!(context, "{}", render_rich_text(context, caption))?;
+    }
+    Ok(())
+}
+
+// all rich text rendered as markdown goes through here so captions, callouts and
+// table cells get the same inline formatting as paragraphs
+fn render_rich_text(context: &RenderContext, rich_text: &[RichText]) -> String {
+    rich_text::render_rich_text(context.config, rich_text, context)
+}
+
+impl InlineContext for RenderContext<'_> {
+    fn link(&self, text: &RichText) -> Option<InlineLink> {
+        inline_link(self, text)
+    }
+
+    fn expand(&self, text: &str) -> Option<String> {
+        let glossary = self.resources.glossary.as_ref()?;
+        let mut used = self.glossary_used.borrow_mut();
+        glossary.expand(
+            text,
+            &self.resources.page_id,
+            &mut used,
+            &|term, written| glossary_link(self, term, written),
+        )
+    }
+}
+
+// abbr tooltips need a definition, terms without one are linked either way
+fn glossary_link(context: &RenderContext, term: &GlossaryTerm, written: &str) -> String {
+    let abbr = context
+        .config
+        .glossary
+        .as_ref()
+        .map(|glossary| glossary.style == GlossaryStyle::Abbr)
+        .unwrap_or(false);
+    match (&term.definition, abbr) {
+        (Some(definition), true) => format!(
+            "<abbr title=\"{}\">{written}</abbr>",
+            report::escape_html(definition)
+        ),
+        _ => note_link(
+            context.config,
+            written,
+            &term.note,
+            &context.resources.note_dir,
+        ),
+    }
+}
+
+// links in the text and mentions of pages, other mentions stay plain text
+fn inline_link(context: &RenderContext, text: &RichText) -> Option<InlineLink> {
+    match text {
+        RichText::Text { text, .. } => {
+            let url = &text.link.as_ref()?.url;
+            // links to other notion pages are relative to notion.so
+            if url.starts_with('/') {
+                Some(InlineLink::Url(format!("https://www.notion.so{url}")))
+            } else {
+                Some(InlineLink::Url(url.clone()))
+            }
+        }
+        RichText::Mention {
+            mention: MentionObject::Page { page },
+            rich_text,
+        } => {
+            // pages the integration can't see keep pointing at notion
+            match page_link(context, &page.id) {
+                Some(link) => Some(InlineLink::Replace(link)),
+                None => rich_text.href.clone().map(InlineLink::Url),
+            }
+        }
+        _ => None,
+    }
+}
+
+// the exported note of a linked page, or its title when it wasn't exported
+fn page_link(context: &RenderContext, page_id: &PageId) -> Option<String> {
+    let linked = context
+        .resources
+        .linked_pages
+        .get(&normalize_id(&page_id.to_string()))?;
+    Some(match (&linked.note, context.flavor()) {
+        (Some(note), _) => note_link(
+            context.config,
+            &linked.title,
+            note,
+            &context.resources.note_dir,
+        ),
+        (None, Flavor::Obsidian) => internal_link(None, &escape::file_name(&linked.title)),
+        (None, Flavor::Standard) => {
+            external_link(Some(&linked.title), &notion_page_id_to_url(page_id))
+        }
+    })
+}
+
+// inline databases link to their index note with database_as_notes, everything else to notion
+fn database_link(context: &RenderContext, database_id: &DatabaseId) -> String {
+    let url = notion_database_id_to_url(database_id);
+    let Some(title) = context
+        .resources
+        .linked_databases
+        .get(&normalize_id(&database_id.to_string()))
+    else {
+        return external_link(None, &url);
+    };
+    let index_note = context
+        .resources
+        .database_notes
+        .iter()
+        .find(|(block_id, _)| same_id(block_id, &database_id.to_string()));
+    match index_note {
+        Some((_, index_note)) => note_link(
+            context.config,
+            title,
+            index_note,
+            &context.resources.note_dir,
+        ),
+        None => external_link(Some(title), &url),
+    }
+}
+
+pub fn notion_page_id_to_url(id: &PageId) -> String {
+    let id_stripped = id.to_string().replace('-', "");
+    format!("http://notion.so/{}", id_stripped)
+}
+
+pub fn notion_database_id_to_url(id: &DatabaseId) -> String {
+    let id_stripped = id.to_string().replace('-', "");
+    format!("http://notion.so/{}", id_stripped)
+}
+
+// page titles shared by every page of a run, clones share the same entries
+// concurrent lookups of the same page wait for the first request instead of sending their own
+#[derive(Clone, Default)]
+pub struct PageIdCache {
+    // keyed by normalized page id
+    page_to_title: Arc<Mutex<HashMap<String, Arc<OnceCell<String>>>>>,
+}
+
+impl PageIdCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // titles we already know from the search results don't need a request later
+    pub async fn insert(&self, page_id: &str, title: &str) {
+        let cell = self.cell(page_id).await;
+        // an already resolved title wins, both came from notion anyway
+        let _ = cell.set(title.to_owned());
+    }
+
+    pub async fn get_page_title(
+        &self,
+        id: &PageId,
+        client: &NotionClient,
+    ) -> anyhow::Result<String> {
+        let cell = self.cell(&id.to_string()).await;
+        // the map lock is released before the request so other pages aren't blocked on it
+        let title = cell
+            .get_or_try_init(|| async {
+                let page = client.get_page(id.clone()).await?;
+                anyhow::Ok(page.title().unwrap_or("UNKNOWN_TITLE".to_owned()))
+            })
+            .await?;
+        Ok(title.clone())
+    }
+
+    async fn cell(&self, page_id: &str) -> Arc<OnceCell<String>> {
+        self.page_to_title
+            .lock()
+            .await
+            .entry(normalize_id(page_id))
+            .or_default()
+            .clone()
+    }
+}
+
+fn internal_embed(text: Option<&str>, link: &str) -> String {
+    if let Some(text) = text {
+        format!("![[{}|{}]]", link, text)
+    } else {
+        format!("![[{}]]", link)
+    }
+}
+
+fn external_embed(text: Option<&str>, link: &str) -> String {
+    let link = escape::link_destination(link);
+    if let Some(text) = text {
+        format!("![{}]({})", escape::link_text(text), link)
+    } else {
+        format!("![]({})", link)
+    }
+}
+
+pub fn internal_link(text: Option<&str>, link: &str) -> String {
+    let link = escape::wikilink_target(link);
+    if let Some(text) = text {
+        format!("[[{}|{}]]", link, escape::wikilink_text(text))
+    } else {
+        format!("[[{}]]", link)
+    }
+}
+
+pub fn external_link(text: Option<&str>, link: &str) -> String {
+    if let Some(text) = text {
+        format!(
+            "[{}]({})",
+            escape::link_text(text),
+            escape::link_destination(link)
+        )
+    } else {
+        format!("<{}>", link)
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{
+    changes::ChangeSet,
+    configuration::{ChatService, Notification},
+};
+use serde_json::{json, Value};
+
+// discord rejects messages longer than 2000 characters, slack is more lenient
+const MAX_MESSAGE_CHARS: usize = 1900;
+const MAX_LISTED_FAILURES: usize = 10;
+
+// posts a summary of the run to every configured chat webhook
+// a failed post is only logged, the export already happened
+pub async fn notify(notifications: &[Notification], result: &anyhow::Result<ChangeSet>) {
+    let failed = match result {
+        Ok(changes) => !changes.failed.is_empty(),
+        Err(_) => true,
+    };
+    let message = run_summary(result);
+    for notification in notifications {
+        if notification.only_failures && !failed {
+            continue;
+        }
+        if let Err(error) = post_message(notification, &message).await {
+            eprintln!(
+                "Failed to post {:?} notification with error {error:?}",
+                notification.service
+            );
+        }
+    }
+}
+
+fn run_summary(result: &anyhow::Result<ChangeSet>) -> String {
+    let changes = match result {
+        Ok(changes) => changes,
+        Err(error) => return truncate(&format!("notion-bridge export failed: {error:#}")),
+    };
+    let mut message = format!(
+        "notion-bridge export finished: {} added, {} updated, {} removed",
+        changes.added.len(),
+        changes.updated.len(),
+        changes.removed.len()
+    );
+    if !changes.failed.is_empty() {
+        message.push_str(&format!(", {} failed", changes.failed.len()));
+        for failure in changes.failed.iter().take(MAX_LISTED_FAILURES) {
+            message.push_str(&format!("\n- {}: {}", failure.title, failure.error));
+        }
+        if changes.failed.len() > MAX_LISTED_FAILURES {
+            message.push_str(&format!(
+                "\n- and {} more",
+                changes.failed.len() - MAX_LISTED_FAILURES
+            ));
+        }
+    }
+    truncate(&message)
+}
+
+fn truncate(message: &str) -> String {
+    if message.chars().count() <= MAX_MESSAGE_CHARS {
+        return message.to_owned();
+    }
+    let mut truncated: String = message.chars().take(MAX_MESSAGE_CHARS).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn payload(service: ChatService, message: &str) -> Value {
+    match service {
+        ChatService::Slack => json!({ "text": message }),
+        ChatService::Discord => json!({ "content": message }),
+    }
+}
+
+async fn post_message(notification: &Notification, message: &str) -> anyhow::Result<()> {
+    reqwest::Client::new()
+        .post(&notification.webhook_url)
+        .json(&payload(notification.service, message))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use anyhow::Context;
+use serde_yaml::{Mapping, Value};
+
+// export defaults for common targets, compiled in so they work without any files next to the binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Obsidian,
+    Hugo,
+    Mdbook,
+    Plain,
+}
+
+impl std::str::FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<Self> {
+        match text {
+            "obsidian" => Ok(Preset::Obsidian),
+            "hugo" => Ok(Preset::Hugo),
+            "mdbook" => Ok(Preset::Mdbook),
+            "plain" => Ok(Preset::Plain),
+            other => {
+                anyhow::bail!("unknown preset {other}, expected obsidian, hugo, mdbook or plain")
+            }
+        }
+    }
+}
+
+impl Preset {
+    fn fragment(self) -> &'static str {
+        match self {
+            Preset::Obsidian => include_str!("../presets/obsidian.yaml"),
+            Preset::Hugo => include_str!("../presets/hugo.yaml"),
+            Preset::Mdbook => include_str!("../presets/mdbook.yaml"),
+            Preset::Plain => include_str!("../presets/plain.yaml"),
+        }
+    }
+}
+
+// fills in every export setting the user config leaves out, in the export section and in every job
+pub fn apply_preset(preset: Preset, user_config: &mut Value) -> anyhow::Result<()> {
+    let fragment: Mapping = serde_yaml::from_str(preset.fragment())
+        .with_context(|| format!("preset {preset:?} is not a yaml mapping"))?;
+    let user_config = user_config
+        .as_mapping_mut()
+        .context("config file is not a yaml mapping")?;
+    let export = user_config
+        .entry(Value::from("export"))
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    fill_missing(export, &fragment);
+    if let Some(jobs) = user_config
+        .get_mut(Value::from("jobs"))
+        .and_then(Value::as_sequence_mut)
+    {
+        for job in jobs {
+            fill_missing(job, &fragment);
+        }
+    }
+    Ok(())
+}
+
+fn fill_missing(section: &mut Value, fragment: &Mapping) {
+    let Some(section) = section.as_mapping_mut() else {
+        return;
+    };
+    for (key, value) in fragment {
+        if !section.contains_key(key) {
+            section.insert(key.clone(), value.clone());
+        }
+    }
+}
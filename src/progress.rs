@@ -0,0 +1,85 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::sync::OnceLock;
+
+// pages done out of pages queued so far, rows of inline databases and subpages add to the total
+// drawn on stderr and only on a terminal, piped output gets the usual line per page
+pub struct ExportProgress {
+    multi: MultiProgress,
+    pages: ProgressBar,
+    // only shown once the first attachment is downloaded
+    attachments: OnceLock<ProgressBar>,
+    quiet: bool,
+}
+
+impl ExportProgress {
+    pub fn new(pages: usize, quiet: bool) -> Self {
+        let multi = if quiet {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        } else {
+            MultiProgress::new()
+        };
+        let style = ProgressStyle::with_template(
+            "[{elapsed_precise}] {bar:40} {pos}/{len} pages {wide_msg}",
+        )
+        .expect("invalid progress template");
+        let pages = multi.add(ProgressBar::new(pages as u64).with_style(style));
+        Self {
+            multi,
+            pages,
+            attachments: OnceLock::new(),
+            quiet,
+        }
+    }
+
+    pub fn queued(&self, pages: usize) {
+        self.pages.inc_length(pages as u64);
+    }
+
+    // pages already exported by another job or twice in the queue
+    pub fn skipped(&self) {
+        self.pages.dec_length(1);
+    }
+
+    pub fn started(&self, title: &str, url: &str) {
+        self.pages.set_message(title.to_owned());
+        self.println(&format!("Page: {title} {url}"));
+    }
+
+    pub fn finished(&self) {
+        self.pages.inc(1);
+    }
+
+    // bytes of every attachment download, the length grows as responses come in
+    pub fn attachments(&self) -> &ProgressBar {
+        self.attachments.get_or_init(|| {
+            let style = ProgressStyle::with_template(
+                "attachments {bytes}/{total_bytes} {bytes_per_sec} {wide_msg}",
+            )
+            .expect("invalid progress template");
+            self.multi.add(ProgressBar::new(0).with_style(style))
+        })
+    }
+
+    // lines printed around the bars instead of through them
+    pub fn println(&self, line: &str) {
+        if self.quiet {
+            return;
+        }
+        if self.multi.is_hidden() {
+            println!("{line}");
+        } else {
+            self.pages.println(line);
+        }
+    }
+
+    pub fn eprintln(&self, line: &str) {
+        self.multi.suspend(|| eprintln!("{line}"));
+    }
+
+    pub fn finish(&self) {
+        if let Some(attachments) = self.attachments.get() {
+            attachments.finish_and_clear();
+        }
+        self.pages.finish_and_clear();
+    }
+}
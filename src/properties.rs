@@ -0,0 +1,260 @@
+use crate::{configuration::Coercion, rich_text::plain_text};
+use notion::models::{
+    properties::{DateOrDateTime, PropertyValue},
+    users::User,
+    Page,
+};
+use serde_json::{json, Number, Value};
+
+// plain text form of a property, used for matching against user supplied values
+pub fn property_text(value: &PropertyValue) -> Option<String> {
+    match value {
+        PropertyValue::Title { title, .. } => Some(plain_text(title)),
+        PropertyValue::Text { rich_text, .. } => Some(plain_text(rich_text)),
+        PropertyValue::Number { number, .. } => number.as_ref().map(|number| number.to_string()),
+        PropertyValue::Select { select, .. } => {
+            select.as_ref().and_then(|select| select.name.clone())
+        }
+        PropertyValue::Status { status, .. } => {
+            status.as_ref().and_then(|status| status.name.clone())
+        }
+        PropertyValue::MultiSelect { multi_select, .. } => Some(
+            multi_select
+                .iter()
+                .flatten()
+                .filter_map(|option| option.name.clone())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        PropertyValue::Date { date, .. } => date.as_ref().map(|date| date_text(&date.start)),
+        PropertyValue::Checkbox { checkbox, .. } => Some(checkbox.to_string()),
+        PropertyValue::Url { url, .. } => url.clone(),
+        PropertyValue::Email { email, .. } => email.clone(),
+        PropertyValue::PhoneNumber { phone_number, .. } => Some(phone_number.clone()),
+        _ => None,
+    }
+}
+
+// typed frontmatter value of a property, lists stay lists and numbers stay numbers
+// relations are left to the caller since they need the titles of the related pages
+pub fn property_frontmatter_value(value: &PropertyValue) -> Option<Value> {
+    let value = match value {
+        PropertyValue::Text { rich_text, .. } => json!(plain_text(rich_text)),
+        PropertyValue::Number { number, .. } => json!(number.as_ref()?),
+        PropertyValue::Select { select, .. } => json!(select.as_ref()?.name.as_ref()?),
+        PropertyValue::Status { status, .. } => json!(status.as_ref()?.name.as_ref()?),
+        PropertyValue::MultiSelect { multi_select, .. } => {
+            let names: Vec<&String> = multi_select
+                .iter()
+                .flatten()
+                .filter_map(|option| option.name.as_ref())
+                .collect();
+            json!(names)
+        }
+        PropertyValue::Date { date, .. } => {
+            let date = date.as_ref()?;
+            match &date.end {
+                Some(end) => json!({ "start": date_text(&date.start), "end": date_text(end) }),
+                None => json!(date_text(&date.start)),
+            }
+        }
+        PropertyValue::People { people, .. } => {
+            let names: Vec<&String> = people
+                .iter()
+                .filter_map(|user| match user {
+                    User::Person { common, .. } | User::Bot { common, .. } => common.name.as_ref(),
+                })
+                .collect();
+            json!(names)
+        }
+        PropertyValue::Checkbox { checkbox, .. } => json!(checkbox),
+        PropertyValue::Url { url, .. } => json!(url.as_ref()?),
+        PropertyValue::Email { email, .. } => json!(email.as_ref()?),
+        PropertyValue::PhoneNumber { phone_number, .. } => json!(phone_number),
+        PropertyValue::CreatedTime { created_time, .. } => json!(created_time.to_rfc3339()),
+        PropertyValue::LastEditedTime {
+            last_edited_time, ..
+        } => json!(last_edited_time.to_rfc3339()),
+        // the title is the file name already
+        PropertyValue::Title { .. } | PropertyValue::Relation { .. } => return None,
+    };
+    Some(value)
+}
+
+// reshapes a frontmatter value into the type a mapping asks for, values that can't be converted are dropped
+pub fn coerce(value: Value, coercion: Coercion) -> Option<Value> {
+    let value = match (coercion, value) {
+        (Coercion::Text, Value::String(text)) => json!(text),
+        (Coercion::Text, Value::Array(values)) => json!(values
+            .iter()
+            .filter_map(|value| coerce(value.clone(), Coercion::Text)?
+                .as_str()
+                .map(str::to_owned))
+            .collect::<Vec<_>>()
+            .join(", ")),
+        (Coercion::Text, Value::Object(range)) => json!(format!(
+            "{}/{}",
+            range.get("start")?.as_str()?,
+            range.get("end")?.as_str()?
+        )),
+        (Coercion::Text, Value::Null) => return None,
+        (Coercion::Text, value) => json!(value.to_string()),
+        (Coercion::List, Value::Array(values)) => json!(values),
+        (Coercion::List, Value::String(text)) => json!(text
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<_>>()),
+        (Coercion::List, Value::Null) => json!([]),
+        (Coercion::List, value) => json!([value]),
+        (Coercion::Bool, Value::Bool(value)) => json!(value),
+        (Coercion::Bool, Value::String(text)) => {
+            json!(matches!(
+                text.trim().to_lowercase().as_str(),
+                "true" | "yes" | "1" | "x"
+            ))
+        }
+        (Coercion::Bool, Value::Number(number)) => json!(number.as_f64() != Some(0.0)),
+        (Coercion::Bool, Value::Array(values)) => json!(!values.is_empty()),
+        (Coercion::Bool, _) => json!(false),
+        (Coercion::Number, Value::Number(number)) => json!(number),
+        (Coercion::Number, Value::String(text)) => json!(text.trim().parse::<f64>().ok()?),
+        (Coercion::Number, Value::Bool(value)) => json!(u8::from(value)),
+        (Coercion::Number, _) => return None,
+        // ranges keep their start, iso dates and date times stay as they are
+        (Coercion::Date, Value::Object(range)) => range.get("start")?.clone(),
+        (Coercion::Date, Value::String(text)) => {
+            let text = text.trim();
+            let valid = chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok()
+                || chrono::DateTime::parse_from_rfc3339(text).is_ok();
+            if !valid {
+                return None;
+            }
+            json!(text)
+        }
+        (Coercion::Date, _) => return None,
+    };
+    Some(value)
+}
+
+pub fn page_property_text(page: &Page, property: &str) -> Option<String> {
+    page.properties
+        .properties
+        .get(property)
+        .and_then(property_text)
+}
+
+pub fn page_property_number(page: &Page, property: &str) -> Option<Number> {
+    match page.properties.properties.get(property)? {
+        PropertyValue::Number { number, .. } => number.clone(),
+        _ => None,
+    }
+}
+
+// unticked and missing checkboxes both count as false
+pub fn page_checkbox(page: &Page, property: &str) -> bool {
+    matches!(
+        page.properties.properties.get(property),
+        Some(PropertyValue::Checkbox { checkbox: true, .. })
+    )
+}
+
+pub fn date_text(date: &DateOrDateTime) -> String {
+    match date {
+        DateOrDateTime::Date(date) => date.to_string(),
+        DateOrDateTime::DateTime(date_time) => date_time.to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(value: Value) -> PropertyValue {
+        serde_json::from_value(value).unwrap()
+    }
+
+    // what a mapping writes, the natural value of the property coerced
+    fn mapped(value: Value, coercion: Coercion) -> Option<Value> {
+        coerce(property_frontmatter_value(&property(value))?, coercion)
+    }
+
+    #[test]
+    fn numbers_are_parsed_from_text() {
+        assert_eq!(coerce(json!(" 4.5 "), Coercion::Number), Some(json!(4.5)));
+        assert_eq!(coerce(json!(true), Coercion::Number), Some(json!(1)));
+        assert_eq!(coerce(json!("four"), Coercion::Number), None);
+        assert_eq!(
+            mapped(
+                json!({ "id": "a", "type": "number", "number": 3 }),
+                Coercion::Text
+            ),
+            Some(json!("3"))
+        );
+    }
+
+    #[test]
+    fn dates_keep_the_start_of_ranges() {
+        let range = json!({
+            "id": "a",
+            "type": "date",
+            "date": { "start": "2024-03-01", "end": "2024-03-05" },
+        });
+        assert_eq!(
+            mapped(range.clone(), Coercion::Date),
+            Some(json!("2024-03-01"))
+        );
+        assert_eq!(
+            mapped(range, Coercion::Text),
+            Some(json!("2024-03-01/2024-03-05"))
+        );
+        assert_eq!(coerce(json!("next week"), Coercion::Date), None);
+    }
+
+    #[test]
+    fn bools_accept_common_spellings() {
+        assert_eq!(coerce(json!("Yes"), Coercion::Bool), Some(json!(true)));
+        assert_eq!(coerce(json!("no"), Coercion::Bool), Some(json!(false)));
+        assert_eq!(coerce(json!(0), Coercion::Bool), Some(json!(false)));
+        assert_eq!(
+            mapped(
+                json!({ "id": "a", "type": "checkbox", "checkbox": true }),
+                Coercion::Bool
+            ),
+            Some(json!(true))
+        );
+    }
+
+    #[test]
+    fn lists_split_text_and_join_back() {
+        assert_eq!(
+            coerce(json!("a, b,, c"), Coercion::List),
+            Some(json!(["a", "b", "c"]))
+        );
+        let tags = json!({
+            "id": "a",
+            "type": "multi_select",
+            "multi_select": [
+                { "id": "1", "name": "rust", "color": "red" },
+                { "id": "2", "name": "notion", "color": "blue" },
+            ],
+        });
+        assert_eq!(
+            mapped(tags.clone(), Coercion::List),
+            Some(json!(["rust", "notion"]))
+        );
+        assert_eq!(mapped(tags, Coercion::Text), Some(json!("rust, notion")));
+    }
+
+    #[test]
+    fn empty_values_are_left_out() {
+        assert_eq!(coerce(Value::Null, Coercion::Text), None);
+        assert_eq!(
+            mapped(
+                json!({ "id": "a", "type": "number", "number": null }),
+                Coercion::Number
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Context;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+// captures api responses into a directory or serves them back from it
+// so whole exports can be tested against a realistic workspace without credentials
+#[derive(Debug, Clone)]
+pub enum Recording {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+// one request and its response, the token is never written
+#[derive(Serialize, Deserialize, Debug)]
+struct Exchange {
+    method: String,
+    path: String,
+    #[serde(default)]
+    body: Option<Value>,
+    status: u16,
+    response: Value,
+}
+
+impl Recording {
+    pub fn is_replay(&self) -> bool {
+        matches!(self, Recording::Replay(_))
+    }
+
+    // sources see different workspaces so each one gets its own directory
+    pub fn for_source(&self, source_name: Option<&str>) -> Self {
+        let name = source_name.unwrap_or("default");
+        match self {
+            Recording::Record(dir) => Recording::Record(dir.join(name)),
+            Recording::Replay(dir) => Recording::Replay(dir.join(name)),
+        }
+    }
+
+    // the recorded status and response when replaying, None when recording
+    pub fn replay(
+        &self,
+        method: &Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> anyhow::Result<Option<(u16, Value)>> {
+        let Recording::Replay(dir) = self else {
+            return Ok(None);
+        };
+        let file_path = exchange_path(dir, method, path, body);
+        let content = std::fs::read_to_string(&file_path).with_context(|| {
+            format!(
+                "no recorded response for {method} {path} in {}",
+                file_path.display()
+            )
+        })?;
+        let exchange: Exchange = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse recording {}", file_path.display()))?;
+        Ok(Some((exchange.status, exchange.response)))
+    }
+
+    pub fn record(
+        &self,
+        method: &Method,
+        path: &str,
+        body: Option<&Value>,
+        status: u16,
+        response: &Value,
+    ) -> anyhow::Result<()> {
+        let Recording::Record(dir) = self else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(dir)?;
+        let exchange = Exchange {
+            method: method.to_string(),
+            path: path.to_owned(),
+            body: body.cloned(),
+            status,
+            response: response.clone(),
+        };
+        std::fs::write(
+            exchange_path(dir, method, path, body),
+            serde_json::to_string_pretty(&exchange)?,
+        )?;
+        Ok(())
+    }
+}
+
+// the same request always maps to the same file, paging cursors are part of the path or body
+fn exchange_path(dir: &Path, method: &Method, path: &str, body: Option<&Value>) -> PathBuf {
+    let body = body.map(Value::to_string).unwrap_or_default();
+    let hash = Sha256::digest(format!("{method} {path}\n{body}").as_bytes());
+    let hash = format!("{hash:x}");
+    dir.join(format!("{}.json", &hash[..16]))
+}
@@ -0,0 +1,129 @@
+use crate::{
+    configuration::{ExportConfig, RedactionAction, RedactionConfig},
+    properties::page_property_text,
+};
+use notion::models::{properties::PropertyValue, Page};
+use regex::Regex;
+use serde_json::Value;
+
+// what the first matching page rule does with a page
+pub fn page_action(config: &ExportConfig, page: &Page) -> Option<RedactionAction> {
+    let redaction = config.redaction.as_ref()?;
+    redaction.pages.iter().find_map(|rule| {
+        let text = page_property_text(page, &rule.property)?;
+        // multi selects come joined by commas
+        text.split(", ")
+            .any(|value| value.eq_ignore_ascii_case(&rule.value))
+            .then_some(rule.action)
+    })
+}
+
+// only the title is left
+pub fn without_properties(page: &Page) -> Page {
+    let mut page = page.clone();
+    page.properties
+        .properties
+        .retain(|_, value| matches!(value, PropertyValue::Title { .. }));
+    page
+}
+
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    replacement: String,
+}
+
+impl Redactor {
+    // None without patterns, so callers skip the work
+    pub fn new(config: &ExportConfig) -> anyhow::Result<Option<Self>> {
+        let Some(RedactionConfig {
+            patterns,
+            replacement,
+            ..
+        }) = &config.redaction
+        else {
+            return Ok(None);
+        };
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            patterns: patterns
+                .iter()
+                .map(|pattern| Regex::new(pattern))
+                .collect::<Result<_, _>>()?,
+            replacement: replacement.clone(),
+        }))
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut redacted = text.to_owned();
+        for pattern in &self.patterns {
+            // `$` in the replacement is meant literally
+            redacted = pattern
+                .replace_all(&redacted, regex::NoExpand(&self.replacement))
+                .into_owned();
+        }
+        redacted
+    }
+
+    // strings only, replacing inside the serialized text could break its quoting
+    pub fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact(text),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact_json(value)),
+            Value::Object(object) => object
+                .values_mut()
+                .for_each(|value| self.redact_json(value)),
+            _ => {}
+        }
+    }
+
+    pub fn redact_yaml(&self, value: &mut serde_yaml::Value) {
+        match value {
+            serde_yaml::Value::String(text) => *text = self.redact(text),
+            serde_yaml::Value::Sequence(values) => {
+                values.iter_mut().for_each(|value| self.redact_yaml(value))
+            }
+            serde_yaml::Value::Mapping(mapping) => mapping
+                .values_mut()
+                .for_each(|value| self.redact_yaml(value)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn redactor() -> Redactor {
+        let config = ExportConfig {
+            redaction: Some(RedactionConfig {
+                patterns: vec![String::from(r"\b\d{3}-\d{2}-\d{4}\b")],
+                replacement: String::from("[REDACTED]"),
+                pages: vec![],
+            }),
+            ..Default::default()
+        };
+        Redactor::new(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn replaces_every_match() {
+        assert_eq!(
+            redactor().redact("ssn 123-45-6789 and 987-65-4321"),
+            "ssn [REDACTED] and [REDACTED]"
+        );
+    }
+
+    #[test]
+    fn json_keeps_its_structure() {
+        let mut value = json!({ "rich_text": [{ "plain_text": "call 123-45-6789" }], "n": 1 });
+        redactor().redact_json(&mut value);
+        assert_eq!(
+            value,
+            json!({ "rich_text": [{ "plain_text": "call [REDACTED]" }], "n": 1 })
+        );
+    }
+}
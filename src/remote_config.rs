@@ -0,0 +1,67 @@
+use crate::{
+    api::NotionClient,
+    blocks::{fetch_block_tree, walk_blocks},
+    configuration::{ExportConfig, Flavor},
+    ids::normalize_id,
+    rich_text::plain_text,
+};
+use anyhow::Context;
+use notion::{
+    ids::{BlockId, PageId},
+    models::block::Block,
+};
+use serde::Deserialize;
+use std::str::FromStr;
+
+// export rules maintained on a notion page as yaml code blocks
+// anything that is set replaces the local value
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RemoteExportConfig {
+    #[serde(default)]
+    pub roots: Option<Vec<String>>,
+    #[serde(default)]
+    pub excludes: Option<Vec<String>>,
+    #[serde(default)]
+    pub flavor: Option<Flavor>,
+}
+
+impl RemoteExportConfig {
+    pub fn apply(&self, config: &mut ExportConfig) {
+        if let Some(roots) = &self.roots {
+            config.roots = roots.clone();
+        }
+        if let Some(excludes) = &self.excludes {
+            config.excludes = excludes.clone();
+        }
+        if let Some(flavor) = self.flavor {
+            config.flavor = flavor;
+        }
+    }
+}
+
+pub async fn load_remote_config(
+    notion_client: &NotionClient,
+    page_id: &str,
+) -> anyhow::Result<RemoteExportConfig> {
+    let page_id = PageId::from_str(&normalize_id(page_id))
+        .with_context(|| format!("invalid config page id {page_id}"))?;
+    let block_id: BlockId = page_id.into();
+    let blocks = fetch_block_tree(notion_client, block_id)
+        .await
+        .context("failed to read config page")?;
+
+    // several code blocks are joined so the rules can be split up between prose
+    let mut yaml = String::new();
+    walk_blocks(&blocks, &mut |block| {
+        if let Block::Code { code, .. } = block {
+            if format!("{:?}", code.language).to_lowercase() == "yaml" {
+                yaml.push_str(&plain_text(&code.rich_text));
+                yaml.push('\n');
+            }
+        }
+    });
+    if yaml.trim().is_empty() {
+        return Ok(RemoteExportConfig::default());
+    }
+    serde_yaml::from_str(&yaml).context("failed to parse yaml on config page")
+}
@@ -0,0 +1,282 @@
+use crate::{
+    configuration::{AppConfig, ReportFormat},
+    manifest::{ExportManifest, ManifestEntry},
+    state::{self, ExportState},
+    stats,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    io::{BufRead, Write as _},
+    path::Path,
+};
+
+const REPORT_FILE_NAME: &str = "export-report";
+const HISTORY_FILE_NAME: &str = "report-history.jsonl";
+
+// one line per full run so the growth of the workspace can be followed over time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub generated_at: DateTime<Utc>,
+    pub pages: usize,
+    pub words: usize,
+    // downloaded attachments, each file counted once
+    pub attachments_bytes: u64,
+    pub added: usize,
+    pub modified: usize,
+    pub removed: usize,
+}
+
+impl HistoryEntry {
+    pub fn new(manifest: &ExportManifest, diff: &ManifestDiff, export_state: &ExportState) -> Self {
+        let attachment_paths: HashSet<_> = export_state
+            .attachments
+            .values()
+            .map(|attachment| &attachment.path)
+            .collect();
+        Self {
+            generated_at: manifest.generated_at,
+            pages: manifest.pages.len(),
+            words: manifest.pages.values().map(|entry| entry.words).sum(),
+            attachments_bytes: attachment_paths
+                .into_iter()
+                .filter_map(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len())
+                .sum(),
+            added: diff.added.len(),
+            modified: diff.modified.len(),
+            removed: diff.removed.len(),
+        }
+    }
+}
+
+pub fn append_history(output_dir: &Path, entry: &HistoryEntry) -> anyhow::Result<()> {
+    let state_dir = state::state_dir(output_dir);
+    std::fs::create_dir_all(&state_dir)?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(state_dir.join(HISTORY_FILE_NAME))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+fn load_history(output_dir: &Path) -> anyhow::Result<Vec<HistoryEntry>> {
+    let path = state::state_dir(output_dir).join(HISTORY_FILE_NAME);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let file = std::io::BufReader::new(std::fs::File::open(&path)?);
+    let mut entries = vec![];
+    for line in file.lines() {
+        let line = line?;
+        // a run killed halfway through the write leaves a broken last line
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(error) => eprintln!("Skipping unreadable line in {} {error}", path.display()),
+        }
+    }
+    Ok(entries)
+}
+
+// the last runs of every job with the change since the run before
+pub fn print_history(config: &AppConfig, last: usize) -> anyhow::Result<()> {
+    for job in config.export_jobs() {
+        for output_dir in stats::job_output_dirs(config, &job) {
+            let history = load_history(&output_dir)?;
+            println!(
+                "Job {} ({}): {} runs",
+                job.name,
+                output_dir.display(),
+                history.len()
+            );
+            let start = history.len().saturating_sub(last);
+            println!(
+                "  {:<20} {:>14} {:>18} {:>20} {:>7} {:>8} {:>7}",
+                "run", "pages", "words", "attachments", "added", "modified", "removed"
+            );
+            for (index, entry) in history.iter().enumerate().skip(start) {
+                let previous = index.checked_sub(1).map(|index| &history[index]);
+                let delta = |current: u64, previous_value: Option<u64>| match previous_value {
+                    Some(previous_value) => {
+                        format!("{current} ({:+})", current as i64 - previous_value as i64)
+                    }
+                    None => current.to_string(),
+                };
+                println!(
+                    "  {:<20} {:>14} {:>18} {:>20} {:>7} {:>8} {:>7}",
+                    entry.generated_at.format("%Y-%m-%d %H:%M"),
+                    delta(entry.pages as u64, previous.map(|entry| entry.pages as u64)),
+                    delta(entry.words as u64, previous.map(|entry| entry.words as u64)),
+                    delta(
+                        entry.attachments_bytes,
+                        previous.map(|entry| entry.attachments_bytes)
+                    ),
+                    entry.added,
+                    entry.modified,
+                    entry.removed
+                );
+            }
+            if let (Some(first), Some(latest)) = (history.get(start), history.last()) {
+                let days = (latest.generated_at - first.generated_at).num_days();
+                println!(
+                    "  {:+} pages and {:+} words over {days} days",
+                    latest.pages as i64 - first.pages as i64,
+                    latest.words as i64 - first.words as i64
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// what changed between two manifests, meant for a weekly look at the export
+#[derive(Debug, Default)]
+pub struct ManifestDiff {
+    pub added: Vec<ManifestEntry>,
+    pub removed: Vec<ManifestEntry>,
+    // with the change in word count
+    pub modified: Vec<(ManifestEntry, i64)>,
+}
+
+pub fn compare(previous: Option<&ExportManifest>, current: &ExportManifest) -> ManifestDiff {
+    let mut diff = ManifestDiff::default();
+    for (page_id, entry) in &current.pages {
+        match previous.and_then(|previous| previous.pages.get(page_id)) {
+            None => diff.added.push(entry.clone()),
+            Some(previous_entry) if previous_entry.sha256 != entry.sha256 => {
+                let delta = entry.words as i64 - previous_entry.words as i64;
+                diff.modified.push((entry.clone(), delta));
+            }
+            Some(_) => {}
+        }
+    }
+    if let Some(previous) = previous {
+        for (page_id, entry) in &previous.pages {
+            if !current.pages.contains_key(page_id) {
+                diff.removed.push(entry.clone());
+            }
+        }
+    }
+    diff.added.sort_by(|a, b| a.title.cmp(&b.title));
+    diff.removed.sort_by(|a, b| a.title.cmp(&b.title));
+    // biggest changes first
+    diff.modified.sort_by(|(a, a_delta), (b, b_delta)| {
+        b_delta
+            .abs()
+            .cmp(&a_delta.abs())
+            .then(a.title.cmp(&b.title))
+    });
+    diff
+}
+
+pub fn write_report(
+    output_dir: &Path,
+    format: ReportFormat,
+    diff: &ManifestDiff,
+) -> anyhow::Result<()> {
+    let (extension, content) = match format {
+        ReportFormat::Markdown => ("md", render_markdown(diff)),
+        ReportFormat::Html => ("html", render_html(diff)),
+    };
+    let path = output_dir.join(REPORT_FILE_NAME).with_extension(extension);
+    std::fs::create_dir_all(output_dir)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn render_markdown(diff: &ManifestDiff) -> String {
+    let mut report = format!(
+        "# Export report\n\n{} new, {} modified, {} deleted\n",
+        diff.added.len(),
+        diff.modified.len(),
+        diff.removed.len()
+    );
+    let sections = [("New pages", &diff.added), ("Deleted pages", &diff.removed)];
+    for (heading, entries) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+        let _ = write!(report, "\n## {heading}\n\n");
+        for entry in entries {
+            let _ = writeln!(
+                report,
+                "- {} ({} words) `{}`",
+                entry.title, entry.words, entry.path
+            );
+        }
+    }
+    if !diff.modified.is_empty() {
+        report.push_str("\n## Modified pages\n\n");
+        for (entry, delta) in &diff.modified {
+            let _ = writeln!(
+                report,
+                "- {} ({delta:+} words) `{}`",
+                entry.title, entry.path
+            );
+        }
+    }
+    report
+}
+
+fn render_html(diff: &ManifestDiff) -> String {
+    let mut report = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Export report</title>\n</head>\n<body>\n<h1>Export report</h1>\n",
+    );
+    let _ = writeln!(
+        report,
+        "<p>{} new, {} modified, {} deleted</p>",
+        diff.added.len(),
+        diff.modified.len(),
+        diff.removed.len()
+    );
+    let sections = [("New pages", &diff.added), ("Deleted pages", &diff.removed)];
+    for (heading, entries) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+        let _ = write!(
+            report,
+            "<h2>{heading}</h2>\n<table>\n{}",
+            table_header("Words")
+        );
+        for entry in entries {
+            let _ = writeln!(
+                report,
+                "<tr><td>{}</td><td>{}</td><td><code>{}</code></td></tr>",
+                escape_html(&entry.title),
+                entry.words,
+                escape_html(&entry.path)
+            );
+        }
+        report.push_str("</table>\n");
+    }
+    if !diff.modified.is_empty() {
+        report.push_str("<h2>Modified pages</h2>\n<table>\n");
+        report.push_str(&table_header("Word change"));
+        for (entry, delta) in &diff.modified {
+            let _ = writeln!(
+                report,
+                "<tr><td>{}</td><td>{delta:+}</td><td><code>{}</code></td></tr>",
+                escape_html(&entry.title),
+                escape_html(&entry.path)
+            );
+        }
+        report.push_str("</table>\n");
+    }
+    report.push_str("</body>\n</html>\n");
+    report
+}
+
+fn table_header(words_column: &str) -> String {
+    format!("<tr><th>Page</th><th>{words_column}</th><th>Path</th></tr>\n")
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
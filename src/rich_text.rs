@@ -0,0 +1,438 @@
+use crate::{
+    configuration::{ExportConfig, TextColorStyle, UnderlineStyle},
+    escape,
+};
+use notion::models::text::{Annotations, RichText, TextColor};
+
+// formatting that can span several runs, outermost first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Color(TextColor),
+    // obsidian has a single highlight color, every background color maps to it
+    Highlight,
+    Underline,
+    Bold,
+    Italic,
+    Strikethrough,
+}
+
+// where a piece of rich text points, decided by the renderer since it knows the other notes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InlineLink {
+    // `[text](url)`
+    Url(String),
+    // markdown written instead of the text, like a wikilink for a page mention
+    Replace(String),
+}
+
+// what the renderer knows beyond the text itself, like the other notes
+pub trait InlineContext {
+    fn link(&self, text: &RichText) -> Option<InlineLink>;
+    // markdown replacing plain text, for glossary terms
+    fn expand(&self, text: &str) -> Option<String>;
+}
+
+// rich text without any markup, for places where formatting can't be represented
+pub fn plain_text(rich_text: &[RichText]) -> String {
+    rich_text.iter().map(|text| text.plain_text()).collect()
+}
+
+// the same for rich text still in its json form, like in comments or raw blocks
+pub fn raw_plain_text(rich_text: &serde_json::Value) -> String {
+    rich_text
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|text| text["plain_text"].as_str())
+        .collect()
+}
+
+// a piece of text with one set of annotations, neighbours with the same ones are merged
+struct Run {
+    text: String,
+    marks: Vec<Mark>,
+    code: bool,
+    // an inline equation, the text is its tex expression
+    equation: bool,
+    link: Option<InlineLink>,
+}
+
+// notion splits text into runs wherever any annotation changes
+// markdown delimiters can't simply wrap every run, `**a****b**` doesn't parse
+// so marks stay open across runs that share them and whitespace is kept outside the delimiters
+pub fn render_rich_text(
+    config: &ExportConfig,
+    rich_text: &[RichText],
+    inline: &dyn InlineContext,
+) -> String {
+    let runs = merge_runs(config, rich_text, inline);
+    let mut output = String::new();
+    let mut open: Vec<Mark> = vec![];
+    // trailing whitespace of the previous run, written once we know which marks close before it
+    let mut pending_space = String::new();
+    for run in runs {
+        let (leading, core, trailing) = split_whitespace(&run.text);
+        if core.is_empty() {
+            pending_space.push_str(&run.text);
+            continue;
+        }
+        // marks are closed down to the first one the next run doesn't share
+        let keep = open
+            .iter()
+            .zip(&run.marks)
+            .take_while(|(open, wanted)| open == wanted)
+            .count();
+        while open.len() > keep {
+            if let Some(mark) = open.pop() {
+                output.push_str(&close_delimiter(mark));
+            }
+        }
+        output.push_str(&pending_space);
+        output.push_str(leading);
+        pending_space.clear();
+        for mark in &run.marks[keep..] {
+            output.push_str(&open_delimiter(*mark));
+            open.push(*mark);
+        }
+        // math can't start or end with a space, `$x$` renders but `$ x $` doesn't
+        let text = if run.equation {
+            format!("${core}$")
+        } else if run.code {
+            code_span(core)
+        } else {
+            core.to_owned()
+        };
+        match &run.link {
+            // code spans bind tighter than the brackets around them, only plain text needs escaping
+            Some(InlineLink::Url(url)) => {
+                let text = if run.code || run.equation {
+                    text
+                } else {
+                    escape::link_text(&text)
+                };
+                output.push_str(&format!("[{text}]({})", escape::link_destination(url)))
+            }
+            Some(InlineLink::Replace(markdown)) => output.push_str(markdown),
+            None if run.code || run.equation => output.push_str(&text),
+            None => output.push_str(&inline.expand(&text).unwrap_or(text)),
+        }
+        pending_space.push_str(trailing);
+    }
+    while let Some(mark) = open.pop() {
+        output.push_str(&close_delimiter(mark));
+    }
+    output.push_str(&pending_space);
+    output
+}
+
+fn merge_runs(
+    config: &ExportConfig,
+    rich_text: &[RichText],
+    inline: &dyn InlineContext,
+) -> Vec<Run> {
+    let mut runs: Vec<Run> = vec![];
+    for text in rich_text {
+        let annotations = annotations(text);
+        let marks = marks(config, &annotations);
+        let code = annotations.code.unwrap_or(false);
+        if let RichText::Equation { equation, .. } = text {
+            // every equation is a formula of its own
+            runs.push(Run {
+                text: equation.expression.clone(),
+                marks,
+                code: false,
+                equation: true,
+                link: None,
+            });
+            continue;
+        }
+        let link = inline.link(text);
+        // replaced text stands for one mention, two mentions in a row stay two links
+        let mergeable = !matches!(link, Some(InlineLink::Replace(_)));
+        match runs.last_mut() {
+            Some(last)
+                if mergeable
+                    && !last.equation
+                    && last.marks == marks
+                    && last.code == code
+                    && last.link == link =>
+            {
+                last.text.push_str(text.plain_text())
+            }
+            _ => runs.push(Run {
+                text: text.plain_text().to_owned(),
+                marks,
+                code,
+                equation: false,
+                link,
+            }),
+        }
+    }
+    runs
+}
+
+fn annotations(text: &RichText) -> Annotations {
+    let common = match text {
+        RichText::Text { rich_text, .. }
+        | RichText::Mention { rich_text, .. }
+        | RichText::Equation { rich_text, .. } => rich_text,
+    };
+    common.annotations.clone().unwrap_or_default()
+}
+
+fn marks(config: &ExportConfig, annotations: &Annotations) -> Vec<Mark> {
+    let mut marks = vec![];
+    match (config.text_color, annotations.color) {
+        (TextColorStyle::Html, Some(color)) if color != TextColor::Default => {
+            marks.push(Mark::Color(color))
+        }
+        (TextColorStyle::Highlight, Some(color)) if background_color(color).is_some() => {
+            marks.push(Mark::Highlight)
+        }
+        _ => {}
+    }
+    if annotations.underline.unwrap_or(false) && config.underline == UnderlineStyle::Html {
+        marks.push(Mark::Underline);
+    }
+    if annotations.bold.unwrap_or(false) {
+        marks.push(Mark::Bold);
+    }
+    if annotations.italic.unwrap_or(false) {
+        marks.push(Mark::Italic);
+    }
+    if annotations.strikethrough.unwrap_or(false) {
+        marks.push(Mark::Strikethrough);
+    }
+    marks
+}
+
+fn open_delimiter(mark: Mark) -> String {
+    match mark {
+        Mark::Color(color) => match background_color(color) {
+            Some(background) => format!("<span style=\"background-color: {background}\">"),
+            None => format!("<span style=\"color: {}\">", text_color(color)),
+        },
+        Mark::Highlight => String::from("=="),
+        Mark::Underline => String::from("<u>"),
+        Mark::Bold => String::from("**"),
+        Mark::Italic => String::from("*"),
+        Mark::Strikethrough => String::from("~~"),
+    }
+}
+
+fn close_delimiter(mark: Mark) -> String {
+    match mark {
+        Mark::Color(_) => String::from("</span>"),
+        Mark::Highlight => String::from("=="),
+        Mark::Underline => String::from("</u>"),
+        Mark::Bold => String::from("**"),
+        Mark::Italic => String::from("*"),
+        Mark::Strikethrough => String::from("~~"),
+    }
+}
+
+fn background_color(color: TextColor) -> Option<&'static str> {
+    match color {
+        TextColor::GrayBackground => Some("lightgray"),
+        TextColor::BrownBackground => Some("tan"),
+        TextColor::OrangeBackground => Some("moccasin"),
+        TextColor::YellowBackground => Some("lightyellow"),
+        TextColor::GreenBackground => Some("palegreen"),
+        TextColor::BlueBackground => Some("lightblue"),
+        TextColor::PurpleBackground => Some("plum"),
+        TextColor::PinkBackground => Some("pink"),
+        TextColor::RedBackground => Some("mistyrose"),
+        _ => None,
+    }
+}
+
+fn text_color(color: TextColor) -> &'static str {
+    match color {
+        TextColor::Gray => "gray",
+        TextColor::Brown => "brown",
+        TextColor::Orange => "orange",
+        TextColor::Yellow => "goldenrod",
+        TextColor::Green => "green",
+        TextColor::Blue => "blue",
+        TextColor::Purple => "purple",
+        TextColor::Pink => "hotpink",
+        TextColor::Red => "red",
+        _ => "inherit",
+    }
+}
+
+// backticks inside code need a longer fence, padded when the code starts or ends with one
+fn code_span(code: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for character in code.chars() {
+        if character == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    let fence = "`".repeat(longest + 1);
+    if code.starts_with('`') || code.ends_with('`') {
+        format!("{fence} {code} {fence}")
+    } else {
+        format!("{fence}{code}{fence}")
+    }
+}
+
+// delimiters next to whitespace don't count as emphasis
+fn split_whitespace(text: &str) -> (&str, &str, &str) {
+    let core_start = text.len() - text.trim_start().len();
+    let core_end = text.trim_end().len().max(core_start);
+    (
+        &text[..core_start],
+        &text[core_start..core_end],
+        &text[core_end..],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(text: &str, annotations: serde_json::Value) -> RichText {
+        serde_json::from_value(json!({
+            "type": "text",
+            "plain_text": text,
+            "href": null,
+            "annotations": annotations,
+            "text": { "content": text, "link": null },
+        }))
+        .unwrap()
+    }
+
+    struct Links<F: Fn(&RichText) -> Option<InlineLink>>(F);
+
+    impl<F: Fn(&RichText) -> Option<InlineLink>> InlineContext for Links<F> {
+        fn link(&self, text: &RichText) -> Option<InlineLink> {
+            (self.0)(text)
+        }
+
+        fn expand(&self, _text: &str) -> Option<String> {
+            None
+        }
+    }
+
+    fn render(runs: &[RichText]) -> String {
+        render_rich_text(&ExportConfig::default(), runs, &Links(|_| None))
+    }
+
+    #[test]
+    fn adjacent_runs_share_delimiters() {
+        let runs = [
+            run("bold ", json!({ "bold": true })),
+            run("and italic", json!({ "bold": true, "italic": true })),
+            run(" plain", json!({})),
+        ];
+        assert_eq!(render(&runs), "**bold *and italic*** plain");
+    }
+
+    #[test]
+    fn whitespace_stays_outside_delimiters() {
+        let runs = [
+            run("a", json!({})),
+            run(" struck ", json!({ "strikethrough": true })),
+            run("b", json!({})),
+        ];
+        assert_eq!(render(&runs), "a ~~struck~~ b");
+    }
+
+    #[test]
+    fn code_with_backticks_gets_a_longer_fence() {
+        let runs = [run("a `tick`", json!({ "code": true }))];
+        assert_eq!(render(&runs), "`` a `tick` ``");
+    }
+
+    #[test]
+    fn inline_equations_become_math() {
+        let equation = serde_json::from_value(json!({
+            "type": "equation",
+            "plain_text": "E = mc^2 ",
+            "href": null,
+            "annotations": {},
+            "equation": { "expression": "E = mc^2 " },
+        }))
+        .unwrap();
+        let runs = [run("energy ", json!({})), equation, run("holds", json!({}))];
+        assert_eq!(render(&runs), "energy $E = mc^2$ holds");
+    }
+
+    #[test]
+    fn links_sit_inside_the_formatting() {
+        let runs = [
+            run("see ", json!({})),
+            run("the docs", json!({ "bold": true })),
+        ];
+        let link = |text: &RichText| {
+            (text.plain_text() == "the docs")
+                .then(|| InlineLink::Url(String::from("https://example.com")))
+        };
+        assert_eq!(
+            render_rich_text(&ExportConfig::default(), &runs, &Links(link)),
+            "see **[the docs](https://example.com)**"
+        );
+    }
+
+    #[test]
+    fn link_text_and_destinations_are_escaped() {
+        let runs = [run("see [1]", json!({}))];
+        let link = |_: &RichText| Some(InlineLink::Url(String::from("https://example.com/a b")));
+        assert_eq!(
+            render_rich_text(&ExportConfig::default(), &runs, &Links(link)),
+            "[see \\[1\\]](<https://example.com/a b>)"
+        );
+    }
+
+    struct Expands;
+
+    impl InlineContext for Expands {
+        fn link(&self, text: &RichText) -> Option<InlineLink> {
+            (text.plain_text() == "linked term")
+                .then(|| InlineLink::Url(String::from("https://example.com")))
+        }
+
+        fn expand(&self, text: &str) -> Option<String> {
+            text.contains("term")
+                .then(|| text.replace("term", "[[term]]"))
+        }
+    }
+
+    #[test]
+    fn code_and_links_are_not_expanded() {
+        let runs = [
+            run("a term ", json!({})),
+            run("code term", json!({ "code": true })),
+            run(" ", json!({})),
+            run("linked term", json!({})),
+        ];
+        assert_eq!(
+            render_rich_text(&ExportConfig::default(), &runs, &Expands),
+            "a [[term]] `code term` [linked term](https://example.com)"
+        );
+    }
+
+    #[test]
+    fn underline_and_color_follow_the_config() {
+        let runs = [run(
+            "marked",
+            json!({ "underline": true, "color": "yellow_background" }),
+        )];
+        assert_eq!(render(&runs), "marked");
+        let config = ExportConfig {
+            underline: UnderlineStyle::Html,
+            text_color: TextColorStyle::Highlight,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_rich_text(&config, &runs, &Links(|_| None)),
+            "==<u>marked</u>=="
+        );
+    }
+}
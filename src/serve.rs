@@ -0,0 +1,317 @@
+use crate::{
+    api::Source,
+    changes::{ChangeSet, PageFailure},
+    configuration::AppConfig,
+    daemon::{ConfigLoader, ConfigWatcher, HangupSignal},
+    export::{self, RunOptions},
+    ids::same_id,
+    report::escape_html,
+    watch,
+    workspace::Workspace,
+};
+use axum::{
+    extract::State,
+    response::{Html, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{collections::VecDeque, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, Mutex};
+
+// runs listed on the status page, older ones are dropped
+const KEPT_RUNS: usize = 20;
+
+// what started a run
+#[derive(Debug, Clone)]
+enum Trigger {
+    Interval,
+    // the sync now button of everything or of a single root
+    Manual { root: Option<String> },
+    // SIGHUP
+    Signal,
+    // the config file was edited
+    ConfigFile,
+}
+
+impl Trigger {
+    // full syncs asked for by someone pick up config changes first
+    fn reloads_config(&self) -> bool {
+        matches!(
+            self,
+            Trigger::Manual { root: None } | Trigger::Signal | Trigger::ConfigFile
+        )
+    }
+}
+
+struct RunRecord {
+    started_at: DateTime<Utc>,
+    trigger: Trigger,
+    result: Result<ChangeSet, String>,
+}
+
+struct ServeState {
+    // changes when the config is reloaded
+    roots: Mutex<Vec<String>>,
+    runs: Mutex<VecDeque<RunRecord>>,
+    // titles of the roots once a search found them
+    root_titles: Mutex<Vec<(String, String)>>,
+    sync_requests: mpsc::Sender<Trigger>,
+}
+
+#[derive(Deserialize)]
+struct SyncForm {
+    #[serde(default)]
+    root: Option<String>,
+}
+
+// watch mode with a small web page showing recent runs and buttons to sync right away
+// there is no authentication, keep it on localhost or behind a proxy that has some
+pub async fn serve(
+    config_loader: &ConfigLoader,
+    mut sources: Vec<Source>,
+    mut config: AppConfig,
+    interval_override: Option<u64>,
+    listen: SocketAddr,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    let (sync_requests, mut pending) = mpsc::channel(8);
+    let state = Arc::new(ServeState {
+        roots: Mutex::new(config_roots(&config)),
+        runs: Mutex::new(VecDeque::new()),
+        root_titles: Mutex::new(vec![]),
+        sync_requests,
+    });
+
+    let app = Router::new()
+        .route("/", get(status_page))
+        .route("/sync", post(sync_now))
+        .with_state(state.clone());
+    let server = axum::Server::try_bind(&listen)?.serve(app.into_make_service());
+    println!("Serving status page on http://{listen}");
+    tokio::spawn(async move {
+        if let Err(error) = server.await {
+            eprintln!("Status page server failed with error {error:?}");
+        }
+    });
+
+    let mut hangup = HangupSignal::new()?;
+    let mut config_watcher = ConfigWatcher::new();
+    let mut trigger = Trigger::Interval;
+    loop {
+        if trigger.reloads_config() {
+            watch::reload(config_loader, &mut config, &mut sources);
+            *state.roots.lock().await = config_roots(&config);
+        }
+        let started_at = Utc::now();
+        let result = run_cycle(&sources, &config, &trigger, options, &state).await;
+        let mut runs = state.runs.lock().await;
+        runs.push_front(RunRecord {
+            started_at,
+            trigger,
+            result: result.map_err(|error| format!("{error:#}")),
+        });
+        runs.truncate(KEPT_RUNS);
+        drop(runs);
+
+        let interval =
+            Duration::from_secs(interval_override.unwrap_or(config.watch.interval_seconds));
+        trigger = tokio::select! {
+            _ = tokio::time::sleep(interval) => Trigger::Interval,
+            Some(requested) = pending.recv() => requested,
+            _ = hangup.recv() => {
+                println!("Received SIGHUP, reloading the config and syncing");
+                Trigger::Signal
+            }
+            _ = config_watcher.changed() => {
+                println!("Config file changed, reloading it and syncing");
+                Trigger::ConfigFile
+            }
+        };
+    }
+}
+
+fn config_roots(config: &AppConfig) -> Vec<String> {
+    let mut roots: Vec<String> = vec![];
+    for job in config.export_jobs() {
+        for root in job.export.roots {
+            if !roots.iter().any(|known| same_id(known, &root)) {
+                roots.push(root);
+            }
+        }
+    }
+    roots
+}
+
+async fn run_cycle(
+    sources: &[Source],
+    config: &AppConfig,
+    trigger: &Trigger,
+    options: RunOptions,
+    state: &ServeState,
+) -> anyhow::Result<ChangeSet> {
+    let workspaces = export::search_sources(sources, config).await?;
+    let roots = state.roots.lock().await.clone();
+    *state.root_titles.lock().await = root_titles(&roots, &workspaces);
+    match trigger {
+        Trigger::Manual { root: Some(root) } => {
+            // only the pages below the root, everything else stays as the last run left it
+            let workspaces: Vec<Workspace> = workspaces
+                .iter()
+                .map(|workspace| root_workspace(workspace, root, &config.export.excludes))
+                .collect();
+            let options = RunOptions {
+                partial: true,
+                ..options
+            };
+            export::export_workspaces(sources, &workspaces, config, options).await
+        }
+        _ => {
+            let changes = export::export_workspaces(sources, &workspaces, config, options).await?;
+            if changes.is_empty() {
+                println!("Nothing changed");
+            } else {
+                watch::report_changes(&config.watch, &workspaces, &changes).await;
+            }
+            Ok(changes)
+        }
+    }
+}
+
+fn root_workspace(workspace: &Workspace, root: &str, excludes: &[String]) -> Workspace {
+    let mut subset = Workspace::default();
+    for page in workspace.pages_under_roots(&[root.to_owned()], excludes) {
+        subset.add_page(page.clone());
+    }
+    for database in &workspace.databases {
+        subset.add_database(database.clone());
+    }
+    subset
+}
+
+fn root_titles(roots: &[String], workspaces: &[Workspace]) -> Vec<(String, String)> {
+    roots
+        .iter()
+        .filter_map(|root| {
+            let title = workspaces.iter().find_map(|workspace| {
+                match (workspace.page(root), workspace.database(root)) {
+                    (Some(page), _) => page.title(),
+                    (None, Some(database)) => Some(database.title_plain_text()),
+                    (None, None) => None,
+                }
+            })?;
+            Some((root.clone(), title))
+        })
+        .collect()
+}
+
+async fn sync_now(State(state): State<Arc<ServeState>>, Form(form): Form<SyncForm>) -> Redirect {
+    let root = form.root.filter(|root| !root.is_empty());
+    // a full queue already has a run coming up
+    let _ = state.sync_requests.try_send(Trigger::Manual { root });
+    Redirect::to("/")
+}
+
+async fn status_page(State(state): State<Arc<ServeState>>) -> Html<String> {
+    let root_titles = state.root_titles.lock().await.clone();
+    let mut page = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"refresh\" content=\"30\">\n<title>notion-bridge</title>\n</head>\n<body>\n\
+         <h1>notion-bridge</h1>\n",
+    );
+
+    page.push_str("<h2>Sync now</h2>\n");
+    page.push_str(&sync_button(None, "Everything"));
+    let roots = state.roots.lock().await.clone();
+    for root in &roots {
+        let title = root_titles
+            .iter()
+            .find(|(id, _)| id == root)
+            .map(|(_, title)| title.as_str())
+            .unwrap_or(root);
+        page.push_str(&sync_button(Some(root), title));
+    }
+
+    let runs = state.runs.lock().await;
+    page.push_str("<h2>Recent runs</h2>\n");
+    if runs.is_empty() {
+        page.push_str("<p>The first run is still going.</p>\n");
+    } else {
+        page.push_str(
+            "<table>\n<tr><th>Started</th><th>Trigger</th><th>Added</th><th>Updated</th>\
+             <th>Removed</th><th>Result</th></tr>\n",
+        );
+        for run in runs.iter() {
+            page.push_str(&run_row(run, &root_titles));
+        }
+        page.push_str("</table>\n");
+    }
+
+    let failures: Vec<&PageFailure> = runs
+        .iter()
+        .find_map(|run| run.result.as_ref().ok())
+        .map(|changes| changes.failed.iter().collect())
+        .unwrap_or_default();
+    if !failures.is_empty() {
+        page.push_str("<h2>Failed pages of the last run</h2>\n<ul>\n");
+        for failure in failures {
+            page.push_str(&format!(
+                "<li>{}: {}</li>\n",
+                escape_html(&failure.title),
+                escape_html(&failure.error)
+            ));
+        }
+        page.push_str("</ul>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    Html(page)
+}
+
+fn sync_button(root: Option<&str>, label: &str) -> String {
+    format!(
+        "<form method=\"post\" action=\"/sync\"><input type=\"hidden\" name=\"root\" value=\"{}\">\
+         <button type=\"submit\">{}</button></form>\n",
+        escape_html(root.unwrap_or_default()),
+        escape_html(label)
+    )
+}
+
+fn run_row(run: &RunRecord, root_titles: &[(String, String)]) -> String {
+    let trigger = match &run.trigger {
+        Trigger::Interval => String::from("interval"),
+        Trigger::Manual { root: None } => String::from("manual"),
+        Trigger::Signal => String::from("SIGHUP"),
+        Trigger::ConfigFile => String::from("config change"),
+        Trigger::Manual { root: Some(root) } => {
+            let title = root_titles
+                .iter()
+                .find(|(id, _)| id == root)
+                .map(|(_, title)| title.as_str())
+                .unwrap_or(root);
+            format!("manual, {title}")
+        }
+    };
+    let started = run.started_at.format("%Y-%m-%d %H:%M:%S UTC");
+    match &run.result {
+        Ok(changes) => {
+            let result = if changes.failed.is_empty() {
+                String::from("ok")
+            } else {
+                format!("{} pages failed", changes.failed.len())
+            };
+            format!(
+                "<tr><td>{started}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{result}</td></tr>\n",
+                escape_html(&trigger),
+                changes.added.len(),
+                changes.updated.len(),
+                changes.removed.len()
+            )
+        }
+        Err(error) => format!(
+            "<tr><td>{started}</td><td>{}</td><td></td><td></td><td></td><td>{}</td></tr>\n",
+            escape_html(&trigger),
+            escape_html(error)
+        ),
+    }
+}
@@ -0,0 +1,40 @@
+// lowercase ascii-ish slug used for generated directory names
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for character in text.chars().flat_map(char::to_lowercase) {
+        if character.is_alphanumeric() {
+            slug.push(character);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "untitled".to_owned()
+    } else {
+        slug.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn slugs_are_usable_directory_names(text in any::<String>()) {
+            let slug = slugify(&text);
+            prop_assert!(!slug.is_empty());
+            prop_assert!(slug.chars().all(|character| character.is_alphanumeric() || character == '-'));
+            prop_assert!(!slug.starts_with('-') && !slug.ends_with('-'));
+            prop_assert!(!slug.contains("--"));
+        }
+
+        #[test]
+        fn slugs_are_stable(text in any::<String>()) {
+            let slug = slugify(&text);
+            prop_assert_eq!(slugify(&slug), slug);
+        }
+    }
+}
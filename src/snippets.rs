@@ -0,0 +1,145 @@
+use crate::{
+    blocks::walk_blocks, ids::normalize_id, redaction::Redactor, rich_text::plain_text,
+    slug::slugify,
+};
+use notion::models::block::Block;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+const CODE_DIR_NAME: &str = "code";
+// enough of the page id to tell pages with the same slug apart
+const SHORT_ID_LENGTH: usize = 8;
+
+pub fn code_dir(output_dir: &Path, page_title: &str, page_id: &str) -> PathBuf {
+    let page_id = normalize_id(page_id);
+    let short_id = &page_id[..page_id.len().min(SHORT_ID_LENGTH)];
+    output_dir
+        .join(CODE_DIR_NAME)
+        .join(format!("{}-{short_id}", slugify(page_title)))
+}
+
+// writes every code block of a page into its own numbered file
+// and returns the file for each block id
+// `previous` are the files the last run wrote for this page, only those are ever removed
+pub async fn extract_code_blocks(
+    blocks: &[Block],
+    code_dir: &Path,
+    previous: &[PathBuf],
+    redactor: Option<&Redactor>,
+) -> anyhow::Result<HashMap<String, PathBuf>> {
+    let mut snippets = vec![];
+    walk_blocks(blocks, &mut |block| {
+        if let Block::Code { common, code } = block {
+            let language = format!("{:?}", code.language).to_lowercase();
+            let mut content = plain_text(&code.rich_text);
+            if let Some(redactor) = redactor {
+                content = redactor.redact(&content);
+            }
+            snippets.push((common.id.to_string(), language, content));
+        }
+    });
+    write_snippets(snippets, code_dir, previous).await
+}
+
+async fn write_snippets(
+    snippets: Vec<(String, String, String)>,
+    code_dir: &Path,
+    previous: &[PathBuf],
+) -> anyhow::Result<HashMap<String, PathBuf>> {
+    let mut code_files = HashMap::new();
+    if !snippets.is_empty() {
+        tokio::fs::create_dir_all(code_dir).await?;
+    }
+    for (index, (block_id, language, content)) in snippets.into_iter().enumerate() {
+        let path = code_dir.join(format!("{}.{}", index + 1, language_extension(&language)));
+        // leave unchanged files alone so their modification time stays meaningful
+        let previous = tokio::fs::read_to_string(&path).await.ok();
+        if previous.as_deref() != Some(content.as_str()) {
+            tokio::fs::write(&path, &content).await?;
+        }
+        code_files.insert(block_id, path);
+    }
+
+    // drop files of code blocks that were removed from the page, or left behind by a rename
+    let current: HashSet<&PathBuf> = code_files.values().collect();
+    let mut emptied = HashSet::new();
+    for path in previous {
+        if current.contains(path) || !path.is_file() {
+            continue;
+        }
+        tokio::fs::remove_file(path).await?;
+        if let Some(dir) = path.parent() {
+            emptied.insert(dir.to_path_buf());
+        }
+    }
+    // only empty directories go, anything else in there isn't ours
+    for dir in emptied {
+        let _ = tokio::fs::remove_dir(dir).await;
+    }
+    Ok(code_files)
+}
+
+fn language_extension(language: &str) -> &str {
+    match language {
+        "rust" => "rs",
+        "python" => "py",
+        "bash" | "shell" => "sh",
+        "javascript" => "js",
+        "typescript" => "ts",
+        "c++" | "cpp" => "cpp",
+        "c#" | "csharp" => "cs",
+        "markdown" => "md",
+        "yaml" => "yaml",
+        "kotlin" => "kt",
+        "ruby" => "rb",
+        "haskell" => "hs",
+        "powershell" => "ps1",
+        "plaintext" | "plain text" => "txt",
+        "c" | "go" | "java" | "json" | "toml" | "html" | "css" | "sql" | "lua" | "swift"
+        | "scala" | "php" | "r" | "xml" => language,
+        _ => "txt",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(block_id: &str, content: &str) -> (String, String, String) {
+        (block_id.to_owned(), "rust".to_owned(), content.to_owned())
+    }
+
+    #[tokio::test]
+    async fn pages_with_the_same_title_keep_their_own_snippets() {
+        let output_dir = std::env::temp_dir().join(format!(
+            "notion-bridge-snippets-test-{}",
+            std::process::id()
+        ));
+        let first_dir = code_dir(&output_dir, "Notes", "0123abcd-4567-89ab-cdef-0123456789ab");
+        let second_dir = code_dir(&output_dir, "Notes", "fedcba98-4567-89ab-cdef-0123456789ab");
+        assert_ne!(first_dir, second_dir);
+
+        let first = write_snippets(vec![snippet("a", "fn main() {}")], &first_dir, &[])
+            .await
+            .unwrap();
+        let second = write_snippets(vec![snippet("b", "fn other() {}")], &second_dir, &[])
+            .await
+            .unwrap();
+        let second_files: Vec<PathBuf> = second.values().cloned().collect();
+
+        // the second page lost its code blocks, the first page's file stays
+        let second = write_snippets(vec![], &second_dir, &second_files)
+            .await
+            .unwrap();
+        assert!(second.is_empty());
+        assert!(!second_dir.exists());
+        assert_eq!(
+            std::fs::read_to_string(&first["a"]).unwrap(),
+            "fn main() {}"
+        );
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
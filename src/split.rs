@@ -0,0 +1,150 @@
+use crate::{configuration::ExportConfig, frontmatter::frontmatter_len, markdown::note_link};
+use std::path::{Path, PathBuf};
+
+// the index note keeps the frontmatter, the title and whatever comes before the first heading
+pub struct SplitNote {
+    pub index: String,
+    pub parts: Vec<(PathBuf, String)>,
+}
+
+// `Page.md` -> `Page (part 01).md`, next to the index note
+// unlikely to be the title of another page since parts are found and removed by name
+pub fn part_path(note_path: &Path, number: usize) -> PathBuf {
+    let stem = note_path.file_stem().unwrap_or_default().to_string_lossy();
+    note_path.with_file_name(format!("{stem} (part {number:02}).md"))
+}
+
+// None when the note is small enough or has no heading to split at
+pub fn split_note(
+    config: &ExportConfig,
+    note_path: &Path,
+    title: &str,
+    note: &str,
+) -> Option<SplitNote> {
+    let max_bytes = config.split_pages.as_ref()?.max_bytes;
+    if note.len() <= max_bytes {
+        return None;
+    }
+    // hugo notes have no title heading
+    let sections = sections(note, config.hugo.is_none());
+    if sections.len() < 2 {
+        return None;
+    }
+    let note_dir = note_path.parent().unwrap_or(Path::new(""));
+    let back_link = note_link(config, title, note_path, note_dir);
+    let mut index = sections[0].trim_end().to_owned();
+    index.push_str("\n\n");
+    let mut parts = vec![];
+    for (number, section) in sections[1..].iter().enumerate() {
+        let path = part_path(note_path, number + 1);
+        let heading = section
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("# "))
+            .unwrap_or_default();
+        let part_link = note_link(config, &format!("Part {}", number + 1), &path, note_dir);
+        index.push_str(&format!("- {part_link} {heading}\n"));
+        parts.push((path, format!("{back_link}\n\n{}\n", section.trim_end())));
+    }
+    Some(SplitNote { index, parts })
+}
+
+// the note and every part written for it, changes and word counts cover all of them
+pub fn with_parts(config: &ExportConfig, note_path: &Path, note: &str) -> String {
+    let mut content = note.to_owned();
+    for path in existing_parts(config, note_path) {
+        if let Ok(part) = std::fs::read_to_string(path) {
+            content.push_str(&part);
+        }
+    }
+    content
+}
+
+// parts on disk from the last time the note was written
+pub fn existing_parts(config: &ExportConfig, note_path: &Path) -> Vec<PathBuf> {
+    if config.split_pages.is_none() {
+        return vec![];
+    }
+    (1..)
+        .map(|number| part_path(note_path, number))
+        .take_while(|path| path.is_file())
+        .collect()
+}
+
+// parts left over from a longer version of the note or from before it moved
+pub fn remove_parts(config: &ExportConfig, note_path: &Path, from: usize) -> std::io::Result<()> {
+    if config.split_pages.is_none() {
+        return Ok(());
+    }
+    for number in from.. {
+        let path = part_path(note_path, number);
+        if !path.is_file() {
+            break;
+        }
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// split before every `# ` heading after the title, headings in code fences don't count
+fn sections(note: &str, has_title: bool) -> Vec<&str> {
+    let body_start = frontmatter_len(note);
+    let mut sections = vec![];
+    let mut section_start = 0;
+    let mut seen_title = !has_title;
+    let mut fence: Option<&str> = None;
+    let mut offset = body_start;
+    for line in note[body_start..].split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => {}
+            None if trimmed.starts_with("```") => fence = Some("```"),
+            None if trimmed.starts_with("~~~") => fence = Some("~~~"),
+            None if line.starts_with("# ") && !seen_title => seen_title = true,
+            None if line.starts_with("# ") => {
+                sections.push(&note[section_start..offset]);
+                section_start = offset;
+            }
+            None => {}
+        }
+        offset += line.len();
+    }
+    sections.push(&note[section_start..]);
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_at_top_level_headings_outside_code() {
+        let note = "---\naliases: [a]\n---\n# [Title](https://notion.so/x)\n\nintro\n\n# One\n\n```\n# not a heading\n```\n\n## Nested\n\n# Two\n\ntext\n";
+        assert_eq!(
+            sections(note, true),
+            vec![
+                "---\naliases: [a]\n---\n# [Title](https://notion.so/x)\n\nintro\n\n",
+                "# One\n\n```\n# not a heading\n```\n\n## Nested\n\n",
+                "# Two\n\ntext\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn index_links_every_part() {
+        let config = ExportConfig {
+            split_pages: Some(crate::configuration::SplitConfig { max_bytes: 10 }),
+            output_dir: PathBuf::from("out"),
+            ..Default::default()
+        };
+        let note = "# Title\n\nintro\n\n# One\n\nfirst\n\n# Two\n\nsecond\n";
+        let split = split_note(&config, Path::new("out/Page.md"), "Title", note).unwrap();
+        assert_eq!(
+            split.index,
+            "# Title\n\nintro\n\n- [[Page (part 01)|Part 1]] One\n- [[Page (part 02)|Part 2]] Two\n"
+        );
+        assert_eq!(split.parts[1].0, PathBuf::from("out/Page (part 02).md"));
+        assert_eq!(split.parts[1].1, "[[Page|Title]]\n\n# Two\n\nsecond\n");
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{blocks::LinkTargets, database_notes::ChildDatabase, ids::normalize_id};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use notion::ids::PageId;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+const STATE_DIR_NAME: &str = ".notion-bridge";
+const STATE_FILE_NAME: &str = "state.json";
+
+// bump this and add a migration step whenever the layout of the state file changes
+// 1: no version field, pages keyed by dashed ids
+// 2: pages keyed by normalized ids
+pub const STATE_VERSION: u64 = 2;
+
+// bookkeeping that has to survive between export runs
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ExportState {
+    // keyed by normalized page id
+    #[serde(default)]
+    pub pages: HashMap<String, PageState>,
+    // downloaded attachments keyed by their url without the signature
+    #[serde(default)]
+    pub attachments: HashMap<String, AttachmentState>,
+    // export settings of the last full run, edit times only tell something while these stay the same
+    #[serde(default)]
+    pub settings_hash: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AttachmentState {
+    pub path: PathBuf,
+    // hash of the file content, identical files are only stored once
+    pub sha256: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PageState {
+    pub title: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub output_path: Option<PathBuf>,
+    // hash of the normalized page content the note was last rendered from
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    // edit time of the page when its note was last written completely
+    #[serde(default)]
+    pub last_edited_time: Option<DateTime<Utc>>,
+    // what the page showed last time, followed again when the page itself is skipped
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub child_databases: Vec<ChildDatabase>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub child_pages: Vec<String>,
+    // what the note links to and a hash of the titles and paths the links were written with
+    #[serde(default)]
+    pub link_targets: LinkTargets,
+    #[serde(default)]
+    pub links_hash: Option<String>,
+    // extracted code blocks, the only files a later run removes for this page
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub code_files: Vec<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct VersionedState<'a> {
+    version: u64,
+    #[serde(flatten)]
+    state: &'a ExportState,
+}
+
+pub fn state_dir(output_dir: &Path) -> PathBuf {
+    output_dir.join(STATE_DIR_NAME)
+}
+
+fn state_file_path(output_dir: &Path) -> PathBuf {
+    state_dir(output_dir).join(STATE_FILE_NAME)
+}
+
+impl ExportState {
+    pub fn load(output_dir: &Path) -> Result<Self> {
+        let path = state_file_path(output_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file = std::fs::File::open(&path)
+            .with_context(|| format!("failed to open state file {}", path.display()))?;
+        let raw: Value = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("failed to parse state file {}", path.display()))?;
+        let migrated = migrate(raw)
+            .with_context(|| format!("failed to migrate state file {}", path.display()))?;
+        serde_json::from_value(migrated)
+            .with_context(|| format!("failed to parse state file {}", path.display()))
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(state_dir(output_dir))?;
+        let file = std::fs::File::create(state_file_path(output_dir))?;
+        let versioned = VersionedState {
+            version: STATE_VERSION,
+            state: self,
+        };
+        serde_json::to_writer_pretty(file, &versioned)?;
+        Ok(())
+    }
+
+    // paths are stored with the output directory in front, a staged copy has to point into itself
+    pub fn rebase(&mut self, from: &Path, to: &Path) {
+        let paths = self
+            .pages
+            .values_mut()
+            .flat_map(|page| {
+                page.output_path
+                    .iter_mut()
+                    .chain(page.code_files.iter_mut())
+            })
+            .chain(
+                self.attachments
+                    .values_mut()
+                    .map(|attachment| &mut attachment.path),
+            );
+        for path in paths {
+            if let Ok(relative) = path.strip_prefix(from) {
+                *path = to.join(relative);
+            }
+        }
+    }
+
+    pub fn set_output_path(&mut self, page_id: &PageId, output_path: &Path) {
+        self.pages
+            .entry(normalize_id(&page_id.to_string()))
+            .or_default()
+            .output_path = Some(output_path.to_path_buf());
+    }
+
+    pub fn output_path(&self, page_id: &str) -> Option<PathBuf> {
+        self.pages.get(&normalize_id(page_id))?.output_path.clone()
+    }
+
+    pub fn content_hash(&self, page_id: &PageId) -> Option<&str> {
+        self.pages
+            .get(&normalize_id(&page_id.to_string()))?
+            .content_hash
+            .as_deref()
+    }
+
+    pub fn set_content_hash(&mut self, page_id: &PageId, content_hash: String) {
+        self.pages
+            .entry(normalize_id(&page_id.to_string()))
+            .or_default()
+            .content_hash = Some(content_hash);
+    }
+
+    pub fn set_links(&mut self, page_id: &PageId, link_targets: LinkTargets, links_hash: String) {
+        let state = self
+            .pages
+            .entry(normalize_id(&page_id.to_string()))
+            .or_default();
+        state.link_targets = link_targets;
+        state.links_hash = Some(links_hash);
+    }
+
+    // notes of the pages that were exported, keyed by normalized page id
+    pub fn output_paths(&self, page_ids: &[String]) -> HashMap<String, PathBuf> {
+        page_ids
+            .iter()
+            .filter_map(|page_id| Some((normalize_id(page_id), self.output_path(page_id)?)))
+            .collect()
+    }
+
+    pub fn code_files(&self, page_id: &PageId) -> Vec<PathBuf> {
+        self.pages
+            .get(&normalize_id(&page_id.to_string()))
+            .map(|page| page.code_files.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_code_files(&mut self, page_id: &PageId, code_files: Vec<PathBuf>) {
+        self.pages
+            .entry(normalize_id(&page_id.to_string()))
+            .or_default()
+            .code_files = code_files;
+    }
+
+    // `None` forgets the edit time, for notes that weren't written completely
+    pub fn set_last_edited_time(
+        &mut self,
+        page_id: &PageId,
+        last_edited_time: Option<DateTime<Utc>>,
+        child_databases: &[ChildDatabase],
+        child_pages: &[String],
+    ) {
+        let state = self
+            .pages
+            .entry(normalize_id(&page_id.to_string()))
+            .or_default();
+        state.last_edited_time = last_edited_time;
+        state.child_databases = child_databases.to_vec();
+        state.child_pages = child_pages.to_vec();
+    }
+
+    // the state of a page nobody edited since its note was written
+    pub fn unedited_page(
+        &self,
+        page_id: &PageId,
+        last_edited_time: DateTime<Utc>,
+    ) -> Option<&PageState> {
+        self.pages
+            .get(&normalize_id(&page_id.to_string()))
+            .filter(|state| state.last_edited_time == Some(last_edited_time))
+    }
+
+    // drops pages that weren't part of this run and returns them
+    pub fn remove_missing(&mut self, seen_page_ids: &HashSet<String>) -> Vec<(String, PageState)> {
+        let seen: HashSet<String> = seen_page_ids
+            .iter()
+            .map(|page_id| normalize_id(page_id))
+            .collect();
+        let missing: Vec<String> = self
+            .pages
+            .keys()
+            .filter(|page_id| !seen.contains(*page_id))
+            .cloned()
+            .collect();
+        missing
+            .into_iter()
+            .filter_map(|page_id| {
+                let page_state = self.pages.remove(&page_id)?;
+                Some((page_id, page_state))
+            })
+            .collect()
+    }
+
+    // records the current title of a page and returns all titles it was known under before
+    pub fn record_title(&mut self, page_id: &PageId, title: &str) -> Vec<String> {
+        let page_state = self
+            .pages
+            .entry(normalize_id(&page_id.to_string()))
+            .or_default();
+        if !page_state.title.is_empty()
+            && page_state.title != title
+            && !page_state.aliases.contains(&page_state.title)
+        {
+            let previous_title = page_state.title.clone();
+            page_state.aliases.push(previous_title);
+        }
+        page_state.aliases.retain(|alias| alias != title);
+        page_state.title = title.to_owned();
+        page_state.aliases.clone()
+    }
+}
+
+// brings a state file written by any older version up to the current layout
+fn migrate(mut state: Value) -> Result<Value> {
+    let version = state.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version > STATE_VERSION {
+        bail!("state file version {version} was written by a newer notion-bridge, this one understands up to {STATE_VERSION}");
+    }
+    if version < 2 {
+        state = migrate_v1_to_v2(state)?;
+    }
+    if let Some(object) = state.as_object_mut() {
+        object.remove("version");
+    }
+    Ok(state)
+}
+
+fn migrate_v1_to_v2(mut state: Value) -> Result<Value> {
+    let object = state
+        .as_object_mut()
+        .context("state file is not a json object")?;
+    if let Some(pages) = object.remove("pages") {
+        let pages = match pages {
+            Value::Object(pages) => pages,
+            _ => bail!("pages in state file are not a json object"),
+        };
+        let normalized: Map<String, Value> = pages
+            .into_iter()
+            .map(|(page_id, page_state)| (normalize_id(&page_id), page_state))
+            .collect();
+        object.insert(String::from("pages"), Value::Object(normalized));
+    }
+    object.insert(String::from("version"), Value::from(2));
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrates_v1_page_keys_to_normalized_ids() {
+        let v1 = json!({
+            "pages": {
+                "0123ABCD-4567-89ab-cdef-0123456789ab": {
+                    "title": "Meeting notes",
+                    "aliases": ["Old title"],
+                    "output_path": "output/Meeting notes.md"
+                }
+            }
+        });
+        let state: ExportState = serde_json::from_value(migrate(v1).unwrap()).unwrap();
+        let page_state = &state.pages["0123abcd456789abcdef0123456789ab"];
+        assert_eq!(page_state.title, "Meeting notes");
+        assert_eq!(page_state.aliases, vec!["Old title"]);
+        assert_eq!(
+            page_state.output_path,
+            Some(PathBuf::from("output/Meeting notes.md"))
+        );
+    }
+
+    #[test]
+    fn rebases_paths_inside_the_output_directory() {
+        let mut state: ExportState = serde_json::from_value(json!({
+            "pages": {
+                "0123abcd456789abcdef0123456789ab": {
+                    "title": "Meeting notes",
+                    "output_path": "vault/Meeting notes.md"
+                },
+                "1123abcd456789abcdef0123456789ab": {
+                    "title": "Elsewhere",
+                    "output_path": "other/Elsewhere.md"
+                }
+            },
+            "attachments": {
+                "https://example.com/a.png": { "path": "vault/attachments/a.png", "sha256": "a" }
+            }
+        }))
+        .unwrap();
+        state.rebase(Path::new("vault"), Path::new("vault.staging"));
+        assert_eq!(
+            state.output_path("0123abcd456789abcdef0123456789ab"),
+            Some(PathBuf::from("vault.staging/Meeting notes.md"))
+        );
+        assert_eq!(
+            state.output_path("1123abcd456789abcdef0123456789ab"),
+            Some(PathBuf::from("other/Elsewhere.md"))
+        );
+        assert_eq!(
+            state.attachments["https://example.com/a.png"].path,
+            PathBuf::from("vault.staging/attachments/a.png")
+        );
+    }
+
+    #[test]
+    fn migrates_v1_state_without_pages() {
+        let state: ExportState = serde_json::from_value(migrate(json!({})).unwrap()).unwrap();
+        assert!(state.pages.is_empty());
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let current = json!({
+            "version": STATE_VERSION,
+            "pages": { "0123abcd456789abcdef0123456789ab": { "title": "Page" } }
+        });
+        let state: ExportState = serde_json::from_value(migrate(current).unwrap()).unwrap();
+        assert_eq!(
+            state.pages["0123abcd456789abcdef0123456789ab"].title,
+            "Page"
+        );
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(migrate(json!({ "version": STATE_VERSION + 1 })).is_err());
+    }
+
+    #[test]
+    fn saved_state_loads_again() {
+        let output_dir =
+            std::env::temp_dir().join(format!("notion-bridge-state-test-{}", std::process::id()));
+        let mut state = ExportState::default();
+        state.pages.insert(
+            String::from("0123abcd456789abcdef0123456789ab"),
+            PageState {
+                title: String::from("Page"),
+                ..Default::default()
+            },
+        );
+        state.save(&output_dir).unwrap();
+
+        let saved: Value =
+            serde_json::from_str(&std::fs::read_to_string(state_file_path(&output_dir)).unwrap())
+                .unwrap();
+        assert_eq!(saved["version"], json!(STATE_VERSION));
+        let loaded = ExportState::load(&output_dir).unwrap();
+        assert_eq!(loaded.pages.len(), 1);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{
+    clean_attachments::{self, AttachmentMatcher},
+    configuration::{AppConfig, ExportJob, MetricProperty},
+    formats,
+    manifest::{ExportManifest, ManifestEntry},
+    properties::page_property_number,
+    state::ExportState,
+};
+use notion::models::Page;
+use serde_json::Number;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+// disk usage of one exported page
+struct PageSize {
+    title: String,
+    path: String,
+    note_bytes: u64,
+    // attachments embedded by several pages count for each of them
+    attachment_bytes: u64,
+}
+
+impl PageSize {
+    fn total(&self) -> u64 {
+        self.note_bytes + self.attachment_bytes
+    }
+}
+
+// pages without a value for a metric are left out instead of counting as zero
+pub fn page_metrics(metrics: &[MetricProperty], page: &Page) -> BTreeMap<String, Number> {
+    metrics
+        .iter()
+        .filter_map(|metric| {
+            let value = page_property_number(page, &metric.property)?;
+            Some((metric.key().to_owned(), value))
+        })
+        .collect()
+}
+
+// named sources export into subdirectories, see export_workspaces
+pub fn job_output_dirs(config: &AppConfig, job: &ExportJob) -> Vec<PathBuf> {
+    let output_dirs = if config.sources.is_empty() {
+        vec![job.export.output_dir.clone()]
+    } else {
+        config
+            .sources
+            .iter()
+            .map(|source| job.export.output_dir.join(&source.name))
+            .collect()
+    };
+    output_dirs
+        .iter()
+        .map(|output_dir| formats::notes_dir(output_dir, &job.export.formats))
+        .collect()
+}
+
+// summarizes the manifests of the last export, nothing is fetched from notion
+pub fn print_stats(config: &AppConfig, top: usize) -> anyhow::Result<()> {
+    for job in config.export_jobs() {
+        let output_dirs = job_output_dirs(config, &job);
+        let mut entries: Vec<ManifestEntry> = vec![];
+        let mut sizes: Vec<PageSize> = vec![];
+        for output_dir in &output_dirs {
+            if let Some(manifest) = ExportManifest::load(output_dir)? {
+                sizes.extend(page_sizes(output_dir, &manifest)?);
+                entries.extend(manifest.pages.into_values());
+            }
+        }
+        println!("Job {}: {} pages", job.name, entries.len());
+
+        println!(
+            "  Size on disk: {} in notes, {} in attachments",
+            format_bytes(sizes.iter().map(|size| size.note_bytes).sum()),
+            format_bytes(sizes.iter().map(|size| size.attachment_bytes).sum())
+        );
+        sizes.sort_by_key(|size| std::cmp::Reverse(size.total()));
+        println!("  Largest pages:");
+        for (rank, size) in sizes.iter().take(top).enumerate() {
+            println!(
+                "  {:>3}. {} ({}, note {}, attachments {}) {}",
+                rank + 1,
+                size.title,
+                format_bytes(size.total()),
+                format_bytes(size.note_bytes),
+                format_bytes(size.attachment_bytes),
+                size.path
+            );
+        }
+
+        for metric in &job.export.metrics {
+            let key = metric.key();
+            let mut ranked: Vec<(&ManifestEntry, f64)> = entries
+                .iter()
+                .filter_map(|entry| Some((entry, entry.metrics.get(key)?.as_f64()?)))
+                .collect();
+            ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+            println!("  Top pages by {key}:");
+            for (rank, (entry, value)) in ranked.iter().take(top).enumerate() {
+                println!(
+                    "  {:>3}. {} ({value}) {}",
+                    rank + 1,
+                    entry.title,
+                    entry.path
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// the note on disk and every attachment it embeds
+fn page_sizes(output_dir: &Path, manifest: &ExportManifest) -> anyhow::Result<Vec<PageSize>> {
+    let export_state = ExportState::load(output_dir)?;
+    let attachments = clean_attachments::attachment_files(output_dir, &export_state)?;
+    let attachment_sizes: HashMap<&str, u64> = attachments
+        .iter()
+        .filter_map(|path| {
+            let name = clean_attachments::file_name(path)?;
+            Some((name, std::fs::metadata(path).ok()?.len()))
+        })
+        .collect();
+    let matcher = AttachmentMatcher::new(&attachments)?;
+    let mut sizes = vec![];
+    for entry in manifest.pages.values() {
+        let note_path = output_dir.join(&entry.path);
+        let Ok(note) = entry.read_note(output_dir) else {
+            continue;
+        };
+        sizes.push(PageSize {
+            title: entry.title.clone(),
+            path: note_path.display().to_string(),
+            note_bytes: note.len() as u64,
+            attachment_bytes: matcher
+                .referenced(&note)
+                .into_iter()
+                .filter_map(|name| attachment_sizes.get(name))
+                .sum(),
+        });
+    }
+    Ok(sizes)
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
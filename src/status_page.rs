@@ -0,0 +1,102 @@
+use crate::{api::NotionClient, changes::ChangeSet, ids::normalize_id};
+use anyhow::Context;
+use chrono::Utc;
+use notion::ids::BlockId;
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+// notion takes at most 100 children per append, this leaves room for the summary blocks
+const MAX_LISTED_FAILURES: usize = 90;
+
+// replaces the content of the status page with a summary of the run that just finished
+// so people working in notion can see when the export last ran and what went wrong
+pub async fn write_status_page(
+    notion_client: &NotionClient,
+    page_id: &str,
+    result: &anyhow::Result<ChangeSet>,
+) -> anyhow::Result<()> {
+    let block_id = BlockId::from_str(&normalize_id(page_id))
+        .with_context(|| format!("invalid status page id {page_id}"))?;
+    for child_id in notion_client.list_block_child_ids(&block_id).await? {
+        notion_client.delete_block(&child_id).await?;
+    }
+    notion_client
+        .append_block_children(&block_id, status_blocks(result))
+        .await?;
+    Ok(())
+}
+
+fn status_blocks(result: &anyhow::Result<ChangeSet>) -> Vec<Value> {
+    let timestamp = Utc::now().format("%Y-%m-%d %H:%M UTC");
+    let changes = match result {
+        Ok(changes) => changes,
+        Err(error) => {
+            return vec![
+                callout("❌", &format!("Export failed at {timestamp}")),
+                paragraph(&format!("{error:#}")),
+            ]
+        }
+    };
+    let (icon, headline) = if changes.failed.is_empty() {
+        ("✅", format!("Last synced at {timestamp}"))
+    } else {
+        (
+            "⚠️",
+            format!(
+                "Last synced at {timestamp}, {} pages failed",
+                changes.failed.len()
+            ),
+        )
+    };
+    let mut blocks = vec![
+        callout(icon, &headline),
+        paragraph(&format!(
+            "{} added, {} updated, {} removed, {} truncated",
+            changes.added.len(),
+            changes.updated.len(),
+            changes.removed.len(),
+            changes.truncated.len()
+        )),
+    ];
+    blocks.extend(
+        changes
+            .failed
+            .iter()
+            .take(MAX_LISTED_FAILURES)
+            .map(|failure| bulleted_item(&format!("{}: {}", failure.title, failure.error))),
+    );
+    if changes.failed.len() > MAX_LISTED_FAILURES {
+        blocks.push(paragraph(&format!(
+            "and {} more",
+            changes.failed.len() - MAX_LISTED_FAILURES
+        )));
+    }
+    blocks
+}
+
+// rich text content is capped at 2000 characters per object
+fn rich_text(text: &str) -> Value {
+    let content: String = text.chars().take(2000).collect();
+    json!([{ "type": "text", "text": { "content": content } }])
+}
+
+fn callout(emoji: &str, text: &str) -> Value {
+    json!({
+        "type": "callout",
+        "callout": {
+            "rich_text": rich_text(text),
+            "icon": { "type": "emoji", "emoji": emoji },
+        }
+    })
+}
+
+fn paragraph(text: &str) -> Value {
+    json!({ "type": "paragraph", "paragraph": { "rich_text": rich_text(text) } })
+}
+
+fn bulleted_item(text: &str) -> Value {
+    json!({
+        "type": "bulleted_list_item",
+        "bulleted_list_item": { "rich_text": rich_text(text) }
+    })
+}
@@ -0,0 +1,121 @@
+use crate::{
+    api::{find_source, Source},
+    configuration::{TaskDatabase, TasksConfig},
+    ids::{normalize_id, same_id},
+    markdown::{external_link, notion_page_id_to_url},
+    properties::page_property_text,
+};
+use anyhow::{bail, Context};
+use notion::{
+    ids::DatabaseId,
+    models::{properties::PropertyValue, users::User, Page},
+};
+use std::{fmt::Write, path::Path, str::FromStr};
+
+struct Task {
+    title: String,
+    url: String,
+    status: Option<String>,
+    due: Option<String>,
+}
+
+// collects open rows assigned to the configured users into one markdown agenda
+pub async fn export_assigned_to_me(
+    sources: &[Source],
+    config: &TasksConfig,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    if config.user_ids.is_empty() {
+        bail!("no user ids configured under tasks.user_ids");
+    }
+    if config.databases.is_empty() {
+        bail!("no task databases configured under tasks.databases");
+    }
+
+    let mut agenda = String::from("# My tasks\n\n");
+    for task_database in &config.databases {
+        let source = find_source(sources, task_database.source.as_deref())?;
+        let database_id = DatabaseId::from_str(&normalize_id(&task_database.database_id))
+            .with_context(|| format!("invalid database id {}", task_database.database_id))?;
+        let database = source.client.get_database(database_id.clone()).await?;
+        let rows = source.client.query_all_rows(database_id).await?;
+
+        let mut tasks: Vec<Task> = rows
+            .iter()
+            .filter(|row| is_assigned(row, &task_database.person_property, &config.user_ids))
+            .filter_map(|row| open_task(row, task_database))
+            .collect();
+        if tasks.is_empty() {
+            continue;
+        }
+        // tasks without a due date go last
+        tasks.sort_by(|a, b| match (&a.due, &b.due) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.title.cmp(&b.title),
+        });
+
+        writeln!(agenda, "## {}\n", database.title_plain_text())?;
+        for task in tasks {
+            write!(
+                agenda,
+                "- [ ] {}",
+                external_link(Some(&task.title), &task.url)
+            )?;
+            if let Some(status) = &task.status {
+                write!(agenda, " ({status})")?;
+            }
+            if let Some(due) = &task.due {
+                write!(agenda, " 📅 {due}")?;
+            }
+            agenda.push('\n');
+        }
+        agenda.push('\n');
+    }
+
+    match output.or(config.output_file.as_deref()) {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path, agenda).await?;
+        }
+        None => print!("{agenda}"),
+    }
+    Ok(())
+}
+
+fn is_assigned(row: &Page, person_property: &str, user_ids: &[String]) -> bool {
+    let Some(PropertyValue::People { people, .. }) = row.properties.properties.get(person_property)
+    else {
+        return false;
+    };
+    people.iter().any(|user| {
+        let id = match user {
+            User::Person { common, .. } | User::Bot { common, .. } => common.id.to_string(),
+        };
+        user_ids.iter().any(|user_id| same_id(user_id, &id))
+    })
+}
+
+fn open_task(row: &Page, task_database: &TaskDatabase) -> Option<Task> {
+    let status = task_database
+        .status_property
+        .as_ref()
+        .and_then(|property| page_property_text(row, property));
+    if let Some(status) = &status {
+        if task_database.done_values.iter().any(|done| done == status) {
+            return None;
+        }
+    }
+    Some(Task {
+        title: row.title().unwrap_or_default(),
+        url: notion_page_id_to_url(&row.id),
+        status,
+        due: task_database
+            .due_property
+            .as_ref()
+            .and_then(|property| page_property_text(row, property)),
+    })
+}
@@ -0,0 +1,101 @@
+use crate::{
+    api::{find_source, Source},
+    blocks::fetch_block_tree_until,
+    capabilities::{self, Capability},
+    configuration::PageTemplate,
+    copy::copy_blocks,
+    ids::normalize_id,
+};
+use anyhow::{bail, Context};
+use notion::{
+    ids::{BlockId, PageId},
+    models::{properties::PropertyValue, Parent},
+};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+// the public api has no duplicate endpoint so the template's block tree is copied block by block
+pub async fn create_from_template(
+    sources: &[Source],
+    templates: &[PageTemplate],
+    template_name: &str,
+    title: &str,
+) -> anyhow::Result<()> {
+    let Some(template) = templates
+        .iter()
+        .find(|template| template.name == template_name)
+    else {
+        let known: Vec<&str> = templates
+            .iter()
+            .map(|template| template.name.as_str())
+            .collect();
+        bail!(
+            "unknown template {template_name}, configured templates are: {}",
+            known.join(", ")
+        );
+    };
+    let notion_client = &find_source(sources, template.source.as_deref())?.client;
+    notion_client.ensure_writable()?;
+
+    let template_id = PageId::from_str(&normalize_id(&template.page_id))
+        .with_context(|| format!("invalid template page id {}", template.page_id))?;
+    let template_page = notion_client.get_page(template_id.clone()).await?;
+    capabilities::require(notion_client, Capability::InsertContent, &template_id).await?;
+
+    let parent = match (&template.parent_page_id, &template_page.parent) {
+        (Some(parent_page_id), _) => json!({ "page_id": normalize_id(parent_page_id) }),
+        (None, Parent::Page { page_id }) => json!({ "page_id": page_id.to_string() }),
+        (None, Parent::Database { database_id }) => {
+            json!({ "database_id": database_id.to_string() })
+        }
+        _ => bail!("template {template_name} has no page or database parent, set parent_page_id"),
+    };
+    // database rows name their title property freely, pages below a page only take `title`
+    let title_property = if parent.get("database_id").is_some() {
+        template_page
+            .properties
+            .properties
+            .iter()
+            .find(|(_, value)| matches!(value, PropertyValue::Title { .. }))
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| String::from("title"))
+    } else {
+        String::from("title")
+    };
+
+    let mut body = json!({
+        "parent": parent,
+        "properties": {
+            title_property: { "title": [{ "text": { "content": title } }] }
+        },
+    });
+    // uploaded icons can't be reused, only emojis and external links
+    if let Some(icon) = &template_page.icon {
+        let icon = serde_json::to_value(icon)?;
+        if icon.get("type").and_then(Value::as_str) != Some("file") {
+            body["icon"] = icon;
+        }
+    }
+
+    let created_page = notion_client.create_page(&body).await?;
+    let new_page_id = created_page
+        .get("id")
+        .and_then(Value::as_str)
+        .context("created page has no id")?;
+
+    let tree = fetch_block_tree_until(notion_client, template_id.into(), None, true).await?;
+    copy_blocks(
+        notion_client,
+        BlockId::from_str(new_page_id)?,
+        &tree.raw,
+        false,
+    )
+    .await?;
+
+    let url = created_page
+        .get("url")
+        .and_then(Value::as_str)
+        .unwrap_or(new_page_id);
+    println!("Created {title} {url}");
+    Ok(())
+}
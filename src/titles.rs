@@ -0,0 +1,103 @@
+use crate::{
+    api::NotionClient, configuration::TitleSource, ids::same_id, properties::page_property_text,
+    rich_text::plain_text, workspace::Workspace,
+};
+use notion::models::{
+    block::Block,
+    properties::PropertyValue,
+    text::{RichText, RichTextCommon, Text},
+    Page, Parent,
+};
+
+// the rest of the export only ever looks at the title property
+// so pages of configured databases get their title property replaced up front
+// and file names, headings, frontmatter, links and the manifest all follow
+pub async fn retitle_workspace(
+    notion_client: &NotionClient,
+    title_sources: &[TitleSource],
+    workspace: &Workspace,
+) -> Workspace {
+    let mut retitled = workspace.clone();
+    for page in retitled.pages.iter_mut() {
+        retitle_page(notion_client, title_sources, page).await;
+    }
+    retitled
+}
+
+pub async fn retitle_page(
+    notion_client: &NotionClient,
+    title_sources: &[TitleSource],
+    page: &mut Page,
+) {
+    let Some(title_source) = title_source(title_sources, page) else {
+        return;
+    };
+    let mut title = title_source
+        .property
+        .as_ref()
+        .and_then(|property| page_property_text(page, property))
+        .filter(|title| !title.trim().is_empty());
+    if title.is_none() && title_source.first_heading {
+        // one page that can't be read shouldn't stop the job, it keeps its notion title
+        title = first_heading(notion_client, page)
+            .await
+            .unwrap_or_else(|error| {
+                eprintln!(
+                    "Failed to read first heading of page {} with error {error:?}",
+                    page.id
+                );
+                None
+            });
+    }
+    // pages without a value keep the title they have in notion
+    if let Some(title) = title {
+        set_title(page, title.trim());
+    }
+}
+
+fn title_source<'a>(title_sources: &'a [TitleSource], page: &Page) -> Option<&'a TitleSource> {
+    let Parent::Database { database_id } = &page.parent else {
+        return None;
+    };
+    title_sources
+        .iter()
+        .find(|source| same_id(&source.database_id, &database_id.to_string()))
+}
+
+// only the first batch of blocks is fetched, a title heading further down doesn't count
+async fn first_heading(
+    notion_client: &NotionClient,
+    page: &Page,
+) -> anyhow::Result<Option<String>> {
+    let blocks = notion_client
+        .get_block_children(page.id.clone().into())
+        .await?;
+    Ok(blocks.results.iter().find_map(|block| match block {
+        Block::Heading1 { heading_1, .. } => Some(plain_text(&heading_1.rich_text)),
+        _ => None,
+    }))
+}
+
+fn set_title(page: &mut Page, title: &str) {
+    let title_property = page
+        .properties
+        .properties
+        .values_mut()
+        .find_map(|value| match value {
+            PropertyValue::Title { title, .. } => Some(title),
+            _ => None,
+        });
+    if let Some(rich_text) = title_property {
+        *rich_text = vec![RichText::Text {
+            rich_text: RichTextCommon {
+                plain_text: title.to_owned(),
+                href: None,
+                annotations: None,
+            },
+            text: Text {
+                content: title.to_owned(),
+                link: None,
+            },
+        }];
+    }
+}
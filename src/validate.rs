@@ -0,0 +1,296 @@
+use crate::{
+    api::{self, Source},
+    configuration::{user_config_file_path, AppConfig},
+    i18n::Text,
+    ids::{is_valid_id, normalize_id},
+};
+use notion::ids::{DatabaseId, PageId};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectKind {
+    Page,
+    Database,
+    // roots may point at either
+    PageOrDatabase,
+}
+
+struct IdReference {
+    setting: String,
+    id: String,
+    kind: ObjectKind,
+}
+
+struct Problem {
+    line: Option<usize>,
+    message: String,
+}
+
+// checks everything a long run would trip over and returns whether the config is usable
+pub async fn validate_config() -> anyhow::Result<bool> {
+    let path = user_config_file_path()?;
+    println!("Validating {}", path.display());
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("error: failed to read config file: {error}");
+            return Ok(false);
+        }
+    };
+    let config: AppConfig = match serde_yaml::from_str(&text) {
+        Ok(config) => config,
+        Err(error) => {
+            let location = error
+                .location()
+                .map(|location| format!("{}:{}: ", location.line(), location.column()))
+                .unwrap_or_default();
+            println!("error: {location}{error}");
+            return Ok(false);
+        }
+    };
+
+    let mut problems = vec![];
+    let references = id_references(&config);
+    for reference in &references {
+        if !is_valid_id(&reference.id) {
+            problems.push(Problem {
+                line: line_of(&text, &reference.id),
+                message: format!(
+                    "{} `{}` is not a notion id",
+                    reference.setting, reference.id
+                ),
+            });
+        }
+    }
+
+    match api::connect_sources(&config, None) {
+        Ok(sources) => {
+            for reference in references
+                .iter()
+                .filter(|reference| is_valid_id(&reference.id))
+            {
+                if !object_exists(&sources, reference).await {
+                    problems.push(Problem {
+                        line: line_of(&text, &reference.id),
+                        message: format!(
+                            "{} `{}` doesn't exist or isn't shared with any configured integration",
+                            reference.setting, reference.id
+                        ),
+                    });
+                }
+            }
+        }
+        Err(error) => problems.push(Problem {
+            line: None,
+            message: format!("{error:#}"),
+        }),
+    }
+
+    for job in config.export_jobs() {
+        for key in job.export.translations.keys() {
+            if !Text::ALL.iter().any(|known| known.key() == key) {
+                problems.push(Problem {
+                    line: line_of(&text, key),
+                    message: format!("translations `{key}` is not a known text"),
+                });
+            }
+        }
+    }
+
+    for (setting, directory) in writable_directories(&config) {
+        if let Err(error) = check_writable(&directory) {
+            problems.push(Problem {
+                line: line_of(&text, &directory.to_string_lossy()),
+                message: format!("{setting} {} is not writable: {error}", directory.display()),
+            });
+        }
+    }
+
+    if problems.is_empty() {
+        println!("Config is valid, checked {} ids", references.len());
+        return Ok(true);
+    }
+    for problem in &problems {
+        match problem.line {
+            Some(line) => println!("error: line {line}: {}", problem.message),
+            None => println!("error: {}", problem.message),
+        }
+    }
+    println!("Found {} problems", problems.len());
+    Ok(false)
+}
+
+fn id_references(config: &AppConfig) -> Vec<IdReference> {
+    let mut references = vec![];
+    let mut add = |setting: &str, id: &str, kind: ObjectKind| {
+        references.push(IdReference {
+            setting: setting.to_owned(),
+            id: id.to_owned(),
+            kind,
+        })
+    };
+    for job in config.export_jobs() {
+        let prefix = if config.jobs.is_empty() {
+            String::from("export")
+        } else {
+            format!("jobs.{}", job.name)
+        };
+        for root in &job.export.roots {
+            add(&format!("{prefix}.roots"), root, ObjectKind::PageOrDatabase);
+        }
+        for exclude in &job.export.excludes {
+            add(
+                &format!("{prefix}.excludes"),
+                exclude,
+                ObjectKind::PageOrDatabase,
+            );
+        }
+        for daily_notes in &job.export.daily_note_databases {
+            add(
+                &format!("{prefix}.daily_note_databases"),
+                &daily_notes.database_id,
+                ObjectKind::Database,
+            );
+        }
+        for wiki in &job.export.wikis {
+            add(
+                &format!("{prefix}.wikis"),
+                &wiki.database_id,
+                ObjectKind::Database,
+            );
+        }
+        for title_source in &job.export.title_sources {
+            add(
+                &format!("{prefix}.title_sources"),
+                &title_source.database_id,
+                ObjectKind::Database,
+            );
+        }
+        if let Some(glossary) = &job.export.glossary {
+            add(
+                &format!("{prefix}.glossary"),
+                &glossary.database_id,
+                ObjectKind::Database,
+            );
+        }
+        for mapping in &job.export.frontmatter_mappings {
+            add(
+                &format!("{prefix}.frontmatter_mappings"),
+                &mapping.database_id,
+                ObjectKind::Database,
+            );
+        }
+        for database_export in &job.export.database_exports {
+            add(
+                &format!("{prefix}.database_exports"),
+                &database_export.database_id,
+                ObjectKind::Database,
+            );
+        }
+    }
+    if let Some(config_page) = &config.config_page {
+        add("config_page", config_page, ObjectKind::Page);
+    }
+    if let Some(status_page) = &config.status_page {
+        add("status_page", status_page, ObjectKind::Page);
+    }
+    for task_database in &config.tasks.databases {
+        add(
+            "tasks.databases",
+            &task_database.database_id,
+            ObjectKind::Database,
+        );
+    }
+    for template in &config.templates {
+        add(
+            &format!("templates.{}", template.name),
+            &template.page_id,
+            ObjectKind::Page,
+        );
+        if let Some(parent_page_id) = &template.parent_page_id {
+            add(
+                &format!("templates.{}.parent_page_id", template.name),
+                parent_page_id,
+                ObjectKind::Page,
+            );
+        }
+    }
+    for rule in &config.watch.rules {
+        for root in &rule.roots {
+            add("watch.rules.roots", root, ObjectKind::PageOrDatabase);
+        }
+        if let Some(database_id) = &rule.database_id {
+            add("watch.rules.database_id", database_id, ObjectKind::Database);
+        }
+    }
+    references
+}
+
+// any source seeing the object is enough, jobs export every source
+async fn object_exists(sources: &[Source], reference: &IdReference) -> bool {
+    let id = normalize_id(&reference.id);
+    for source in sources {
+        let is_page = || async {
+            match PageId::from_str(&id) {
+                Ok(page_id) => source.client.get_page(page_id).await.is_ok(),
+                Err(_) => false,
+            }
+        };
+        let is_database = || async {
+            match DatabaseId::from_str(&id) {
+                Ok(database_id) => source.client.get_database(database_id).await.is_ok(),
+                Err(_) => false,
+            }
+        };
+        let found = match reference.kind {
+            ObjectKind::Page => is_page().await,
+            ObjectKind::Database => is_database().await,
+            ObjectKind::PageOrDatabase => is_page().await || is_database().await,
+        };
+        if found {
+            return true;
+        }
+    }
+    false
+}
+
+fn writable_directories(config: &AppConfig) -> Vec<(String, PathBuf)> {
+    let mut directories: Vec<(String, PathBuf)> = config
+        .export_jobs()
+        .into_iter()
+        .map(|job| (format!("output_dir of {}", job.name), job.export.output_dir))
+        .collect();
+    let parent_of = |path: &Path| {
+        path.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."))
+    };
+    if let Some(changelog_file) = &config.watch.changelog_file {
+        directories.push((
+            String::from("watch.changelog_file"),
+            parent_of(changelog_file),
+        ));
+    }
+    if let Some(output_file) = &config.tasks.output_file {
+        directories.push((String::from("tasks.output_file"), parent_of(output_file)));
+    }
+    directories
+}
+
+// creates the directory like an export would and leaves nothing behind but the directory
+fn check_writable(directory: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(directory)?;
+    let probe = directory.join(format!(".notion-bridge-probe-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+fn line_of(text: &str, needle: &str) -> Option<usize> {
+    text.lines()
+        .position(|line| line.contains(needle))
+        .map(|index| index + 1)
+}
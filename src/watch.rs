@@ -0,0 +1,289 @@
+use crate::{
+    api::Source,
+    changes::{ChangeSet, PageChange},
+    configuration::{AppConfig, WatchConfig, WatchRule},
+    daemon::{self, ConfigLoader, ConfigWatcher, HangupSignal},
+    export::{self, RunOptions},
+    ids::same_id,
+    properties::page_property_text,
+    workspace::{Workspace, WorkspaceSnapshot},
+};
+use notion::models::Parent;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub async fn watch(
+    config_loader: &ConfigLoader,
+    mut sources: Vec<Source>,
+    mut config: AppConfig,
+    interval_override: Option<u64>,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    let mut hangup = HangupSignal::new()?;
+    let mut config_watcher = ConfigWatcher::new();
+    let mut poll = Poll::default();
+    loop {
+        // keep watching on failures, the next cycle might work again
+        match poll.search(&sources, &config).await {
+            Ok(None) => println!("Nothing edited since the last cycle"),
+            Ok(Some(workspaces)) => {
+                match export::export_workspaces(&sources, workspaces, &config, options).await {
+                    Ok(changes) if changes.is_empty() => println!("Nothing changed"),
+                    Ok(changes) => report_changes(&config.watch, workspaces, &changes).await,
+                    Err(error) => eprintln!("Export cycle failed: {error:#}"),
+                }
+            }
+            Err(error) => eprintln!("Workspace search failed: {error:#}"),
+        }
+        let interval =
+            Duration::from_secs(interval_override.unwrap_or(config.watch.interval_seconds));
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = hangup.recv() => {
+                println!("Received SIGHUP, reloading the config and syncing");
+                reload(config_loader, &mut config, &mut sources);
+                poll = Poll::default();
+            }
+            _ = config_watcher.changed() => {
+                println!("Config file changed, reloading it and syncing");
+                reload(config_loader, &mut config, &mut sources);
+                poll = Poll::default();
+            }
+        }
+    }
+}
+
+// the search results of every source, kept in memory between cycles
+#[derive(Default)]
+struct Poll {
+    snapshots: Vec<Option<WorkspaceSnapshot>>,
+    workspaces: Vec<Workspace>,
+}
+
+impl Poll {
+    // None when no page was edited since the last cycle
+    async fn search(
+        &mut self,
+        sources: &[Source],
+        config: &AppConfig,
+    ) -> anyhow::Result<Option<&[Workspace]>> {
+        self.snapshots.resize(sources.len(), None);
+        let mut snapshots = vec![];
+        let mut edited = false;
+        for (source, snapshot) in sources.iter().zip(self.snapshots.drain(..)) {
+            let (snapshot, source_edited) =
+                export::search_workspace_incremental(&source.client, snapshot, config).await?;
+            snapshots.push(Some(snapshot));
+            edited |= source_edited;
+        }
+        self.snapshots = snapshots;
+        if !edited {
+            return Ok(None);
+        }
+        self.workspaces = self
+            .snapshots
+            .iter()
+            .flatten()
+            .map(|snapshot| snapshot.clone().into_workspace())
+            .collect();
+        Ok(Some(&self.workspaces))
+    }
+}
+
+// a broken config keeps the daemon running with the one it had
+pub fn reload(config_loader: &ConfigLoader, config: &mut AppConfig, sources: &mut Vec<Source>) {
+    match config_loader.reload() {
+        Ok((new_config, new_sources)) => {
+            match daemon::config_changes(config, &new_config) {
+                Ok(changes) if changes.is_empty() => println!("Config unchanged"),
+                Ok(changes) => {
+                    for change in changes {
+                        println!("Config changed {change}");
+                    }
+                }
+                Err(error) => eprintln!("Failed to compare configs with error {error:?}"),
+            }
+            *config = new_config;
+            *sources = new_sources;
+        }
+        Err(error) => {
+            eprintln!("Failed to reload the config, keeping the old one. Error {error:?}")
+        }
+    }
+}
+
+pub async fn report_changes(
+    watch_config: &WatchConfig,
+    workspaces: &[Workspace],
+    changes: &ChangeSet,
+) {
+    let changes = if watch_config.include_diffs {
+        changes.clone()
+    } else {
+        changes.without_diffs()
+    };
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    println!(
+        "{} added, {} updated, {} removed",
+        changes.added.len(),
+        changes.updated.len(),
+        changes.removed.len()
+    );
+
+    if let Some(changelog_file) = &watch_config.changelog_file {
+        let entry = format_changelog_entry(&timestamp, &changes);
+        if let Err(error) = append_changelog(watch_config, changelog_file, &entry) {
+            eprintln!("Failed to write changelog with error {error:?}");
+        }
+    }
+
+    let changes = filter_changes(&watch_config.rules, workspaces, &changes);
+    if changes.is_empty() {
+        return;
+    }
+
+    if let Some(webhook_url) = &watch_config.webhook_url {
+        let payload = serde_json::json!({
+            "timestamp": timestamp,
+            "changes": changes,
+        });
+        if let Err(error) = post_webhook(webhook_url, &payload).await {
+            eprintln!("Failed to call changelog webhook with error {error:?}");
+        }
+    }
+}
+
+fn filter_changes(rules: &[WatchRule], workspaces: &[Workspace], changes: &ChangeSet) -> ChangeSet {
+    if rules.is_empty() {
+        return changes.clone();
+    }
+    let matching = |page_changes: &[PageChange]| {
+        page_changes
+            .iter()
+            .filter(|change| {
+                rules
+                    .iter()
+                    .any(|rule| rule_matches(rule, workspaces, change))
+            })
+            .cloned()
+            .collect()
+    };
+    ChangeSet {
+        added: matching(&changes.added),
+        updated: matching(&changes.updated),
+        removed: matching(&changes.removed),
+        truncated: changes.truncated.clone(),
+        failed: changes.failed.clone(),
+    }
+}
+
+fn rule_matches(rule: &WatchRule, workspaces: &[Workspace], change: &PageChange) -> bool {
+    let found = workspaces
+        .iter()
+        .find_map(|workspace| Some((workspace, workspace.page(&change.page_id)?)));
+    // removed pages are gone from the workspace so only unconditional rules can match them
+    let Some((workspace, page)) = found else {
+        return rule.roots.is_empty() && rule.database_id.is_none() && rule.property.is_none();
+    };
+    if !rule.roots.is_empty() && !workspace.is_under_root(page, &rule.roots) {
+        return false;
+    }
+    if let Some(database_id) = &rule.database_id {
+        match &page.parent {
+            Parent::Database {
+                database_id: parent_id,
+            } if same_id(database_id, &parent_id.to_string()) => {}
+            _ => return false,
+        }
+    }
+    if let Some(property) = &rule.property {
+        let value = page_property_text(page, property);
+        match &rule.equals {
+            Some(expected) => value.as_deref() == Some(expected.as_str()),
+            None => value.is_some(),
+        }
+    } else {
+        true
+    }
+}
+
+fn format_changelog_entry(timestamp: &str, changes: &ChangeSet) -> String {
+    let mut entry = format!("## {timestamp}\n");
+    let sections = [
+        ("added", &changes.added),
+        ("updated", &changes.updated),
+        ("removed", &changes.removed),
+        ("truncated", &changes.truncated),
+    ];
+    for (label, page_changes) in sections {
+        for change in page_changes {
+            let path = change
+                .path
+                .as_ref()
+                .map(|path| format!(" ({})", path.display()))
+                .unwrap_or_default();
+            entry.push_str(&format!("{label}: {}{path}\n", change.title));
+            if let Some(diff) = &change.diff {
+                entry.push_str(diff);
+            }
+        }
+    }
+    entry.push('\n');
+    entry
+}
+
+fn append_changelog(watch_config: &WatchConfig, path: &Path, entry: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let current_size = std::fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    if current_size + entry.len() as u64 > watch_config.changelog_max_bytes {
+        rotate_logs(path, watch_config.changelog_keep)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(entry.as_bytes())?;
+    Ok(())
+}
+
+// changes.log -> changes.log.1 -> changes.log.2 ... dropping the oldest
+fn rotate_logs(path: &Path, keep: usize) -> anyhow::Result<()> {
+    let rotated_path = |index: usize| -> PathBuf {
+        let mut file_name = path.as_os_str().to_owned();
+        file_name.push(format!(".{index}"));
+        PathBuf::from(file_name)
+    };
+    if keep == 0 {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    for index in (1..keep).rev() {
+        let from = rotated_path(index);
+        if from.exists() {
+            std::fs::rename(&from, rotated_path(index + 1))?;
+        }
+    }
+    if path.exists() {
+        std::fs::rename(path, rotated_path(1))?;
+    }
+    Ok(())
+}
+
+async fn post_webhook(url: &str, payload: &serde_json::Value) -> anyhow::Result<()> {
+    reqwest::Client::new()
+        .post(url)
+        .json(payload)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use crate::{
+    api::NotionClient,
+    configuration::{ExportConfig, FolderIcons, WikiDatabase},
+    escape, folder_icons,
+    frontmatter::Frontmatter,
+    i18n::{self, Text},
+    ids::same_id,
+    layout::note_file_name,
+    workspace::Workspace,
+};
+use notion::models::{Page, Parent};
+use serde_json::Value;
+use std::{collections::HashSet, path::PathBuf};
+
+// the wiki database a page is a row of
+pub fn wiki_database<'a>(wikis: &'a [WikiDatabase], page: &Page) -> Option<&'a WikiDatabase> {
+    let Parent::Database { database_id } = &page.parent else {
+        return None;
+    };
+    wikis
+        .iter()
+        .find(|wiki| same_id(&wiki.database_id, &database_id.to_string()))
+}
+
+// wiki pages are nested like in the notion sidebar
+// <wiki title>/<parent page>/<page>.md with every parent next to its own folder
+pub fn wiki_page_path(
+    config: &ExportConfig,
+    workspace: &Workspace,
+    page: &Page,
+) -> Option<PathBuf> {
+    let mut ancestors = vec![];
+    let mut current = page;
+    let mut visited = HashSet::new();
+    let database_id = loop {
+        if !visited.insert(current.id.to_string()) {
+            return None;
+        }
+        match &current.parent {
+            Parent::Database { database_id } => break database_id.to_string(),
+            Parent::Page { page_id } => {
+                current = workspace.page(&page_id.to_string())?;
+                let title = current.title().unwrap_or_else(|| current.id.to_string());
+                ancestors.push(match config.folder_icons {
+                    FolderIcons::Prefix => folder_icons::folder_name(current, &title),
+                    _ => title,
+                });
+            }
+            _ => return None,
+        }
+    };
+    if !config
+        .wikis
+        .iter()
+        .any(|wiki| same_id(&wiki.database_id, &database_id))
+    {
+        return None;
+    }
+
+    let wiki_title = workspace
+        .database(&database_id)
+        .map(|database| database.title_plain_text())
+        .filter(|title| !title.is_empty())
+        .unwrap_or(database_id);
+    let mut path = config.output_dir.join(escape::file_name(&wiki_title));
+    for ancestor in ancestors.iter().rev() {
+        path.push(escape::file_name(ancestor));
+    }
+    let title = page.title().unwrap_or_else(|| page.id.to_string());
+    path.push(note_file_name(&title));
+    Some(path)
+}
+
+// the crate doesn't model verification properties so they are read from the raw page
+pub async fn add_wiki_frontmatter(
+    notion_client: &NotionClient,
+    config: &ExportConfig,
+    wiki: &WikiDatabase,
+    page: &Page,
+    frontmatter: &mut Frontmatter,
+) -> anyhow::Result<()> {
+    let raw_page = notion_client.get_page_json(&page.id).await?;
+    let properties = &raw_page["properties"];
+
+    let owners: Vec<String> = properties[&wiki.owner_property]["people"]
+        .as_array()
+        .map(|people| people.iter().filter_map(user_name).collect())
+        .unwrap_or_default();
+    if !owners.is_empty() {
+        frontmatter.insert(i18n::text(config, Text::Owners), owners)?;
+    }
+
+    let verification = &properties[&wiki.verification_property]["verification"];
+    if let Some(state) = verification["state"].as_str() {
+        frontmatter.insert(i18n::text(config, Text::Verification), state)?;
+    }
+    if let Some(verified_by) = user_name(&verification["verified_by"]) {
+        frontmatter.insert(i18n::text(config, Text::VerifiedBy), verified_by)?;
+    }
+    if let Some(verified_at) = verification["date"]["start"].as_str() {
+        frontmatter.insert(i18n::text(config, Text::VerifiedAt), verified_at)?;
+    }
+    // unset for verifications that never expire
+    if let Some(verified_until) = verification["date"]["end"].as_str() {
+        frontmatter.insert(i18n::text(config, Text::VerifiedUntil), verified_until)?;
+    }
+    Ok(())
+}
+
+// user objects on pages only carry the name when the integration can read user information
+fn user_name(user: &Value) -> Option<String> {
+    user["name"]
+        .as_str()
+        .or_else(|| user["id"].as_str())
+        .map(str::to_owned)
+}
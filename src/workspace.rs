@@ -0,0 +1,176 @@
+use crate::{
+    configuration::get_project_dirs,
+    ids::{normalize_id, same_id},
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use notion::models::{Database, Page, Parent};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+// the snapshot is only a cache so an outdated one is thrown away instead of migrated
+const SNAPSHOT_VERSION: u64 = 1;
+
+// search results from the last run so incremental runs only have to ask for newer edits
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkspaceSnapshot {
+    #[serde(default)]
+    pub version: u64,
+    pub synced_at: DateTime<Utc>,
+    // last time every object was searched, not just the edited ones
+    #[serde(default)]
+    pub full_search_at: Option<DateTime<Utc>>,
+    pub pages: Vec<Page>,
+    pub databases: Vec<Database>,
+}
+
+impl WorkspaceSnapshot {
+    pub fn path(source_name: Option<&str>) -> anyhow::Result<PathBuf> {
+        let file_name = format!("workspace-{}.json", source_name.unwrap_or("default"));
+        Ok(get_project_dirs()?.cache_dir().join(file_name))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = std::fs::File::open(path)?;
+        let snapshot: Self = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("failed to parse workspace snapshot {}", path.display()))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Ok(None);
+        }
+        Ok(Some(snapshot))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn into_workspace(self) -> Workspace {
+        let mut workspace = Workspace::default();
+        for page in self.pages {
+            workspace.add_page(page);
+        }
+        for database in self.databases {
+            workspace.add_database(database);
+        }
+        workspace
+    }
+}
+
+// everything the search api returned, shared between export jobs
+#[derive(Debug, Default, Clone)]
+pub struct Workspace {
+    pub pages: Vec<Page>,
+    pub databases: Vec<Database>,
+    page_index: HashMap<String, usize>,
+}
+
+impl Workspace {
+    pub fn add_page(&mut self, page: Page) {
+        self.page_index
+            .insert(normalize_id(&page.id.to_string()), self.pages.len());
+        self.pages.push(page);
+    }
+
+    pub fn add_database(&mut self, database: Database) {
+        self.databases.push(database);
+    }
+
+    pub fn has_database(&self, id: &str) -> bool {
+        self.databases
+            .iter()
+            .any(|database| same_id(id, &database.id.to_string()))
+    }
+
+    // newer search results replace what we knew about the same objects
+    pub fn merge(&mut self, newer: Workspace) {
+        for page in newer.pages {
+            match self.page_index.get(&normalize_id(&page.id.to_string())) {
+                Some(index) => self.pages[*index] = page,
+                None => self.add_page(page),
+            }
+        }
+        for database in newer.databases {
+            let existing = self
+                .databases
+                .iter_mut()
+                .find(|known| known.id == database.id);
+            match existing {
+                Some(known) => *known = database,
+                None => self.add_database(database),
+            }
+        }
+    }
+
+    pub fn snapshot(
+        &self,
+        synced_at: DateTime<Utc>,
+        full_search_at: Option<DateTime<Utc>>,
+    ) -> WorkspaceSnapshot {
+        WorkspaceSnapshot {
+            version: SNAPSHOT_VERSION,
+            synced_at,
+            full_search_at,
+            pages: self.pages.clone(),
+            databases: self.databases.clone(),
+        }
+    }
+
+    pub fn page(&self, id: &str) -> Option<&Page> {
+        self.page_index
+            .get(&normalize_id(id))
+            .map(|index| &self.pages[*index])
+    }
+
+    pub fn database(&self, id: &str) -> Option<&Database> {
+        self.databases
+            .iter()
+            .find(|database| same_id(id, &database.id.to_string()))
+    }
+
+    // pages that are one of the roots or live somewhere below them
+    // an empty root list selects the whole workspace
+    pub fn pages_under_roots(&self, roots: &[String], excludes: &[String]) -> Vec<&Page> {
+        self.pages
+            .iter()
+            .filter(|page| roots.is_empty() || self.is_under_root(page, roots))
+            .filter(|page| excludes.is_empty() || !self.is_under_root(page, excludes))
+            .collect()
+    }
+
+    pub fn is_under_root(&self, page: &Page, roots: &[String]) -> bool {
+        let mut current_id = page.id.to_string();
+        let mut current_parent = Some(page.parent.clone());
+        let mut visited = HashSet::new();
+        loop {
+            if roots.iter().any(|root| same_id(root, &current_id)) {
+                return true;
+            }
+            if !visited.insert(normalize_id(&current_id)) {
+                return false;
+            }
+            match current_parent {
+                Some(Parent::Page { page_id }) => {
+                    current_id = page_id.to_string();
+                    current_parent = self.page(&current_id).map(|page| page.parent.clone());
+                }
+                Some(Parent::Database { database_id }) => {
+                    // database objects don't tell us their parent so the walk stops here
+                    current_id = database_id.to_string();
+                    current_parent = None;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
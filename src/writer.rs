@@ -0,0 +1,54 @@
+use std::fmt::Write;
+
+// writes the current stack of prefixes in front of every line
+// nested contexts (quotes, callouts, list children) push a prefix and pop it when done
+pub struct PrefixedWriter<'a> {
+    inner: &'a mut dyn Write,
+    prefixes: Vec<String>,
+    at_line_start: bool,
+}
+
+impl<'a> PrefixedWriter<'a> {
+    pub fn new(inner: &'a mut dyn Write) -> Self {
+        Self {
+            inner,
+            prefixes: Vec::new(),
+            at_line_start: true,
+        }
+    }
+
+    pub fn push_prefix(&mut self, prefix: &str) {
+        self.prefixes.push(prefix.to_owned());
+    }
+
+    pub fn pop_prefix(&mut self) {
+        self.prefixes.pop();
+    }
+
+    pub fn push_indent(&mut self, width: usize) {
+        self.prefixes.push(" ".repeat(width));
+    }
+
+    fn write_prefix(&mut self, empty_line: bool) -> std::fmt::Result {
+        let prefix = self.prefixes.concat();
+        if empty_line {
+            // avoid trailing whitespace on empty lines
+            self.inner.write_str(prefix.trim_end())
+        } else {
+            self.inner.write_str(&prefix)
+        }
+    }
+}
+
+impl Write for PrefixedWriter<'_> {
+    fn write_str(&mut self, text: &str) -> std::fmt::Result {
+        for line in text.split_inclusive('\n') {
+            if self.at_line_start {
+                self.write_prefix(line == "\n")?;
+            }
+            self.inner.write_str(line)?;
+            self.at_line_start = line.ends_with('\n');
+        }
+        Ok(())
+    }
+}